@@ -0,0 +1,51 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rdb::filter::Simple;
+use rdb::formatter::Nil;
+use rdb::{BorrowedValue, ParallelDecoder, RdbDecoder, SliceDecoder, Type};
+use regex::Regex;
+
+// The first byte of the input selects the decoder options, the rest is the dump
+fuzz_target!(|data: &[u8]| {
+    let Some((&options, dump)) = data.split_first() else {
+        return;
+    };
+
+    let mut filter = Simple::new();
+    if options & 0x04 != 0 {
+        filter.add_database(0);
+    }
+    if options & 0x08 != 0 {
+        filter.add_type(Type::Hash);
+    }
+    if options & 0x10 != 0 {
+        filter.add_keys(Regex::new("^[a-m]").unwrap());
+    }
+
+    if options & 0x80 != 0 {
+        if let Ok(mut decoder) = ParallelDecoder::new(dump, filter) {
+            decoder.threads(2);
+            decoder.ordered(false);
+            decoder.skip_corrupt_keys(true);
+            decoder.verify_checksum(options & 0x20 != 0);
+            let _ = decoder.parse(Nil::new(None));
+        }
+    } else if options & 0x40 != 0 {
+        if let Ok(mut decoder) = SliceDecoder::new(dump, filter) {
+            decoder.skip_corrupt_keys(options & 0x01 != 0);
+            decoder.verify_checksum(options & 0x20 != 0);
+            // Decompress the strings left compressed in the dump
+            for value in decoder {
+                let _ = value.and_then(BorrowedValue::into_owned);
+            }
+        }
+    } else if let Ok(mut decoder) = RdbDecoder::new(dump, filter) {
+        decoder.skip_corrupt_keys(options & 0x01 != 0);
+        decoder.stream_elements(options & 0x02 != 0);
+        decoder.verify_checksum(options & 0x20 != 0);
+        for value in decoder {
+            let _ = value;
+        }
+    }
+});
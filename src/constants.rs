@@ -1,16 +1,31 @@
 pub mod version {
     pub const SUPPORTED_MINIMUM: u32 = 1;
+    // Newest Redis version whose types and opcodes are known. Newer Redis
+    // versions are accepted as long as they only contain known constructs.
     pub const SUPPORTED_MAXIMUM: u32 = 12;
+    // Valkey leaves the versions up to 79 to Redis and numbers its own from 80
+    pub const VALKEY_MINIMUM: u32 = 80;
+    pub const VALKEY_MAXIMUM: u32 = 80;
+    // Redis version Valkey forked from, Valkey versions contain the same constructs
+    pub const VALKEY_BASE: u32 = 11;
+    // First version ending with a CRC64 checksum
+    pub const CHECKSUM_MINIMUM: u32 = 5;
 }
 
 pub mod constant {
     pub const RDB_6BITLEN: u8 = 0;
     pub const RDB_14BITLEN: u8 = 1;
     pub const RDB_ENCVAL: u8 = 3;
+    pub const RDB_32BITLEN: u8 = 0x80;
+    pub const RDB_64BITLEN: u8 = 0x81;
     pub const RDB_MAGIC: &str = "REDIS";
+    pub const VALKEY_MAGIC: &str = "VALKEY";
 }
 
 pub mod op_code {
+    pub const SLOT_INFO: u8 = 244;
+    pub const FUNCTION2: u8 = 245;
+    pub const FUNCTION_PRE_GA: u8 = 246;
     pub const MODULE_AUX: u8 = 247;
     pub const IDLE: u8 = 248;
     pub const FREQ: u8 = 249;
@@ -44,6 +59,10 @@ pub mod encoding_type {
     pub const STREAM_LIST_PACKS_2: u8 = 19;
     pub const SET_LIST_PACK: u8 = 20;
     pub const STREAM_LIST_PACKS_3: u8 = 21;
+    pub const HASH_METADATA_PRE_GA: u8 = 22;
+    pub const HASH_LIST_PACK_EX_PRE_GA: u8 = 23;
+    pub const HASH_METADATA: u8 = 24;
+    pub const HASH_LIST_PACK_EX: u8 = 25;
 }
 
 pub mod encoding {
@@ -52,3 +71,12 @@ pub mod encoding {
     pub const INT32: u32 = 2;
     pub const LZF: u32 = 3;
 }
+
+pub mod module_opcode {
+    pub const EOF: u64 = 0;
+    pub const SINT: u64 = 1;
+    pub const UINT: u64 = 2;
+    pub const FLOAT: u64 = 3;
+    pub const DOUBLE: u64 = 4;
+    pub const STRING: u64 = 5;
+}
@@ -0,0 +1,149 @@
+use crate::crc64;
+use crate::types::{RdbError, RdbResult};
+use std::io::{self, Read, Seek, SeekFrom};
+
+// Skips seeked over instead of read, small ones are cheaper to read from
+// the buffer of the input than to seek over, which discards it
+const MIN_SEEK_LENGTH: u64 = 8 * 1024;
+
+/// Input whose bytes can be passed over without decoding them
+pub trait Skip: Read {
+    /// Skips the next `len` bytes, failing if the input ends before
+    fn skip(&mut self, len: u64) -> RdbResult<()>;
+}
+
+// Moves a seekable input forward by up to `len` bytes without passing
+// its end at `end`, returning how far it moved
+type SeekOver<R> = fn(&mut R, u64, u64) -> io::Result<u64>;
+
+/// Reader keeping track of the number of bytes read, to report the
+/// position of keys in the dump, and optionally of their CRC64 checksum
+/// and a copy of them
+pub struct CountingReader<R: Read> {
+    inner: R,
+    position: u64,
+    checksum: u64,
+    track_checksum: bool,
+    recording: Option<Vec<u8>>,
+    seek: Option<(SeekOver<R>, u64)>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader {
+            inner,
+            position: 0,
+            checksum: 0,
+            track_checksum: false,
+            recording: None,
+            seek: None,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Starts or pauses updating the checksum with the bytes read
+    pub fn track_checksum(&mut self, enabled: bool) {
+        self.track_checksum = enabled;
+    }
+
+    /// Checksum of the bytes read while tracking was enabled
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// Starts keeping a copy of the bytes read
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Returns the bytes read since `start_recording` and stops copying them
+    pub fn stop_recording(&mut self) -> Vec<u8> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    fn count(&mut self, read: &[u8]) {
+        self.position += read.len() as u64;
+        if self.track_checksum {
+            self.checksum = crc64::update(self.checksum, read);
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.extend_from_slice(read);
+        }
+    }
+}
+
+impl<R: Read + Seek> CountingReader<R> {
+    /// Seeks over skipped bytes instead of reading them, unless they are
+    /// needed for the checksum or a recording
+    pub fn enable_seek(&mut self) -> io::Result<()> {
+        let position = self.inner.stream_position()?;
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(position))?;
+        self.seek = Some((seek_over::<R>, end));
+        Ok(())
+    }
+}
+
+fn seek_over<R: Seek>(inner: &mut R, len: u64, end: u64) -> io::Result<u64> {
+    let position = inner.stream_position()?;
+    let len = len.min(end.saturating_sub(position));
+    inner.seek(SeekFrom::Current(len as i64))?;
+    Ok(len)
+}
+
+impl<'a> CountingReader<&'a [u8]> {
+    /// Bytes left to read, to borrow data from instead of copying it
+    pub fn remaining(&self) -> &'a [u8] {
+        self.inner
+    }
+
+    /// Counts the first `len` bytes left as read, after borrowing them
+    pub fn advance(&mut self, len: usize) {
+        let (read, rest) = self.inner.split_at(len);
+        self.inner = rest;
+        self.count(read);
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<R: Read> Skip for CountingReader<R> {
+    fn skip(&mut self, len: u64) -> RdbResult<()> {
+        let skipped = match self.seek {
+            Some((seek_over, end))
+                if len >= MIN_SEEK_LENGTH && !self.track_checksum && self.recording.is_none() =>
+            {
+                let skipped = seek_over(&mut self.inner, len, end)?;
+                self.position += skipped;
+                skipped
+            }
+            _ => io::copy(&mut self.take(len), &mut io::sink())?,
+        };
+        if skipped != len {
+            return Err(RdbError::truncated());
+        }
+        Ok(())
+    }
+}
+
+impl Skip for &[u8] {
+    fn skip(&mut self, len: u64) -> RdbResult<()> {
+        let Some(rest) = usize::try_from(len).ok().and_then(|len| self.get(len..)) else {
+            // Like reading past the end, which consumes all data left
+            *self = &[];
+            return Err(RdbError::truncated());
+        };
+        *self = rest;
+        Ok(())
+    }
+}
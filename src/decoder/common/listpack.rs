@@ -1,5 +1,7 @@
+use super::utils::{bounded_capacity, read_exact, take};
 use crate::types::{RdbError, RdbResult};
-use byteorder::ReadBytesExt;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::borrow::Cow;
 use std::io::Read;
 
 /// Skip the backlen field in a listpack entry
@@ -17,55 +19,118 @@ fn skip_backlen<R: Read>(reader: &mut R, element_len: u32) -> RdbResult<()> {
         5
     };
 
-    let mut buf = vec![0; backlen];
-    reader.read_exact(&mut buf)?;
+    let mut buf = [0; 5];
+    reader.read_exact(&mut buf[..backlen])?;
     Ok(())
 }
 
+#[derive(Debug, Clone)]
+pub enum ListPackEntry<S = Vec<u8>> {
+    String(S),
+    Integer(i64),
+}
+
+impl<S: AsRef<[u8]>> ListPackEntry<S> {
+    /// The entry as an integer
+    /// String entries holding a decimal number are converted as well
+    pub fn into_int(self) -> RdbResult<i64> {
+        match self {
+            ListPackEntry::Integer(val) => Ok(val),
+            ListPackEntry::String(val) => std::str::from_utf8(val.as_ref())
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or_else(|| {
+                    RdbError::corrupt(
+                        "listpack",
+                        format!(
+                            "Not an integer: {:?}",
+                            String::from_utf8_lossy(val.as_ref())
+                        ),
+                    )
+                }),
+        }
+    }
+}
+
 /// Read a single entry from a listpack as a string
+pub fn read_list_pack_entry_as_string<R: Read>(reader: &mut R) -> RdbResult<Vec<u8>> {
+    match read_list_pack_entry(reader)? {
+        ListPackEntry::String(val) => Ok(val),
+        ListPackEntry::Integer(val) => Ok(val.to_string().into_bytes()),
+    }
+}
+
+/// Read a single entry from a listpack held in memory as a string,
+/// borrowing string entries from it
+pub fn read_list_pack_entry_as_bytes<'a>(input: &mut &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+    match read_entry(input, take)? {
+        ListPackEntry::String(val) => Ok(Cow::Borrowed(val)),
+        ListPackEntry::Integer(val) => Ok(Cow::Owned(val.to_string().into_bytes())),
+    }
+}
+
+/// Read a single entry from a listpack as an integer
+/// String entries holding a decimal number are converted as well
+pub fn read_list_pack_entry_as_int<R: Read>(reader: &mut R) -> RdbResult<i64> {
+    read_list_pack_entry(reader)?.into_int()
+}
+
+/// Read a single entry from a listpack held in memory as an integer,
+/// see `read_list_pack_entry_as_int`
+pub fn read_list_pack_entry_as_int_from(input: &mut &[u8]) -> RdbResult<i64> {
+    read_entry(input, take)?.into_int()
+}
+
+/// Read a single entry from a listpack
 /// Format (first 2 bits):
 /// 00/01: 7-bit integer
 /// 10: string with 6-bit length
 /// 11: complex encoding (integers or strings)
-pub fn read_list_pack_entry_as_string<R: Read>(reader: &mut R) -> RdbResult<Vec<u8>> {
+pub fn read_list_pack_entry<R: Read>(reader: &mut R) -> RdbResult<ListPackEntry> {
+    read_entry(reader, read_exact)
+}
+
+// Reads an entry, taking the bytes of string entries with `read_string`
+fn read_entry<R: Read, S>(
+    reader: &mut R,
+    read_string: fn(&mut R, usize) -> RdbResult<S>,
+) -> RdbResult<ListPackEntry<S>> {
     let header = reader.read_u8()?;
 
     match header >> 6 {
         0 | 1 => {
-            let val = (header & 0x7F) as i8;
+            let val = (header & 0x7F) as i64;
             skip_backlen(reader, 1)?;
-            Ok(val.to_string().into_bytes())
+            Ok(ListPackEntry::Integer(val))
         }
         2 => {
             let str_len = (header & 0x3F) as usize;
-            let mut result = vec![0; str_len];
-            reader.read_exact(&mut result)?;
+            let result = read_string(reader, str_len)?;
 
             let content_len = 1 + str_len;
             skip_backlen(reader, content_len as u32)?;
 
-            Ok(result)
+            Ok(ListPackEntry::String(result))
         }
         3 => match header >> 4 {
             12 | 13 => {
                 let next = reader.read_u8()?;
-                let mut val = (((header & 0x1F) as u16) << 8) | (next as u16);
+                let mut val = (((header & 0x1F) as i64) << 8) | (next as i64);
                 if val >= 1 << 12 {
-                    val = !(8191 - val);
+                    val -= 1 << 13;
                 }
                 skip_backlen(reader, 2)?;
-                Ok(val.to_string().into_bytes())
+                Ok(ListPackEntry::Integer(val))
             }
             14 => {
                 let len_high = (header & 0x0F) as u16;
                 let len_low = reader.read_u8()? as u16;
                 let str_len = ((len_high << 8) | len_low) as usize;
 
-                let mut result = vec![0; str_len];
-                reader.read_exact(&mut result)?;
+                let result = read_string(reader, str_len)?;
 
                 skip_backlen(reader, (2 + str_len) as u32)?;
-                Ok(result)
+                Ok(ListPackEntry::String(result))
             }
             _ => match header & 0x0F {
                 0 => {
@@ -73,53 +138,111 @@ pub fn read_list_pack_entry_as_string<R: Read>(reader: &mut R) -> RdbResult<Vec<
                     reader.read_exact(&mut len_bytes)?;
                     let str_len = u32::from_le_bytes(len_bytes) as usize;
 
-                    let mut result = vec![0; str_len];
-                    reader.read_exact(&mut result)?;
+                    let result = read_string(reader, str_len)?;
 
                     skip_backlen(reader, (5 + str_len) as u32)?;
-                    Ok(result)
+                    Ok(ListPackEntry::String(result))
                 }
                 1..=4 => {
-                    let size = match header & 0x0F {
-                        1 => 2,
-                        2 => 3,
-                        3 => 4,
-                        4 => 8,
-                        _ => unreachable!(),
-                    };
-                    let mut int_bytes = vec![0; size];
-                    reader.read_exact(&mut int_bytes)?;
-
-                    let val = match size {
-                        2 => i16::from_le_bytes(int_bytes.try_into().unwrap()) as i64,
-                        3 => {
-                            let mut bytes = [0u8; 4];
-                            bytes[..3].copy_from_slice(&int_bytes);
-                            i32::from_le_bytes(bytes) as i64 >> 8
-                        }
-                        4 => i32::from_le_bytes(int_bytes.try_into().unwrap()) as i64,
-                        8 => i64::from_le_bytes(int_bytes.try_into().unwrap()),
-                        _ => unreachable!(),
+                    let (size, val) = match header & 0x0F {
+                        1 => (2, reader.read_i16::<LittleEndian>()? as i64),
+                        2 => (3, reader.read_i24::<LittleEndian>()? as i64),
+                        3 => (4, reader.read_i32::<LittleEndian>()? as i64),
+                        _ => (8, reader.read_i64::<LittleEndian>()?),
                     };
 
-                    skip_backlen(reader, (size + 1) as u32)?;
-                    Ok(val.to_string().into_bytes())
+                    skip_backlen(reader, size + 1)?;
+                    Ok(ListPackEntry::Integer(val))
                 }
-                15 => Err(RdbError::MissingValue("listpack entry")),
-                _ => Err(RdbError::ParsingError {
-                    context: "read_list_pack_entry_as_string",
-                    message: format!("Unknown encoding value: {}", header),
-                }),
+                15 => Err(RdbError::corrupt("listpack", "Unexpected end of listpack")),
+                _ => Err(RdbError::corrupt(
+                    "listpack",
+                    format!("Unknown encoding value: {}", header),
+                )),
             },
         },
-        _ => unreachable!(),
+        // The header is shifted by 6 bits, leaving 2 bits
+        _ => Err(RdbError::corrupt(
+            "listpack",
+            format!("Unknown encoding value: {}", header),
+        )),
+    }
+}
+
+pub fn read_list_pack_length(buf: &[u8], cursor: &mut usize) -> RdbResult<usize> {
+    let header = buf
+        .get(*cursor..*cursor + 6)
+        .ok_or_else(|| RdbError::corrupt("listpack", "Missing listpack header"))?;
+    let _total_bytes = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let count = u16::from_le_bytes([header[4], header[5]]) as usize;
+    *cursor += 6;
+    Ok(count)
+}
+
+/// Reads the entries of a listpack held in memory up to its end marker,
+/// borrowing string entries from it
+pub fn read_list_pack_entries(listpack: &[u8]) -> RdbResult<Vec<Cow<'_, [u8]>>> {
+    read_entries(listpack).map_err(|err| err.within_blob("listpack"))
+}
+
+fn read_entries(listpack: &[u8]) -> RdbResult<Vec<Cow<'_, [u8]>>> {
+    let mut input = listpack;
+    let total_bytes = input.read_u32::<LittleEndian>()?;
+    let num_elements = input.read_u16::<LittleEndian>()?;
+
+    let mut values = Vec::with_capacity(bounded_capacity(num_elements.into()));
+
+    // Read until we reach the end of the listpack
+    let entries_end = (total_bytes as usize).saturating_sub(1);
+    while listpack.len() - input.len() < entries_end {
+        values.push(read_list_pack_entry_as_bytes(&mut input)?);
     }
+
+    // Verify end byte
+    let last_byte = input.read_u8()?;
+    if last_byte != 0xFF {
+        return Err(RdbError::corrupt(
+            "listpack",
+            format!("Unknown encoding value: {}", last_byte),
+        ));
+    }
+
+    Ok(values)
 }
 
-pub fn read_list_pack_length(buf: &[u8], cursor: &mut usize) -> usize {
-    let _total_bytes = u32::from_le_bytes(buf[*cursor..*cursor + 4].try_into().unwrap()) as usize;
-    *cursor += 4;
-    let count = u16::from_le_bytes(buf[*cursor..*cursor + 2].try_into().unwrap()) as usize;
-    *cursor += 2;
-    count
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::io::Cursor;
+
+    #[rstest]
+    #[case::int_13bit_positive(&[0xcf, 0xff, 0x02], "4095")]
+    #[case::int_13bit_negative(&[0xdf, 0xfb, 0x02], "-5")]
+    #[case::int_13bit_minimum(&[0xd0, 0x00, 0x02], "-4096")]
+    #[case::int_24bit_positive(&[0xf2, 0x40, 0x42, 0x0f, 0x04], "1000000")]
+    #[case::int_24bit_negative(&[0xf2, 0x90, 0xee, 0xfe, 0x04], "-70000")]
+    fn test_read_list_pack_integer(#[case] input: &[u8], #[case] expected: &str) {
+        let mut cursor = Cursor::new(Vec::from(input));
+        let value = read_list_pack_entry_as_string(&mut cursor).unwrap();
+        assert_eq!(expected.as_bytes(), value.as_slice());
+        assert_eq!(input.len() as u64, cursor.position());
+    }
+
+    #[test]
+    fn test_entry_overrunning_listpack_is_corrupt() {
+        // One entry announcing a 40 byte string in a 10 byte listpack
+        let listpack = [0x0a, 0, 0, 0, 0x01, 0, 0x80 | 40, b'a', b'b', 0xff];
+        let err = read_list_pack_entries(&listpack).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                RdbError::CorruptEncoding {
+                    encoding: "listpack",
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+    }
 }
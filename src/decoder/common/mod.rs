@@ -1,6 +1,11 @@
+mod counting;
 mod listpack;
 pub mod utils;
 mod ziplist;
 
-pub use listpack::{read_list_pack_entry_as_string, read_list_pack_length};
-pub use ziplist::{read_ziplist_entry_string, read_ziplist_metadata};
+pub use counting::{CountingReader, Skip};
+pub use listpack::{
+    read_list_pack_entries, read_list_pack_entry_as_bytes, read_list_pack_entry_as_int,
+    read_list_pack_entry_as_int_from, read_list_pack_entry_as_string, read_list_pack_length,
+};
+pub use ziplist::read_ziplist_entries;
@@ -1,15 +1,32 @@
-use crate::types::RdbError;
+use crate::types::{ErrorLocation, RdbError, ServerFlavor};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use lzf;
 use std::io::Read;
+use std::ops::RangeInclusive;
 
 #[doc(hidden)]
 use crate::constants::{constant, encoding, version};
 
 #[doc(hidden)]
-pub use crate::types::{RdbOk, RdbResult};
+pub use crate::types::RdbResult;
+
+// Number of elements reserved at most before reading them
+const MAX_PREALLOCATED: u64 = 4096;
+// LZF expands 3 bytes of a back reference to at most 264 bytes
+const MAX_LZF_RATIO: u64 = 88;
 
 pub fn read_length_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u32, bool)> {
+    let (length, is_encoded) = read_length64_with_encoding(input)?;
+    let length = u32::try_from(length).map_err(|_| {
+        RdbError::corrupt(
+            "length",
+            format!("Length does not fit into 32 bits: {}", length),
+        )
+    })?;
+    Ok((length, is_encoded))
+}
+
+pub fn read_length64_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u64, bool)> {
     let length;
     let mut is_encoded = false;
 
@@ -18,18 +35,29 @@ pub fn read_length_with_encoding<R: Read>(input: &mut R) -> RdbResult<(u32, bool
     match (enc_type & 0xC0) >> 6 {
         constant::RDB_ENCVAL => {
             is_encoded = true;
-            length = (enc_type & 0x3F) as u32;
+            length = (enc_type & 0x3F) as u64;
         }
         constant::RDB_6BITLEN => {
-            length = (enc_type & 0x3F) as u32;
+            length = (enc_type & 0x3F) as u64;
         }
         constant::RDB_14BITLEN => {
             let next_byte = input.read_u8()?;
-            length = (((enc_type & 0x3F) as u32) << 8) | next_byte as u32;
-        }
-        _ => {
-            length = input.read_u32::<BigEndian>()?;
+            length = (((enc_type & 0x3F) as u64) << 8) | next_byte as u64;
         }
+        _ => match enc_type {
+            constant::RDB_32BITLEN => {
+                length = input.read_u32::<BigEndian>()? as u64;
+            }
+            constant::RDB_64BITLEN => {
+                length = input.read_u64::<BigEndian>()?;
+            }
+            _ => {
+                return Err(RdbError::corrupt(
+                    "length",
+                    format!("Unknown length encoding: {}", enc_type),
+                ));
+            }
+        },
     }
 
     Ok((length, is_encoded))
@@ -40,44 +68,75 @@ pub fn read_length<R: Read>(input: &mut R) -> RdbResult<u32> {
     Ok(length)
 }
 
-pub fn verify_magic<R: Read>(input: &mut R) -> RdbOk {
+pub fn read_length64<R: Read>(input: &mut R) -> RdbResult<u64> {
+    let (length, _) = read_length64_with_encoding(input)?;
+    Ok(length)
+}
+
+/// Verifies the magic string, `REDIS` or `VALKEY`, and returns the flavor it denotes
+pub fn verify_magic<R: Read>(input: &mut R) -> RdbResult<ServerFlavor> {
     let mut magic = [0; 5];
-    match input.read(&mut magic) {
-        Ok(5) => (),
-        Ok(_) => return Err(RdbError::MissingValue("magic bytes")),
-        Err(e) => return Err(RdbError::Io(e)),
-    };
+    input.read_exact(&mut magic)?;
 
     if magic == constant::RDB_MAGIC.as_bytes() {
-        Ok(())
-    } else {
-        Err(RdbError::MissingValue("invalid magic string"))
+        return Ok(ServerFlavor::Redis);
+    }
+    // The Valkey magic is one byte longer, its version one digit shorter
+    let mut found = magic.to_vec();
+    if magic == constant::VALKEY_MAGIC.as_bytes()[..5] {
+        let last = input.read_u8()?;
+        if last == b'Y' {
+            return Ok(ServerFlavor::Valkey);
+        }
+        found.push(last);
     }
+    Err(RdbError::BadMagic {
+        found,
+        location: ErrorLocation::default(),
+    })
 }
 
-pub fn verify_version<R: Read>(input: &mut R) -> RdbOk {
+/// Verifies the 4 digit version following the `REDIS` magic string.
+/// Versions from 80 on are Valkey versions.
+pub fn verify_version<R: Read>(input: &mut R) -> RdbResult<u32> {
+    read_version_digits(
+        input,
+        4,
+        version::SUPPORTED_MINIMUM..=version::VALKEY_MAXIMUM,
+    )
+}
+
+/// Verifies the 3 digit version following the `VALKEY` magic string
+pub fn verify_valkey_version<R: Read>(input: &mut R) -> RdbResult<u32> {
+    read_version_digits(input, 3, version::VALKEY_MINIMUM..=version::VALKEY_MAXIMUM)
+}
+
+fn read_version_digits<R: Read>(
+    input: &mut R,
+    digits: usize,
+    supported: RangeInclusive<u32>,
+) -> RdbResult<u32> {
     let mut buf = [0u8; 4];
-    input.read_exact(&mut buf)?;
+    let buf = &mut buf[..digits];
+    input.read_exact(buf)?;
 
-    // Check if all characters are ASCII digits
-    for &byte in &buf {
+    // Convert ASCII digits to number (e.g., "0003" -> 3)
+    let mut version = 0;
+    for &byte in buf.iter() {
         if !byte.is_ascii_digit() {
-            return Err(RdbError::MissingValue("invalid version number"));
+            version = 0;
+            break;
         }
+        version = version * 10 + (byte - b'0') as u32;
     }
 
-    // Convert ASCII string to number (e.g., "0003" -> 3)
-    let version_str = std::str::from_utf8(&buf).unwrap();
-    let version = version_str.parse::<u32>().unwrap();
-
-    // Check if version is in supported range
-    let is_ok = (version::SUPPORTED_MINIMUM..=version::SUPPORTED_MAXIMUM).contains(&version);
-
-    if !is_ok {
-        return Err(RdbError::MissingValue("unsupported version"));
+    if !supported.contains(&version) {
+        return Err(RdbError::UnsupportedVersion {
+            found: String::from_utf8_lossy(buf).into_owned(),
+            location: ErrorLocation::default(),
+        });
     }
-
-    Ok(())
+    Ok(version)
 }
 
 pub fn read_blob<R: Read>(input: &mut R) -> RdbResult<Vec<u8>> {
@@ -91,11 +150,15 @@ pub fn read_blob<R: Read>(input: &mut R) -> RdbResult<Vec<u8>> {
             encoding::LZF => {
                 let compressed_length = read_length(input)?;
                 let real_length = read_length(input)?;
+                check_lzf_lengths(compressed_length as usize, real_length as usize)?;
                 let data = read_exact(input, compressed_length as usize)?;
-                lzf::decompress(&data, real_length as usize).unwrap()
+                decompress_lzf(&data, real_length as usize)?
             }
             _ => {
-                panic!("Unknown encoding: {}", length)
+                return Err(RdbError::corrupt(
+                    "string",
+                    format!("Unknown encoding value: {}", length),
+                ));
             }
         };
 
@@ -105,6 +168,39 @@ pub fn read_blob<R: Read>(input: &mut R) -> RdbResult<Vec<u8>> {
     }
 }
 
+// Checked before decompressing, which allocates the full length upfront
+fn check_lzf_lengths(compressed_length: usize, real_length: usize) -> RdbResult<()> {
+    if real_length as u64 > compressed_length as u64 * MAX_LZF_RATIO {
+        return Err(decompression_error(format!(
+            "{} bytes cannot expand to {} bytes",
+            compressed_length, real_length
+        )));
+    }
+    Ok(())
+}
+
+/// Decompresses an LZF compressed string, which expands to `real_length` bytes
+pub fn decompress_lzf(data: &[u8], real_length: usize) -> RdbResult<Vec<u8>> {
+    check_lzf_lengths(data.len(), real_length)?;
+    let result =
+        lzf::decompress(data, real_length).map_err(|err| decompression_error(err.to_string()))?;
+    if result.len() != real_length {
+        return Err(decompression_error(format!(
+            "Data expands to {} bytes instead of {}",
+            result.len(),
+            real_length
+        )));
+    }
+    Ok(result)
+}
+
+fn decompression_error(message: String) -> RdbError {
+    RdbError::Decompression {
+        message,
+        location: ErrorLocation::default(),
+    }
+}
+
 pub fn int_to_vec(number: i32) -> Vec<u8> {
     let number = number.to_string();
     let mut result = Vec::with_capacity(number.len());
@@ -114,19 +210,55 @@ pub fn int_to_vec(number: i32) -> Vec<u8> {
     result
 }
 
+/// Reads `len` bytes. Lengths are taken from the dump, so the buffer grows
+/// with the data actually read instead of being allocated upfront.
 pub fn read_exact<T: Read>(reader: &mut T, len: usize) -> RdbResult<Vec<u8>> {
-    let mut buf = vec![0; len];
-    reader.read_exact(&mut buf)?;
+    let mut buf = Vec::with_capacity(bounded_capacity(len as u64));
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(RdbError::truncated());
+    }
 
     Ok(buf)
 }
 
+/// Takes the next `len` bytes of data held in memory without copying them
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> RdbResult<&'a [u8]> {
+    if input.len() < len {
+        // Like reading past the end, which consumes all data left
+        *input = &[];
+        return Err(RdbError::truncated());
+    }
+    let (read, rest) = input.split_at(len);
+    *input = rest;
+    Ok(read)
+}
+
+/// Capacity to reserve for a number of elements read from the dump.
+/// Corrupted lengths must not make the decoder allocate all memory upfront.
+pub fn bounded_capacity(len: u64) -> usize {
+    len.min(MAX_PREALLOCATED) as usize
+}
+
+/// Parses a sorted set score stored as a string
+pub fn parse_score(encoding: &'static str, score: &[u8]) -> RdbResult<f64> {
+    std::str::from_utf8(score)
+        .ok()
+        .and_then(|score| score.parse::<f64>().ok())
+        .ok_or_else(|| {
+            RdbError::corrupt(
+                encoding,
+                format!("Invalid score: {:?}", String::from_utf8_lossy(score)),
+            )
+        })
+}
+
 pub fn read_sequence<R: Read, T, F>(input: &mut R, mut transform: F) -> RdbResult<Vec<T>>
 where
     F: FnMut(&mut R) -> RdbResult<T>,
 {
     let mut len = read_length(input)?;
-    let mut values = Vec::with_capacity(len as usize);
+    let mut values = Vec::with_capacity(bounded_capacity(len.into()));
 
     while len > 0 {
         values.push(transform(input)?);
@@ -157,6 +289,16 @@ mod tests {
         assert_eq!(expected_position, cursor.position());
     }
 
+    #[test]
+    fn test_read_length64() {
+        let mut cursor = Cursor::new(vec![0x81, 0, 0, 0x01, 0x90, 0x00, 0xc7, 0x9c, 0x00]);
+        assert_eq!(1_718_000_000_000, read_length64(&mut cursor).unwrap());
+        assert_eq!(9, cursor.position());
+
+        let mut cursor = Cursor::new(vec![0x81, 0, 0, 0x01, 0x90, 0x00, 0xc7, 0x9c, 0x00]);
+        assert!(read_length(&mut cursor).is_err());
+    }
+
     #[test]
     fn test_read_blob() {
         assert_eq!(
@@ -165,6 +307,19 @@ mod tests {
         );
     }
 
+    #[rstest]
+    // A length beyond the available data
+    #[case(&[0x80, 0xff, 0xff, 0xff, 0xf0, 0x61])]
+    // LZF data claiming to expand 4 bytes to 4 GB
+    #[case(&[0xc3, 0x04, 0x80, 0xff, 0xff, 0xff, 0xff, 0x00, 0x61, 0x61, 0x61])]
+    // LZF data expanding to fewer bytes than announced
+    #[case(&[0xc3, 0x02, 0x05, 0x00, 0x61])]
+    // Unknown encoding
+    #[case(&[0xc4])]
+    fn test_read_blob_rejects_malformed_input(#[case] input: &[u8]) {
+        assert!(read_blob(&mut Cursor::new(Vec::from(input))).is_err());
+    }
+
     #[test]
     fn test_verify_version() {
         // Valid version "0003" should succeed
@@ -174,6 +329,26 @@ mod tests {
         // Invalid version "000:" should fail
         let failure = verify_version(&mut Cursor::new(vec![0x30, 0x30, 0x30, 0x3a]));
         assert!(failure.is_err());
+
+        // Newer Redis versions and Valkey's "0080" are accepted
+        assert_eq!(
+            13,
+            verify_version(&mut Cursor::new(b"0013".to_vec())).unwrap()
+        );
+        assert_eq!(
+            80,
+            verify_version(&mut Cursor::new(b"0080".to_vec())).unwrap()
+        );
+        assert!(verify_version(&mut Cursor::new(b"0081".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_verify_valkey_version() {
+        assert_eq!(
+            80,
+            verify_valkey_version(&mut Cursor::new(b"080".to_vec())).unwrap()
+        );
+        assert!(verify_valkey_version(&mut Cursor::new(b"012".to_vec())).is_err());
     }
 
     #[test]
@@ -183,5 +358,9 @@ mod tests {
 
         let failure = verify_magic(&mut Cursor::new(vec![0x51, 0x0, 0x0, 0x0, 0x0]));
         assert!(failure.is_err(), "Expected error for invalid magic bytes");
+
+        let valkey = verify_magic(&mut Cursor::new(b"VALKEY".to_vec()));
+        assert_eq!(ServerFlavor::Valkey, valkey.unwrap());
+        assert!(verify_magic(&mut Cursor::new(b"VALKEX".to_vec())).is_err());
     }
 }
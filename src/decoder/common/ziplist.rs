@@ -1,12 +1,13 @@
+use std::borrow::Cow;
 use std::io::Read;
 
-use super::utils::read_exact;
+use super::utils::{bounded_capacity, take};
 use crate::types::{RdbError, RdbResult};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 
 #[derive(Debug, Clone)]
-pub enum ZiplistEntry {
-    String(Vec<u8>),
+pub enum ZiplistEntry<'a> {
+    String(&'a [u8]),
     Number(i64),
 }
 
@@ -18,28 +19,43 @@ pub fn read_ziplist_metadata<T: Read>(input: &mut T) -> RdbResult<(u32, u32, u16
     Ok((zlbytes, zltail, zllen))
 }
 
-pub fn read_ziplist_entry_string<R: Read>(input: &mut R) -> RdbResult<Vec<u8>> {
-    let entry = read_ziplist_entry(input)?;
-    match entry {
-        ZiplistEntry::String(val) => Ok(val),
-        ZiplistEntry::Number(val) => Ok(val.to_string().into_bytes()),
+/// Reads the entries of a ziplist held in memory as strings, borrowing
+/// string entries from it
+pub fn read_ziplist_entries(ziplist: &[u8]) -> RdbResult<Vec<Cow<'_, [u8]>>> {
+    read_entries(ziplist).map_err(|err| err.within_blob("ziplist"))
+}
+
+fn read_entries(ziplist: &[u8]) -> RdbResult<Vec<Cow<'_, [u8]>>> {
+    let mut input = ziplist;
+    let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut input)?;
+
+    let mut values = Vec::with_capacity(bounded_capacity(zllen.into()));
+
+    for _ in 0..zllen {
+        let entry = match read_ziplist_entry(&mut input)? {
+            ZiplistEntry::String(val) => Cow::Borrowed(val),
+            ZiplistEntry::Number(val) => Cow::Owned(val.to_string().into_bytes()),
+        };
+        values.push(entry);
     }
+
+    let last_byte = input.read_u8()?;
+    if last_byte != 0xFF {
+        return Err(RdbError::corrupt(
+            "ziplist",
+            format!("Unknown encoding value: {}", last_byte),
+        ));
+    }
+
+    Ok(values)
 }
 
-fn read_ziplist_entry<R: Read>(input: &mut R) -> RdbResult<ZiplistEntry> {
+fn read_ziplist_entry<'a>(input: &mut &'a [u8]) -> RdbResult<ZiplistEntry<'a>> {
     // 1. 1 or 5 bytes length of previous entry
     let byte = input.read_u8()?;
     if byte == 254 {
         let mut bytes = [0; 4];
-        match input.read(&mut bytes) {
-            Ok(4) => (),
-            Ok(_) => {
-                return Err(RdbError::MissingValue(
-                    "4 bytes to skip after ziplist length",
-                ))
-            }
-            Err(e) => return Err(RdbError::Io(e)),
-        };
+        input.read_exact(&mut bytes)?;
     }
 
     let number_value: i64;
@@ -62,11 +78,7 @@ fn read_ziplist_entry<R: Read>(input: &mut R) -> RdbResult<ZiplistEntry> {
                 0xF => match flag & 0xF {
                     0 => {
                         let mut bytes = [0; 3];
-                        match input.read(&mut bytes) {
-                            Ok(3) => (),
-                            Ok(_) => return Err(RdbError::MissingValue("24bit number")),
-                            Err(e) => return Err(RdbError::Io(e)),
-                        };
+                        input.read_exact(&mut bytes)?;
 
                         let number: i32 = (((bytes[2] as i32) << 24)
                             ^ ((bytes[1] as i32) << 16)
@@ -84,10 +96,10 @@ fn read_ziplist_entry<R: Read>(input: &mut R) -> RdbResult<ZiplistEntry> {
                     }
                 },
                 _ => {
-                    return Err(RdbError::ParsingError {
-                        context: "read_ziplist_entry",
-                        message: format!("Unknown encoding value: {}", flag),
-                    });
+                    return Err(RdbError::corrupt(
+                        "ziplist",
+                        format!("Unknown encoding value: {}", flag),
+                    ));
                 }
             }
 
@@ -96,6 +108,6 @@ fn read_ziplist_entry<R: Read>(input: &mut R) -> RdbResult<ZiplistEntry> {
     };
 
     // 3. Read value
-    let rawval = read_exact(input, length as usize)?;
+    let rawval = take(input, length as usize)?;
     Ok(ZiplistEntry::String(rawval))
 }
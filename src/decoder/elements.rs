@@ -0,0 +1,298 @@
+use super::common::utils::{read_blob, read_length64};
+use super::{hash, list, sorted_set};
+use crate::constants::encoding_type;
+use crate::types::{
+    CollectionElement, CollectionKind, EncodingType, KeyMeta, RdbError, RdbResult, RdbValue,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Read;
+use std::vec;
+
+/// Collection whose elements are yielded one at a time
+pub(crate) struct PendingCollection {
+    pub key: Vec<u8>,
+    source: ElementSource,
+}
+
+enum ElementSource {
+    // Elements stored one after another in the dump, read as they are yielded
+    Sequence {
+        remaining: u64,
+        element: SequenceElement,
+    },
+    // Nodes of a quicklist, each of them decoded as a whole
+    Quicklist {
+        remaining: u64,
+        is_quicklist_2: bool,
+        node: vec::IntoIter<Vec<u8>>,
+    },
+    // Elements of a compact encoding, which is stored as a single blob
+    Buffered(vec::IntoIter<CollectionElement>),
+}
+
+#[derive(Clone, Copy)]
+enum SequenceElement {
+    Member,
+    Scored { is_zset2: bool },
+    Field,
+    FieldWithTtl { min_expire: Option<u64> },
+}
+
+/// Whether the elements of values of the given type are read from the
+/// input as they are yielded, instead of being decoded upfront
+pub(crate) fn is_read_lazily(value_type: u8) -> bool {
+    matches!(
+        value_type,
+        encoding_type::LIST
+            | encoding_type::SET
+            | encoding_type::ZSET
+            | encoding_type::ZSET_2
+            | encoding_type::HASH
+            | encoding_type::HASH_METADATA_PRE_GA
+            | encoding_type::HASH_METADATA
+            | encoding_type::LIST_QUICKLIST
+            | encoding_type::LIST_QUICKLIST_2
+    )
+}
+
+/// Reads the start of a collection read lazily, up to its first element
+pub(crate) fn read_collection_start<R: Read>(
+    input: &mut R,
+    key: &[u8],
+    value_type: u8,
+    expiry: Option<u64>,
+) -> RdbResult<(RdbValue, PendingCollection)> {
+    let (kind, encoding, element) = match value_type {
+        encoding_type::LIST => (
+            CollectionKind::List,
+            EncodingType::LinkedList,
+            SequenceElement::Member,
+        ),
+        encoding_type::SET => (
+            CollectionKind::Set,
+            EncodingType::Hashtable,
+            SequenceElement::Member,
+        ),
+        encoding_type::ZSET => (
+            CollectionKind::SortedSet,
+            EncodingType::Skiplist,
+            SequenceElement::Scored { is_zset2: false },
+        ),
+        encoding_type::ZSET_2 => (
+            CollectionKind::SortedSet,
+            EncodingType::ZSet2,
+            SequenceElement::Scored { is_zset2: true },
+        ),
+        encoding_type::HASH => (
+            CollectionKind::Hash {
+                field_expiry: false,
+            },
+            EncodingType::Hashtable,
+            SequenceElement::Field,
+        ),
+        encoding_type::HASH_METADATA_PRE_GA | encoding_type::HASH_METADATA => {
+            let min_expire = if value_type == encoding_type::HASH_METADATA {
+                Some(input.read_u64::<LittleEndian>()?)
+            } else {
+                None
+            };
+            (
+                CollectionKind::Hash { field_expiry: true },
+                EncodingType::Hashtable,
+                SequenceElement::FieldWithTtl { min_expire },
+            )
+        }
+        encoding_type::LIST_QUICKLIST | encoding_type::LIST_QUICKLIST_2 => {
+            let is_quicklist_2 = value_type == encoding_type::LIST_QUICKLIST_2;
+            let start = RdbValue::StartCollection {
+                key: key.to_vec(),
+                kind: CollectionKind::List,
+                // Only the number of nodes is stored upfront
+                length: None,
+                expiry,
+                meta: KeyMeta::with_encoding(if is_quicklist_2 {
+                    EncodingType::Quicklist2
+                } else {
+                    EncodingType::Quicklist
+                }),
+            };
+            let source = ElementSource::Quicklist {
+                remaining: read_length64(input)?,
+                is_quicklist_2,
+                node: Vec::new().into_iter(),
+            };
+            return Ok((start, PendingCollection::new(key, source)));
+        }
+        _ => {
+            return Err(RdbError::unsupported(format!(
+                "Type {} is not read element by element",
+                value_type
+            )))
+        }
+    };
+
+    let length = read_length64(input)?;
+    let start = RdbValue::StartCollection {
+        key: key.to_vec(),
+        kind,
+        length: Some(length),
+        expiry,
+        meta: KeyMeta::with_encoding(encoding),
+    };
+    let source = ElementSource::Sequence {
+        remaining: length,
+        element,
+    };
+    Ok((start, PendingCollection::new(key, source)))
+}
+
+/// Splits a decoded collection into its start and its elements.
+/// Other values are returned as they are.
+pub(crate) fn split_collection(value: RdbValue) -> (RdbValue, Option<PendingCollection>) {
+    let (key, kind, elements, expiry, meta) = match value {
+        RdbValue::List {
+            key,
+            values,
+            expiry,
+            meta,
+        } => (
+            key,
+            CollectionKind::List,
+            values.into_iter().map(CollectionElement::Member).collect(),
+            expiry,
+            meta,
+        ),
+        RdbValue::Set {
+            key,
+            members,
+            expiry,
+            meta,
+        } => (
+            key,
+            CollectionKind::Set,
+            members.into_iter().map(CollectionElement::Member).collect(),
+            expiry,
+            meta,
+        ),
+        RdbValue::SortedSet {
+            key,
+            values,
+            expiry,
+            meta,
+        } => (
+            key,
+            CollectionKind::SortedSet,
+            values
+                .into_iter()
+                .map(|(score, member)| CollectionElement::Scored { score, member })
+                .collect(),
+            expiry,
+            meta,
+        ),
+        RdbValue::Hash {
+            key,
+            values,
+            field_expiry,
+            expiry,
+            meta,
+        } => (
+            key,
+            CollectionKind::Hash {
+                field_expiry: !field_expiry.is_empty(),
+            },
+            values
+                .into_iter()
+                .map(|(field, value)| CollectionElement::Field {
+                    expires_at: field_expiry.get(&field).copied(),
+                    field,
+                    value,
+                })
+                .collect::<Vec<_>>(),
+            expiry,
+            meta,
+        ),
+        value => return (value, None),
+    };
+
+    let start = RdbValue::StartCollection {
+        key: key.clone(),
+        kind,
+        length: Some(elements.len() as u64),
+        expiry,
+        meta,
+    };
+    let source = ElementSource::Buffered(elements.into_iter());
+    (start, Some(PendingCollection::new(&key, source)))
+}
+
+impl PendingCollection {
+    fn new(key: &[u8], source: ElementSource) -> PendingCollection {
+        PendingCollection {
+            key: key.to_vec(),
+            source,
+        }
+    }
+
+    /// Reads the next element, `None` once all elements are read
+    pub(crate) fn next_element<R: Read>(
+        &mut self,
+        input: &mut R,
+    ) -> RdbResult<Option<CollectionElement>> {
+        match &mut self.source {
+            ElementSource::Sequence { remaining, element } => {
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                *remaining -= 1;
+                read_sequence_element(input, *element).map(Some)
+            }
+            ElementSource::Quicklist {
+                remaining,
+                is_quicklist_2,
+                node,
+            } => loop {
+                if let Some(value) = node.next() {
+                    return Ok(Some(CollectionElement::Member(value)));
+                }
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                *remaining -= 1;
+                let values = if *is_quicklist_2 {
+                    list::read_quicklist_2_node(input)?
+                } else {
+                    list::read_quicklist_ziplist(input, &self.key)?
+                };
+                *node = values.into_iter();
+            },
+            ElementSource::Buffered(elements) => Ok(elements.next()),
+        }
+    }
+}
+
+fn read_sequence_element<R: Read>(
+    input: &mut R,
+    element: SequenceElement,
+) -> RdbResult<CollectionElement> {
+    let element = match element {
+        SequenceElement::Member => CollectionElement::Member(read_blob(input)?),
+        SequenceElement::Scored { is_zset2 } => {
+            let (score, member) = sorted_set::read_sorted_set_entry(input, is_zset2)?;
+            CollectionElement::Scored { score, member }
+        }
+        SequenceElement::Field => CollectionElement::Field {
+            field: read_blob(input)?,
+            value: read_blob(input)?,
+            expires_at: None,
+        },
+        SequenceElement::FieldWithTtl { min_expire } => {
+            let (field, value, expires_at) = hash::read_hash_metadata_entry(input, min_expire)?;
+            CollectionElement::Field {
+                field,
+                value,
+                expires_at,
+            }
+        }
+    };
+    Ok(element)
+}
@@ -1,12 +1,14 @@
-use super::common::utils::{read_blob, read_exact, read_length};
+use super::common::utils::{read_blob, read_exact, read_length, read_length64};
 use super::common::{
-    read_list_pack_entry_as_string, read_list_pack_length, read_ziplist_entry_string,
-    read_ziplist_metadata,
+    read_list_pack_entry_as_bytes, read_list_pack_entry_as_int_from, read_list_pack_length,
+    read_ziplist_entries,
 };
-use crate::types::{RdbError, RdbResult, RdbValue};
+use crate::constants::encoding_type;
+use crate::types::{EncodingType, KeyMeta, RdbError, RdbResult, RdbValue};
 use byteorder::{LittleEndian, ReadBytesExt};
 use indexmap::IndexMap;
-use std::io::{Cursor, Read};
+use std::borrow::Cow;
+use std::io::Read;
 
 pub fn read_hash<R: Read>(input: &mut R, key: &[u8], expiry: Option<u64>) -> RdbResult<RdbValue> {
     let mut hash_items = read_length(input)?;
@@ -22,7 +24,9 @@ pub fn read_hash<R: Read>(input: &mut R, key: &[u8], expiry: Option<u64>) -> Rdb
     Ok(RdbValue::Hash {
         key: key.to_vec(),
         values,
+        field_expiry: IndexMap::new(),
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Hashtable),
     })
 }
 
@@ -32,42 +36,57 @@ pub fn read_hash_ziplist<R: Read>(
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let ziplist = read_blob(input)?;
-    let mut reader = Cursor::new(ziplist);
-    let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
-
-    assert!(zllen % 2 == 0);
-    let zllen = zllen / 2;
-
-    let mut values = IndexMap::new();
-
-    for _ in 0..zllen {
-        let field = read_ziplist_entry_string(&mut reader)?;
-        let value = read_ziplist_entry_string(&mut reader)?;
-        values.insert(field, value);
-    }
-
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_hash_ziplist",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
-    }
+    let ziplist_size = ziplist.len() as u64;
+    let values = read_hash_ziplist_entries(&ziplist)?;
 
     Ok(RdbValue::Hash {
         key: key.to_vec(),
-        values,
+        values: into_owned_fields(values),
+        field_expiry: IndexMap::new(),
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Ziplist(ziplist_size)),
     })
 }
 
+/// Reads the fields and values of a hash ziplist held in memory,
+/// borrowing them from it
+pub(crate) fn read_hash_ziplist_entries(ziplist: &[u8]) -> RdbResult<Vec<FieldValue<'_>>> {
+    let entries = read_ziplist_entries(ziplist)?;
+    if !entries.len().is_multiple_of(2) {
+        return Err(RdbError::corrupt(
+            "ziplist",
+            format!("Odd number of ziplist entries: {}", entries.len()),
+        ));
+    }
+
+    let mut values = Vec::with_capacity(entries.len() / 2);
+    let mut entries = entries.into_iter();
+    while let (Some(field), Some(value)) = (entries.next(), entries.next()) {
+        values.push((field, value));
+    }
+    Ok(values)
+}
+
 pub fn read_hash_zipmap<R: Read>(
     input: &mut R,
     key: &[u8],
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let zipmap = read_blob(input)?;
-    let mut reader = Cursor::new(zipmap);
+    let zipmap_size = zipmap.len() as u64;
+    let values = read_zipmap_fields(&zipmap).map_err(|err| err.within_blob("zipmap"))?;
+
+    Ok(RdbValue::Hash {
+        key: key.to_vec(),
+        values,
+        field_expiry: IndexMap::new(),
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Zipmap(zipmap_size)),
+    })
+}
+
+fn read_zipmap_fields(zipmap: &[u8]) -> RdbResult<IndexMap<Vec<u8>, Vec<u8>>> {
+    let mut reader = zipmap;
 
     let zmlen = reader.read_u8()?;
 
@@ -103,30 +122,26 @@ pub fn read_hash_zipmap<R: Read>(
             let last_byte = reader.read_u8()?;
 
             if last_byte != 0xFF {
-                return Err(RdbError::ParsingError {
-                    context: "read_hash_zipmap",
-                    message: format!("Unknown encoding value: {}", last_byte),
-                });
+                return Err(RdbError::corrupt(
+                    "zipmap",
+                    format!("Unknown encoding value: {}", last_byte),
+                ));
             }
             break;
         }
     }
 
-    Ok(RdbValue::Hash {
-        key: key.to_vec(),
-        values,
-        expiry,
-    })
+    Ok(values)
 }
 
 fn read_zipmap_entry<T: Read>(next_byte: u8, zipmap: &mut T) -> RdbResult<Vec<u8>> {
     let elem_len = match next_byte {
-        253 => zipmap.read_u32::<LittleEndian>().unwrap(),
+        253 => zipmap.read_u32::<LittleEndian>()?,
         254 | 255 => {
-            return Err(RdbError::ParsingError {
-                context: "read_zipmap_entry",
-                message: format!("Unknown encoding value: {}", next_byte),
-            });
+            return Err(RdbError::corrupt(
+                "zipmap",
+                format!("Unknown encoding value: {}", next_byte),
+            ));
         }
         _ => next_byte as u32,
     };
@@ -140,22 +155,175 @@ pub fn read_hash_list_pack<R: Read>(
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let listpack = read_blob(input)?;
+    let listpack_size = listpack.len() as u64;
+    let values = read_hash_list_pack_entries(&listpack)?;
+
+    Ok(RdbValue::Hash {
+        key: key.to_vec(),
+        values: into_owned_fields(values),
+        field_expiry: IndexMap::new(),
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::ListPack(listpack_size)),
+    })
+}
+
+/// Reads the fields and values of a hash listpack held in memory,
+/// borrowing them from it
+pub(crate) fn read_hash_list_pack_entries(listpack: &[u8]) -> RdbResult<Vec<FieldValue<'_>>> {
+    read_list_pack_fields(listpack).map_err(|err| err.within_blob("listpack"))
+}
+
+fn read_list_pack_fields(listpack: &[u8]) -> RdbResult<Vec<FieldValue<'_>>> {
     let mut cursor = 0;
-    let size = read_list_pack_length(&listpack, &mut cursor);
+    let size = read_list_pack_length(listpack, &mut cursor)?;
+    if !size.is_multiple_of(2) {
+        return Err(RdbError::corrupt(
+            "listpack",
+            format!("Odd number of listpack entries: {}", size),
+        ));
+    }
 
-    let mut values = IndexMap::new();
-    let mut reader = Cursor::new(listpack);
-    reader.set_position(cursor as u64);
+    let mut values = Vec::new();
+    let mut reader = &listpack[cursor..];
 
     for _ in 0..size / 2 {
-        let field = read_list_pack_entry_as_string(&mut reader)?;
-        let value = read_list_pack_entry_as_string(&mut reader)?;
+        let field = read_list_pack_entry_as_bytes(&mut reader)?;
+        let value = read_list_pack_entry_as_bytes(&mut reader)?;
+        values.push((field, value));
+    }
+
+    Ok(values)
+}
+
+// Field and value of a hash, borrowed from the compact encoding holding them
+pub(crate) type FieldValue<'a> = (Cow<'a, [u8]>, Cow<'a, [u8]>);
+pub(crate) type FieldExpiry<'a> = (Cow<'a, [u8]>, u64);
+
+fn into_owned_fields(values: Vec<FieldValue<'_>>) -> IndexMap<Vec<u8>, Vec<u8>> {
+    values
+        .into_iter()
+        .map(|(field, value)| (field.into_owned(), value.into_owned()))
+        .collect()
+}
+
+/// Reads a hash table with per-field expiry (Redis 7.4+).
+///
+/// Each field is prefixed by its TTL, 0 meaning no TTL. `HASH_METADATA`
+/// stores the TTLs relative to the minimal expiry of the hash, the pre-GA
+/// variant stores absolute timestamps.
+pub fn read_hash_metadata<R: Read>(
+    input: &mut R,
+    key: &[u8],
+    expiry: Option<u64>,
+    enc_type: u8,
+) -> RdbResult<RdbValue> {
+    let min_expire = if enc_type == encoding_type::HASH_METADATA {
+        Some(input.read_u64::<LittleEndian>()?)
+    } else {
+        None
+    };
+    let hash_items = read_length(input)?;
+
+    let mut values = IndexMap::new();
+    let mut field_expiry = IndexMap::new();
+    for _ in 0..hash_items {
+        let (field, value, expires_at) = read_hash_metadata_entry(input, min_expire)?;
+        if let Some(expires_at) = expires_at {
+            field_expiry.insert(field.clone(), expires_at);
+        }
         values.insert(field, value);
     }
 
     Ok(RdbValue::Hash {
         key: key.to_vec(),
         values,
+        field_expiry,
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Hashtable),
+    })
+}
+
+/// Reads a field of a hash table with per-field expiry, its value and
+/// its expiry in milliseconds, if it has a TTL
+pub(crate) fn read_hash_metadata_entry<R: Read>(
+    input: &mut R,
+    min_expire: Option<u64>,
+) -> RdbResult<(Vec<u8>, Vec<u8>, Option<u64>)> {
+    let ttl = read_length64(input)?;
+    let field = read_blob(input)?;
+    let value = read_blob(input)?;
+    Ok((field, value, field_expires_at(ttl, min_expire)))
+}
+
+/// Expiry in milliseconds of a field with the TTL stored in front of it
+pub(crate) fn field_expires_at(ttl: u64, min_expire: Option<u64>) -> Option<u64> {
+    match (ttl, min_expire) {
+        (0, _) => None,
+        (ttl, Some(min_expire)) => Some((ttl - 1).saturating_add(min_expire)),
+        (ttl, None) => Some(ttl),
+    }
+}
+
+/// Reads a listpack of field, value and TTL triplets (Redis 7.4+).
+/// A TTL of 0 means the field does not expire.
+pub fn read_hash_list_pack_ex<R: Read>(
+    input: &mut R,
+    key: &[u8],
+    expiry: Option<u64>,
+    enc_type: u8,
+) -> RdbResult<RdbValue> {
+    if enc_type == encoding_type::HASH_LIST_PACK_EX {
+        let _min_expire = input.read_u64::<LittleEndian>()?;
+    }
+    let listpack = read_blob(input)?;
+    let listpack_size = listpack.len() as u64;
+    let (values, field_expiry) = read_hash_list_pack_ex_entries(&listpack)?;
+
+    Ok(RdbValue::Hash {
+        key: key.to_vec(),
+        values: into_owned_fields(values),
+        field_expiry: field_expiry
+            .into_iter()
+            .map(|(field, expires_at)| (field.into_owned(), expires_at))
+            .collect(),
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::ListPack(listpack_size)),
     })
 }
+
+/// Reads the fields, values and field expiries of a hash listpack with
+/// field TTLs held in memory, borrowing fields and values from it
+pub(crate) fn read_hash_list_pack_ex_entries(
+    listpack: &[u8],
+) -> RdbResult<(Vec<FieldValue<'_>>, Vec<FieldExpiry<'_>>)> {
+    read_list_pack_ex_fields(listpack).map_err(|err| err.within_blob("listpack"))
+}
+
+fn read_list_pack_ex_fields(
+    listpack: &[u8],
+) -> RdbResult<(Vec<FieldValue<'_>>, Vec<FieldExpiry<'_>>)> {
+    let mut cursor = 0;
+    let size = read_list_pack_length(listpack, &mut cursor)?;
+    if !size.is_multiple_of(3) {
+        return Err(RdbError::corrupt(
+            "listpack",
+            format!("Listpack length is not a multiple of 3: {}", size),
+        ));
+    }
+
+    let mut values = Vec::new();
+    let mut field_expiry = Vec::new();
+    let mut reader = &listpack[cursor..];
+
+    for _ in 0..size / 3 {
+        let field = read_list_pack_entry_as_bytes(&mut reader)?;
+        let value = read_list_pack_entry_as_bytes(&mut reader)?;
+        let ttl = read_list_pack_entry_as_int_from(&mut reader)?;
+        if ttl != 0 {
+            field_expiry.push((field.clone(), ttl as u64));
+        }
+        values.push((field, value));
+    }
+
+    Ok((values, field_expiry))
+}
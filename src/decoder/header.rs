@@ -0,0 +1,198 @@
+use super::common::utils::{verify_magic, verify_valkey_version, verify_version};
+use crate::constants::{encoding_type, op_code, version};
+use crate::types::{ErrorLocation, RdbError, RdbHeader, RdbOk, RdbResult, ServerFlavor};
+use std::io::Read;
+
+pub(crate) fn verify_header<R: Read>(input: &mut R) -> RdbResult<RdbHeader> {
+    let header = match verify_magic(input)? {
+        ServerFlavor::Valkey => RdbHeader {
+            version: verify_valkey_version(input)?,
+            flavor: ServerFlavor::Valkey,
+        },
+        ServerFlavor::Redis => {
+            let version = verify_version(input)?;
+            let flavor = if version >= version::VALKEY_MINIMUM {
+                ServerFlavor::Valkey
+            } else {
+                ServerFlavor::Redis
+            };
+            RdbHeader { version, flavor }
+        }
+    };
+    Ok(header)
+}
+
+// First Redis version storing each object type, None for unknown types
+fn type_min_version(enc_type: u8) -> Option<u32> {
+    let min_version = match enc_type {
+        encoding_type::STRING
+        | encoding_type::LIST
+        | encoding_type::SET
+        | encoding_type::ZSET
+        | encoding_type::HASH
+        | encoding_type::HASH_ZIPMAP
+        | encoding_type::LIST_ZIPLIST
+        | encoding_type::SET_INTSET
+        | encoding_type::ZSET_ZIPLIST
+        | encoding_type::HASH_ZIPLIST => 1,
+        encoding_type::LIST_QUICKLIST => 7,
+        encoding_type::ZSET_2 | encoding_type::MODULE => 8,
+        encoding_type::MODULE_2 | encoding_type::STREAM_LIST_PACKS => 9,
+        encoding_type::HASH_LIST_PACK
+        | encoding_type::ZSET_LIST_PACK
+        | encoding_type::LIST_QUICKLIST_2
+        | encoding_type::STREAM_LIST_PACKS_2 => 10,
+        encoding_type::SET_LIST_PACK | encoding_type::STREAM_LIST_PACKS_3 => 11,
+        encoding_type::HASH_METADATA_PRE_GA
+        | encoding_type::HASH_LIST_PACK_EX_PRE_GA
+        | encoding_type::HASH_METADATA
+        | encoding_type::HASH_LIST_PACK_EX => 12,
+        _ => return None,
+    };
+    Some(min_version)
+}
+
+// First Redis version storing each opcode, None for unknown opcodes
+fn opcode_min_version(opcode: u8) -> Option<u32> {
+    let min_version = match opcode {
+        op_code::EOF | op_code::SELECTDB | op_code::EXPIRETIME | op_code::EXPIRETIME_MS => 1,
+        op_code::AUX | op_code::RESIZEDB => 7,
+        op_code::MODULE_AUX | op_code::IDLE | op_code::FREQ => 9,
+        op_code::FUNCTION_PRE_GA | op_code::FUNCTION2 => 10,
+        op_code::SLOT_INFO => 12,
+        _ => return None,
+    };
+    Some(min_version)
+}
+
+impl RdbHeader {
+    // Valkey dumps contain the constructs of the Redis version Valkey forked from
+    fn redis_version(&self) -> u32 {
+        match self.flavor {
+            ServerFlavor::Redis => self.version,
+            ServerFlavor::Valkey => self.version.min(version::VALKEY_BASE),
+        }
+    }
+
+    /// Whether a dump of this version may contain objects of the given type
+    pub fn supports_type(&self, enc_type: u8) -> bool {
+        type_min_version(enc_type).is_some_and(|min| min <= self.redis_version())
+    }
+
+    /// Whether a dump of this version may contain the given opcode
+    pub fn supports_opcode(&self, opcode: u8) -> bool {
+        opcode_min_version(opcode).is_some_and(|min| min <= self.redis_version())
+    }
+
+    /// Whether a dump of this version ends with a checksum
+    pub fn has_checksum(&self) -> bool {
+        self.redis_version() >= version::CHECKSUM_MINIMUM
+    }
+}
+
+fn flavor_name(flavor: ServerFlavor) -> &'static str {
+    match flavor {
+        ServerFlavor::Redis => "Redis",
+        ServerFlavor::Valkey => "Valkey",
+    }
+}
+
+/// Checks that the type or opcode read at the start of an entry may occur in this dump
+pub(crate) fn check_operation(header: &RdbHeader, operation: u8) -> RdbOk {
+    let (kind, known, supported) = if operation >= op_code::SLOT_INFO {
+        (
+            "opcode",
+            opcode_min_version(operation).is_some(),
+            header.supports_opcode(operation),
+        )
+    } else {
+        (
+            "type",
+            type_min_version(operation).is_some(),
+            header.supports_type(operation),
+        )
+    };
+
+    if supported {
+        return Ok(());
+    }
+    let message = if known {
+        format!(
+            "{} {} is not supported by {} RDB version {}",
+            kind,
+            operation,
+            flavor_name(header.flavor),
+            header.version
+        )
+    } else {
+        format!(
+            "Unknown {} {} in {} RDB version {}",
+            kind,
+            operation,
+            flavor_name(header.flavor),
+            header.version
+        )
+    };
+    Err(RdbError::InvalidOperation {
+        operation,
+        message,
+        location: ErrorLocation::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_verify_header() {
+        let header = verify_header(&mut Cursor::new(b"REDIS0011".to_vec())).unwrap();
+        assert_eq!(
+            header,
+            RdbHeader {
+                version: 11,
+                flavor: ServerFlavor::Redis
+            }
+        );
+
+        let header = verify_header(&mut Cursor::new(b"VALKEY080".to_vec())).unwrap();
+        assert_eq!(
+            header,
+            RdbHeader {
+                version: 80,
+                flavor: ServerFlavor::Valkey
+            }
+        );
+
+        let header = verify_header(&mut Cursor::new(b"REDIS0080".to_vec())).unwrap();
+        assert_eq!(header.flavor, ServerFlavor::Valkey);
+    }
+
+    #[test]
+    fn test_check_operation() {
+        let redis_11 = RdbHeader {
+            version: 11,
+            flavor: ServerFlavor::Redis,
+        };
+        let redis_13 = RdbHeader {
+            version: 13,
+            flavor: ServerFlavor::Redis,
+        };
+        let valkey_80 = RdbHeader {
+            version: 80,
+            flavor: ServerFlavor::Valkey,
+        };
+
+        assert!(check_operation(&redis_11, encoding_type::SET_LIST_PACK).is_ok());
+        assert!(check_operation(&redis_11, encoding_type::HASH_LIST_PACK_EX).is_err());
+        assert!(check_operation(&redis_13, encoding_type::HASH_LIST_PACK_EX).is_ok());
+        assert!(check_operation(&valkey_80, encoding_type::SET_LIST_PACK).is_ok());
+        assert!(check_operation(&valkey_80, encoding_type::HASH_METADATA).is_err());
+        assert!(check_operation(&valkey_80, op_code::FUNCTION2).is_ok());
+        assert!(check_operation(&valkey_80, op_code::SLOT_INFO).is_err());
+
+        let error = check_operation(&redis_13, 42).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown type 42 in Redis RDB version 13");
+    }
+}
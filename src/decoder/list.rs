@@ -1,10 +1,8 @@
 use super::common::utils::{read_blob, read_length, read_sequence};
-use super::common::{
-    read_list_pack_entry_as_string, read_ziplist_entry_string, read_ziplist_metadata,
-};
-use crate::types::{RdbError, RdbResult, RdbValue};
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{Cursor, Read};
+use super::common::{read_list_pack_entries, read_ziplist_entries};
+use crate::types::{EncodingType, KeyMeta, RdbError, RdbResult, RdbValue};
+use std::borrow::Cow;
+use std::io::Read;
 
 pub fn read_linked_list<R: Read>(
     input: &mut R,
@@ -17,6 +15,7 @@ pub fn read_linked_list<R: Read>(
         key: key.to_vec(),
         values,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::LinkedList),
     })
 }
 
@@ -26,28 +25,17 @@ pub fn read_list_ziplist<R: Read>(
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let ziplist = read_blob(input)?;
-    let mut reader = Cursor::new(ziplist);
-    let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
-
-    let mut values = Vec::with_capacity(zllen as usize);
-
-    for _ in 0..zllen {
-        let entry = read_ziplist_entry_string(&mut reader)?;
-        values.push(entry);
-    }
-
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_list_ziplist",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
-    }
+    let ziplist_size = ziplist.len() as u64;
+    let values = read_ziplist_entries(&ziplist)?
+        .into_iter()
+        .map(Cow::into_owned)
+        .collect();
 
     Ok(RdbValue::List {
         key: key.to_vec(),
         values,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Ziplist(ziplist_size)),
     })
 }
 
@@ -68,6 +56,7 @@ pub fn read_quicklist<R: Read>(
         key: key.to_vec(),
         values,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Quicklist),
     })
 }
 
@@ -80,79 +69,42 @@ pub fn read_quicklist_2<R: Read>(
     let mut values = Vec::new();
 
     for _ in 0..len {
-        let container_type = read_length(input)?;
-        match container_type {
-            1 => {
-                // QUICKLIST_NODE_CONTAINER_PLAIN
-                let entry = read_blob(input)?;
-                values.push(entry);
-            }
-            2 => {
-                // QUICKLIST_NODE_CONTAINER_PACKED
-                let mut listpack_values = read_quicklist_listpack(input)?;
-                values.append(&mut listpack_values);
-            }
-            _ => {
-                return Err(RdbError::ParsingError {
-                    context: "read_quicklist_2",
-                    message: format!("Unknown encoding value: {}", container_type),
-                })
-            }
-        }
+        let mut node_values = read_quicklist_2_node(input)?;
+        values.append(&mut node_values);
     }
 
     Ok(RdbValue::List {
         key: key.to_vec(),
         values,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Quicklist2),
     })
 }
 
-fn read_quicklist_ziplist<R: Read>(input: &mut R, _key: &[u8]) -> RdbResult<Vec<Vec<u8>>> {
-    let ziplist = read_blob(input)?;
-    let mut reader = Cursor::new(ziplist);
-    let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
-
-    let mut values = Vec::with_capacity(zllen as usize);
-
-    for _ in 0..zllen {
-        let entry = read_ziplist_entry_string(&mut reader)?;
-        values.push(entry);
-    }
-
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_quicklist_ziplist",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
+/// Reads the elements of a single node of a quicklist
+pub(crate) fn read_quicklist_2_node<R: Read>(input: &mut R) -> RdbResult<Vec<Vec<u8>>> {
+    let container_type = read_length(input)?;
+    match container_type {
+        // QUICKLIST_NODE_CONTAINER_PLAIN
+        1 => Ok(vec![read_blob(input)?]),
+        // QUICKLIST_NODE_CONTAINER_PACKED
+        2 => {
+            let listpack = read_blob(input)?;
+            let values = read_list_pack_entries(&listpack)?;
+            Ok(values.into_iter().map(Cow::into_owned).collect())
+        }
+        _ => Err(RdbError::corrupt(
+            "quicklist",
+            format!("Unknown encoding value: {}", container_type),
+        )),
     }
-
-    Ok(values)
 }
 
-fn read_quicklist_listpack<R: Read>(input: &mut R) -> RdbResult<Vec<Vec<u8>>> {
-    let listpack = read_blob(input)?;
-    let mut reader = Cursor::new(listpack);
-    let total_bytes = reader.read_u32::<LittleEndian>()?;
-    let num_elements = reader.read_u16::<LittleEndian>()?;
-
-    let mut values = Vec::with_capacity(num_elements as usize);
-
-    // Read until we reach the end of the listpack
-    while reader.position() < total_bytes as u64 - 1 {
-        let entry = read_list_pack_entry_as_string(&mut reader)?;
-        values.push(entry);
-    }
-
-    // Verify end byte
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_quicklist_listpack",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
-    }
-
-    Ok(values)
+pub(crate) fn read_quicklist_ziplist<R: Read>(
+    input: &mut R,
+    _key: &[u8],
+) -> RdbResult<Vec<Vec<u8>>> {
+    let ziplist = read_blob(input)?;
+    let values = read_ziplist_entries(&ziplist)?;
+    Ok(values.into_iter().map(Cow::into_owned).collect())
 }
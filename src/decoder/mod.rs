@@ -1,31 +1,133 @@
 mod common;
+mod elements;
 mod hash;
+mod header;
 mod list;
+mod module;
+mod parallel;
 mod rdb;
 mod set;
+mod slice;
 mod sorted_set;
+mod stream;
 
-use std::io::Read;
+use std::io::{Read, Seek};
 
+use self::common::CountingReader;
+use self::module::ModuleRegistry;
 use self::rdb::DecoderState;
 use crate::filter::Filter;
-use crate::types::{RdbResult, RdbValue};
+use crate::types::{FilteredKeys, RdbHeader, RdbResult, RdbValue, TruncationReport};
+
+pub use self::module::{ModuleDecoder, ModuleReader};
+pub use self::parallel::ParallelDecoder;
+pub use self::slice::SliceDecoder;
+
+pub(crate) use self::common::utils::decompress_lzf;
+
+fn read_header<R: Read>(reader: &mut CountingReader<R>) -> RdbResult<RdbHeader> {
+    // The checksum covers the header, which is only read here
+    reader.track_checksum(true);
+    let header =
+        header::verify_header(reader).map_err(|err| err.at(reader.position(), None, None))?;
+    reader.track_checksum(false);
+    Ok(header)
+}
 
 pub struct RdbDecoder<R: Read, F: Filter> {
-    reader: R,
+    reader: CountingReader<R>,
     filter: F,
+    modules: ModuleRegistry,
     state: DecoderState,
 }
 
 impl<R: Read, F: Filter> RdbDecoder<R, F> {
-    pub(crate) fn new(mut reader: R, filter: F) -> RdbResult<Self> {
-        rdb::verify_header(&mut reader)?;
+    pub fn new(reader: R, filter: F) -> RdbResult<Self> {
+        let mut reader = CountingReader::new(reader);
+        let header = read_header(&mut reader)?;
         Ok(Self {
             reader,
             filter,
-            state: DecoderState::default(),
+            modules: ModuleRegistry::default(),
+            state: DecoderState {
+                header,
+                ..Default::default()
+            },
         })
     }
+
+    /// Version and server flavor of the dump.
+    /// The flavor is final once the aux fields at the start of the dump are read.
+    pub fn header(&self) -> RdbHeader {
+        self.state.header
+    }
+
+    /// Verifies the CRC64 checksum at the end of the dump and fails with
+    /// `RdbError::ChecksumMismatch` if it does not match the data read.
+    /// Has to be enabled before the first value is read.
+    pub fn verify_checksum(&mut self, enabled: bool) {
+        self.reader.track_checksum(enabled);
+        self.state.verify_checksum = enabled;
+    }
+
+    /// Skips keys whose value fails to decode instead of stopping.
+    /// The error is still returned, and decoding continues with the next key
+    /// as long as the outer length of the value could be read. Errors leaving
+    /// the position of the next entry unknown end the iteration.
+    pub fn skip_corrupt_keys(&mut self, enabled: bool) {
+        self.state.skip_corrupt_keys = enabled;
+    }
+
+    /// Yields collections as `RdbValue::StartCollection`, followed by an
+    /// `RdbValue::Element` per element and `RdbValue::EndCollection`,
+    /// instead of a single value holding all elements.
+    ///
+    /// Lists, sets, sorted sets and hashes stored as hash tables, skiplists
+    /// or quicklists are read one element, or one quicklist node, at a time,
+    /// so memory stays bounded regardless of their size. The length in the
+    /// metadata of such keys only covers the bytes up to their first element,
+    /// and they cannot be skipped when they fail to decode. Compact encodings
+    /// are decoded as a whole before their elements are yielded.
+    pub fn stream_elements(&mut self, enabled: bool) {
+        self.state.stream_elements = enabled;
+    }
+
+    /// Whether all entries were read, or decoding stopped at an error
+    /// it cannot recover from
+    pub fn is_finished(&self) -> bool {
+        self.state.reached_eof
+    }
+
+    /// Number of keys left out by the filter so far
+    pub fn filtered_keys(&self) -> FilteredKeys {
+        self.state.filtered_keys
+    }
+
+    /// How far the dump could be read, once decoding stopped at its truncated end
+    pub fn truncation(&self) -> Option<&TruncationReport> {
+        self.state.truncation.as_ref()
+    }
+
+    /// Registers a decoder for values of a specific module type
+    pub fn add_module_decoder(&mut self, decoder: Box<dyn ModuleDecoder>) {
+        self.modules.add(decoder);
+    }
+
+    /// Keeps the payload of module values without a registered decoder
+    /// as raw fields instead of skipping it
+    pub fn capture_module_payloads(&mut self, capture: bool) {
+        self.modules.set_capture_payloads(capture);
+    }
+}
+
+impl<R: Read + Seek, F: Filter> RdbDecoder<R, F> {
+    /// Seeks over the values of filtered keys instead of reading them, so
+    /// that decoding a small part of a dump does not read all of it.
+    /// Skipped bytes are still read while the checksum is verified.
+    pub fn skip_by_seeking(&mut self) -> RdbResult<()> {
+        self.reader.enable_seek()?;
+        Ok(())
+    }
 }
 
 impl<R: Read, F: Filter> Iterator for RdbDecoder<R, F> {
@@ -38,6 +140,7 @@ impl<R: Read, F: Filter> Iterator for RdbDecoder<R, F> {
         Some(rdb::process_next_operation(
             &mut self.reader,
             &self.filter,
+            &self.modules,
             &mut self.state,
         ))
     }
@@ -0,0 +1,334 @@
+use super::{ModuleDecoder, ModuleReader};
+use crate::types::{
+    BloomFilter, CountMinSketch, CuckooFilter, ModuleValue, RdbError, RdbResult, TopK,
+};
+
+// Encoding versions of the RedisBloom types
+const BLOOM_MAX_VERSION: u64 = 4;
+const BLOOM_MIN_OPTIONS_VERSION: u64 = 2;
+const BLOOM_MIN_GROWTH_VERSION: u64 = 4;
+const CUCKOO_MAX_VERSION: u64 = 4;
+const CUCKOO_MIN_EXPANSION_VERSION: u64 = 4;
+const COUNT_MIN_MAX_VERSION: u64 = 0;
+const TOPK_MAX_VERSION: u64 = 1;
+
+// Defaults of cuckoo filters stored before expansion support
+const CUCKOO_DEFAULT_BUCKET_SIZE: u64 = 2;
+const CUCKOO_DEFAULT_MAX_ITERATIONS: u64 = 20;
+const CUCKOO_DEFAULT_EXPANSION: u64 = 1;
+
+fn check_version(context: &'static str, version: u64, max_version: u64) -> RdbResult<()> {
+    if version > max_version {
+        return Err(RdbError::corrupt(
+            context,
+            format!("Unknown encoding version: {}", version),
+        ));
+    }
+    Ok(())
+}
+
+/// Decoder for scalable Bloom filters (`MBbloom--`).
+///
+/// The chain header holds the number of items and filters, followed by
+/// every filter with its capacity, error rate and bit array.
+pub struct BloomDecoder;
+
+impl ModuleDecoder for BloomDecoder {
+    fn name(&self) -> &str {
+        "MBbloom--"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        check_version("MBbloom--", version, BLOOM_MAX_VERSION)?;
+
+        let items = reader.read_unsigned()?;
+        let filters = reader.read_unsigned()?;
+        if version >= BLOOM_MIN_OPTIONS_VERSION {
+            let _options = reader.read_unsigned()?;
+        }
+        let expansion = if version >= BLOOM_MIN_GROWTH_VERSION {
+            reader.read_unsigned()?
+        } else {
+            2
+        };
+
+        let mut capacity = 0u64;
+        let mut error_rate = 0.0;
+        let mut bytes = 0u64;
+        for i in 0..filters {
+            let entries = reader.read_unsigned()?;
+            let error = reader.read_double()?;
+            let _hashes = reader.read_unsigned()?;
+            let _bits_per_entry = reader.read_double()?;
+            if version > 0 {
+                let _bits = reader.read_unsigned()?;
+                let _n2 = reader.read_unsigned()?;
+            }
+            let bit_array = reader.read_string()?;
+            let _size = reader.read_unsigned()?;
+
+            if i == 0 {
+                error_rate = error;
+            }
+            capacity = capacity.saturating_add(entries);
+            bytes = bytes.saturating_add(bit_array.len() as u64);
+        }
+
+        Ok(ModuleValue::Bloom(BloomFilter {
+            capacity,
+            error_rate,
+            items,
+            filters,
+            expansion,
+            bytes,
+        }))
+    }
+}
+
+/// Decoder for Cuckoo filters (`MBbloomCF`)
+pub struct CuckooDecoder;
+
+impl ModuleDecoder for CuckooDecoder {
+    fn name(&self) -> &str {
+        "MBbloomCF"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        check_version("MBbloomCF", version, CUCKOO_MAX_VERSION)?;
+
+        let filters = reader.read_unsigned()?;
+        let buckets = reader.read_unsigned()?;
+        let items = reader.read_unsigned()?;
+        let (deletes, bucket_size, max_iterations, expansion) =
+            if version >= CUCKOO_MIN_EXPANSION_VERSION {
+                (
+                    reader.read_unsigned()?,
+                    reader.read_unsigned()?,
+                    reader.read_unsigned()?,
+                    reader.read_unsigned()?,
+                )
+            } else {
+                (
+                    0,
+                    CUCKOO_DEFAULT_BUCKET_SIZE,
+                    CUCKOO_DEFAULT_MAX_ITERATIONS,
+                    CUCKOO_DEFAULT_EXPANSION,
+                )
+            };
+
+        let mut capacity = 0u64;
+        let mut filter_buckets = buckets;
+        for _ in 0..filters {
+            if version >= CUCKOO_MIN_EXPANSION_VERSION {
+                filter_buckets = reader.read_unsigned()?;
+            }
+            let _data = reader.read_string()?;
+            capacity = capacity.saturating_add(filter_buckets.saturating_mul(bucket_size));
+            if version < CUCKOO_MIN_EXPANSION_VERSION {
+                filter_buckets = filter_buckets.saturating_mul(expansion);
+            }
+        }
+
+        Ok(ModuleValue::Cuckoo(CuckooFilter {
+            capacity,
+            bucket_size,
+            items,
+            deletes,
+            filters,
+            expansion,
+            max_iterations,
+        }))
+    }
+}
+
+/// Decoder for Count-Min sketches (`CMSk-TYPE`)
+pub struct CountMinSketchDecoder;
+
+impl ModuleDecoder for CountMinSketchDecoder {
+    fn name(&self) -> &str {
+        "CMSk-TYPE"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        check_version("CMSk-TYPE", version, COUNT_MIN_MAX_VERSION)?;
+
+        let width = reader.read_unsigned()?;
+        let depth = reader.read_unsigned()?;
+        let count = reader.read_unsigned()?;
+        let _counters = reader.read_string()?;
+
+        Ok(ModuleValue::CountMinSketch(CountMinSketch {
+            width,
+            depth,
+            count,
+        }))
+    }
+}
+
+/// Decoder for Top-K sketches (`TopK-TYPE`).
+///
+/// The heap is followed by one string per heap slot holding the tracked
+/// item, unused slots are stored as a single NUL byte.
+pub struct TopKDecoder;
+
+impl ModuleDecoder for TopKDecoder {
+    fn name(&self) -> &str {
+        "TopK-TYPE"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        check_version("TopK-TYPE", version, TOPK_MAX_VERSION)?;
+
+        let k = reader.read_unsigned()?;
+        let width = reader.read_unsigned()?;
+        let depth = reader.read_unsigned()?;
+        let decay = reader.read_double()?;
+        let _buckets = reader.read_string()?;
+        let _heap = reader.read_string()?;
+
+        let mut items = Vec::new();
+        for _ in 0..k {
+            let item = reader.read_string()?;
+            if item.len() > 1 || item.first().is_some_and(|&b| b != 0) {
+                items.push(item);
+            }
+        }
+
+        Ok(ModuleValue::TopK(TopK {
+            k,
+            width,
+            depth,
+            decay,
+            items,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::module_opcode;
+    use std::io::Cursor;
+
+    fn uint(out: &mut Vec<u8>, val: u8) {
+        out.extend_from_slice(&[module_opcode::UINT as u8, val]);
+    }
+
+    fn double(out: &mut Vec<u8>, val: f64) {
+        out.push(module_opcode::DOUBLE as u8);
+        out.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn string(out: &mut Vec<u8>, val: &[u8]) {
+        out.extend_from_slice(&[module_opcode::STRING as u8, val.len() as u8]);
+        out.extend_from_slice(val);
+    }
+
+    fn decode(decoder: &dyn ModuleDecoder, input: Vec<u8>, version: u64) -> ModuleValue {
+        let mut cursor = Cursor::new(input);
+        let mut reader = ModuleReader::new(&mut cursor, true);
+        let value = decoder.decode(&mut reader, version).unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+        value
+    }
+
+    #[test]
+    fn test_decode_bloom() {
+        let mut input = Vec::new();
+        uint(&mut input, 7);
+        uint(&mut input, 2);
+        uint(&mut input, 0);
+        uint(&mut input, 2);
+        for (entries, error) in [(10, 0.01), (20, 0.005)] {
+            uint(&mut input, entries);
+            double(&mut input, error);
+            uint(&mut input, 7);
+            double(&mut input, 9.5);
+            uint(&mut input, 60);
+            uint(&mut input, 7);
+            string(&mut input, &[0; 12]);
+            uint(&mut input, 3);
+        }
+
+        assert_eq!(
+            decode(&BloomDecoder, input, 4),
+            ModuleValue::Bloom(BloomFilter {
+                capacity: 30,
+                error_rate: 0.01,
+                items: 7,
+                filters: 2,
+                expansion: 2,
+                bytes: 24,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_cuckoo() {
+        let mut input = Vec::new();
+        uint(&mut input, 1);
+        uint(&mut input, 8);
+        uint(&mut input, 5);
+        uint(&mut input, 1);
+        uint(&mut input, 2);
+        uint(&mut input, 20);
+        uint(&mut input, 1);
+        uint(&mut input, 8);
+        string(&mut input, &[0; 16]);
+
+        assert_eq!(
+            decode(&CuckooDecoder, input, 4),
+            ModuleValue::Cuckoo(CuckooFilter {
+                capacity: 16,
+                bucket_size: 2,
+                items: 5,
+                deletes: 1,
+                filters: 1,
+                expansion: 1,
+                max_iterations: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_count_min_sketch() {
+        let mut input = Vec::new();
+        uint(&mut input, 4);
+        uint(&mut input, 2);
+        uint(&mut input, 11);
+        string(&mut input, &[0; 32]);
+
+        assert_eq!(
+            decode(&CountMinSketchDecoder, input, 0),
+            ModuleValue::CountMinSketch(CountMinSketch {
+                width: 4,
+                depth: 2,
+                count: 11,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_topk() {
+        let mut input = Vec::new();
+        uint(&mut input, 2);
+        uint(&mut input, 8);
+        uint(&mut input, 3);
+        double(&mut input, 0.9);
+        string(&mut input, &[0; 48]);
+        string(&mut input, &[0; 48]);
+        string(&mut input, b"apple");
+        string(&mut input, &[0]);
+
+        assert_eq!(
+            decode(&TopKDecoder, input, 1),
+            ModuleValue::TopK(TopK {
+                k: 2,
+                width: 8,
+                depth: 3,
+                decay: 0.9,
+                items: vec![b"apple".to_vec()],
+            })
+        );
+    }
+}
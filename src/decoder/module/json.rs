@@ -0,0 +1,255 @@
+use super::{ModuleDecoder, ModuleReader};
+use crate::types::{ModuleValue, RdbError, RdbResult};
+use rustc_serialize::json::{self, JsonEvent};
+
+// Node types of the tree encoding used by RedisJSON 1.x
+const NODE_NULL: u64 = 0x1;
+const NODE_STRING: u64 = 0x2;
+const NODE_NUMBER: u64 = 0x4;
+const NODE_INTEGER: u64 = 0x8;
+const NODE_BOOLEAN: u64 = 0x10;
+const NODE_DICT: u64 = 0x20;
+const NODE_ARRAY: u64 = 0x40;
+const NODE_KEYVAL: u64 = 0x80;
+
+// Same nesting limit RedisJSON enforces on documents
+const MAX_DEPTH: usize = 128;
+
+/// Decoder for RedisJSON documents (`ReJSON-RL`).
+///
+/// Encoding version 0 stores the document as a tree of typed nodes,
+/// versions 2 and 3 store the serialized JSON text.
+pub struct JsonDecoder;
+
+impl ModuleDecoder for JsonDecoder {
+    fn name(&self) -> &str {
+        "ReJSON-RL"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        let document = match version {
+            0 => {
+                let mut document = String::new();
+                read_node(reader, &mut document, 0)?;
+                document
+            }
+            2 | 3 => {
+                let document = String::from_utf8(reader.read_string()?)
+                    .map_err(|_| RdbError::corrupt("ReJSON-RL", "Document is not valid UTF-8"))?;
+                // Formatters embed the document as it is, so it has to be valid JSON
+                validate_document(&document)?;
+                document
+            }
+            _ => {
+                return Err(RdbError::corrupt(
+                    "ReJSON-RL",
+                    format!("Unknown encoding version: {}", version),
+                ))
+            }
+        };
+        Ok(ModuleValue::Json(document))
+    }
+}
+
+fn validate_document(document: &str) -> RdbResult<()> {
+    let mut depth = 0;
+    for event in json::Parser::new(document.chars()) {
+        match event {
+            JsonEvent::ObjectStart | JsonEvent::ArrayStart => {
+                depth += 1;
+                if depth > MAX_DEPTH {
+                    return Err(RdbError::corrupt(
+                        "ReJSON-RL",
+                        format!("Document nesting exceeds {} levels", MAX_DEPTH),
+                    ));
+                }
+            }
+            JsonEvent::ObjectEnd | JsonEvent::ArrayEnd => depth -= 1,
+            JsonEvent::Error(err) => {
+                return Err(RdbError::corrupt(
+                    "ReJSON-RL",
+                    format!("Document is not valid JSON: {}", err),
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read_json_string(reader: &mut ModuleReader<'_>, out: &mut String) -> RdbResult<()> {
+    let buf = reader.read_string()?;
+    let encoded = json::encode(&String::from_utf8_lossy(&buf).as_ref()).map_err(|err| {
+        RdbError::corrupt("ReJSON-RL", format!("Failed to encode string: {}", err))
+    })?;
+    out.push_str(&encoded);
+    Ok(())
+}
+
+fn read_node(reader: &mut ModuleReader<'_>, out: &mut String, depth: usize) -> RdbResult<()> {
+    if depth > MAX_DEPTH {
+        return Err(RdbError::corrupt(
+            "ReJSON-RL",
+            format!("Document nesting exceeds {} levels", MAX_DEPTH),
+        ));
+    }
+
+    match reader.read_unsigned()? {
+        NODE_NULL => out.push_str("null"),
+        NODE_BOOLEAN => {
+            let buf = reader.read_string()?;
+            out.push_str(if buf.first() == Some(&b'1') {
+                "true"
+            } else {
+                "false"
+            });
+        }
+        NODE_INTEGER => out.push_str(&reader.read_signed()?.to_string()),
+        NODE_NUMBER => {
+            let val = reader.read_double()?;
+            if !val.is_finite() {
+                return Err(RdbError::corrupt(
+                    "ReJSON-RL",
+                    format!("Invalid number: {}", val),
+                ));
+            }
+            out.push_str(&format!("{:?}", val));
+        }
+        NODE_STRING => read_json_string(reader, out)?,
+        NODE_DICT => {
+            let len = reader.read_unsigned()?;
+            out.push('{');
+            for i in 0..len {
+                if i > 0 {
+                    out.push(',');
+                }
+                let node_type = reader.read_unsigned()?;
+                if node_type != NODE_KEYVAL {
+                    return Err(RdbError::corrupt(
+                        "ReJSON-RL",
+                        format!("Expected a key-value node, found {}", node_type),
+                    ));
+                }
+                read_json_string(reader, out)?;
+                out.push(':');
+                read_node(reader, out, depth + 1)?;
+            }
+            out.push('}');
+        }
+        NODE_ARRAY => {
+            let len = reader.read_unsigned()?;
+            out.push('[');
+            for i in 0..len {
+                if i > 0 {
+                    out.push(',');
+                }
+                read_node(reader, out, depth + 1)?;
+            }
+            out.push(']');
+        }
+        node_type => {
+            return Err(RdbError::corrupt(
+                "ReJSON-RL",
+                format!("Unknown node type: {}", node_type),
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::module_opcode;
+    use std::io::Cursor;
+
+    fn uint(out: &mut Vec<u8>, val: u64) {
+        out.push(module_opcode::UINT as u8);
+        if val < 64 {
+            out.push(val as u8);
+        } else {
+            out.extend_from_slice(&[0x40 | (val >> 8) as u8, val as u8]);
+        }
+    }
+
+    fn string(out: &mut Vec<u8>, val: &[u8]) {
+        out.extend_from_slice(&[module_opcode::STRING as u8, val.len() as u8]);
+        out.extend_from_slice(val);
+    }
+
+    fn decode(input: Vec<u8>, version: u64) -> RdbResult<ModuleValue> {
+        let mut cursor = Cursor::new(input);
+        let mut reader = ModuleReader::new(&mut cursor, true);
+        JsonDecoder.decode(&mut reader, version)
+    }
+
+    #[test]
+    fn test_decode_tree_encoding() {
+        // {"name":"rdb","tags":[1,2.5,true,null]}
+        let mut input = Vec::new();
+        uint(&mut input, NODE_DICT);
+        uint(&mut input, 2);
+        uint(&mut input, NODE_KEYVAL);
+        string(&mut input, b"name");
+        uint(&mut input, NODE_STRING);
+        string(&mut input, b"rdb");
+        uint(&mut input, NODE_KEYVAL);
+        string(&mut input, b"tags");
+        uint(&mut input, NODE_ARRAY);
+        uint(&mut input, 4);
+        uint(&mut input, NODE_INTEGER);
+        input.extend_from_slice(&[module_opcode::SINT as u8, 1]);
+        uint(&mut input, NODE_NUMBER);
+        input.push(module_opcode::DOUBLE as u8);
+        input.extend_from_slice(&2.5f64.to_le_bytes());
+        uint(&mut input, NODE_BOOLEAN);
+        string(&mut input, b"1");
+        uint(&mut input, NODE_NULL);
+
+        assert_eq!(
+            decode(input, 0).unwrap(),
+            ModuleValue::Json(r#"{"name":"rdb","tags":[1,2.5,true,null]}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_serialized_encoding() {
+        let mut input = Vec::new();
+        string(&mut input, br#"{"a":[1,2]}"#);
+
+        assert_eq!(
+            decode(input, 3).unwrap(),
+            ModuleValue::Json(r#"{"a":[1,2]}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_document() {
+        for document in [
+            &br#"{"a":[1,2]"#[..],
+            br#"{"a":1} {"b":2}"#,
+            br#"{"a":1},"injected":true"#,
+            b"",
+        ] {
+            let mut input = Vec::new();
+            string(&mut input, document);
+            assert!(decode(input, 3).is_err());
+        }
+
+        let nested = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert!(validate_document(&nested).is_err());
+        assert!(validate_document(&nested[1..nested.len() - 1]).is_ok());
+    }
+
+    #[test]
+    fn test_decode_rejects_deep_nesting() {
+        let mut input = Vec::new();
+        for _ in 0..=MAX_DEPTH + 1 {
+            uint(&mut input, NODE_ARRAY);
+            uint(&mut input, 1);
+        }
+        uint(&mut input, NODE_NULL);
+
+        assert!(decode(input, 0).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+mod bloom;
+mod json;
+mod timeseries;
+
+use super::common::utils::{read_blob, read_length64};
+use crate::constants::{encoding_type, module_opcode};
+use crate::types::{
+    EncodingType, KeyMeta, ModuleField, ModuleId, ModuleValue, RdbError, RdbResult, RdbValue,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Read;
+
+/// Decodes the payload of a specific module type into a structured value.
+///
+/// Decoders are looked up by the 9 character module type name, e.g.
+/// `ReJSON-RL`, and read the payload in the same order the module's
+/// `rdb_load` callback does, using the typed accessors of `ModuleReader`.
+pub trait ModuleDecoder: Send + Sync {
+    /// The module type name this decoder handles
+    fn name(&self) -> &str;
+
+    /// Decodes a value stored with the given encoding version
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue>;
+}
+
+/// Reads the values a module stored for a key.
+///
+/// Values of `MODULE_2` objects are prefixed by an opcode describing their
+/// type and the payload is terminated by an EOF opcode, so they can be
+/// checked and skipped without knowing the module. Values of legacy `MODULE`
+/// objects are stored without opcodes.
+pub struct ModuleReader<'a> {
+    input: &'a mut dyn Read,
+    with_opcodes: bool,
+    reached_eof: bool,
+}
+
+impl<'a> ModuleReader<'a> {
+    pub(crate) fn new(input: &'a mut dyn Read, with_opcodes: bool) -> ModuleReader<'a> {
+        ModuleReader {
+            input,
+            with_opcodes,
+            reached_eof: false,
+        }
+    }
+
+    fn expect_opcode(&mut self, expected: u64) -> RdbResult<()> {
+        if !self.with_opcodes {
+            return Ok(());
+        }
+        let opcode = read_length64(&mut self.input)?;
+        if opcode != expected {
+            return Err(RdbError::corrupt(
+                "module",
+                format!("Expected opcode {}, found {}", expected, opcode),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read_unsigned(&mut self) -> RdbResult<u64> {
+        self.expect_opcode(module_opcode::UINT)?;
+        read_length64(&mut self.input)
+    }
+
+    pub fn read_signed(&mut self) -> RdbResult<i64> {
+        self.expect_opcode(module_opcode::SINT)?;
+        Ok(read_length64(&mut self.input)? as i64)
+    }
+
+    pub fn read_float(&mut self) -> RdbResult<f32> {
+        self.expect_opcode(module_opcode::FLOAT)?;
+        Ok(self.input.read_f32::<LittleEndian>()?)
+    }
+
+    pub fn read_double(&mut self) -> RdbResult<f64> {
+        self.expect_opcode(module_opcode::DOUBLE)?;
+        Ok(self.input.read_f64::<LittleEndian>()?)
+    }
+
+    pub fn read_string(&mut self) -> RdbResult<Vec<u8>> {
+        self.expect_opcode(module_opcode::STRING)?;
+        read_blob(&mut self.input)
+    }
+
+    /// Reads the next value together with its type.
+    /// Returns `None` once the end of the payload is reached.
+    pub fn read_field(&mut self) -> RdbResult<Option<ModuleField>> {
+        if !self.with_opcodes {
+            return Err(RdbError::unsupported(
+                "Values of MODULE objects carry no type information",
+            ));
+        }
+        if self.reached_eof {
+            return Ok(None);
+        }
+
+        let field = match read_length64(&mut self.input)? {
+            module_opcode::EOF => {
+                self.reached_eof = true;
+                return Ok(None);
+            }
+            module_opcode::SINT => ModuleField::SInt(read_length64(&mut self.input)? as i64),
+            module_opcode::UINT => ModuleField::UInt(read_length64(&mut self.input)?),
+            module_opcode::FLOAT => ModuleField::Float(self.input.read_f32::<LittleEndian>()?),
+            module_opcode::DOUBLE => ModuleField::Double(self.input.read_f64::<LittleEndian>()?),
+            module_opcode::STRING => ModuleField::String(read_blob(&mut self.input)?),
+            opcode => {
+                return Err(RdbError::corrupt(
+                    "module",
+                    format!("Unknown module opcode: {}", opcode),
+                ))
+            }
+        };
+        Ok(Some(field))
+    }
+
+    /// Reads all values up to the end of the payload
+    pub fn read_remaining(&mut self) -> RdbResult<Vec<ModuleField>> {
+        let mut fields = Vec::new();
+        while let Some(field) = self.read_field()? {
+            fields.push(field);
+        }
+        Ok(fields)
+    }
+
+    /// Consumes the rest of the payload including the EOF opcode
+    fn finish(&mut self) -> RdbResult<()> {
+        if self.with_opcodes {
+            while self.read_field()?.is_some() {}
+        }
+        Ok(())
+    }
+}
+
+/// Module decoders registered on a decoder.
+/// Decoders registered later take precedence over earlier and built-in ones.
+pub(crate) struct ModuleRegistry {
+    decoders: Vec<Box<dyn ModuleDecoder>>,
+    capture_payloads: bool,
+}
+
+impl Default for ModuleRegistry {
+    fn default() -> ModuleRegistry {
+        ModuleRegistry {
+            decoders: vec![
+                Box::new(json::JsonDecoder),
+                Box::new(bloom::BloomDecoder),
+                Box::new(bloom::CuckooDecoder),
+                Box::new(bloom::CountMinSketchDecoder),
+                Box::new(bloom::TopKDecoder),
+                Box::new(timeseries::TimeSeriesDecoder),
+            ],
+            capture_payloads: false,
+        }
+    }
+}
+
+impl ModuleRegistry {
+    pub fn add(&mut self, decoder: Box<dyn ModuleDecoder>) {
+        self.decoders.push(decoder);
+    }
+
+    pub fn set_capture_payloads(&mut self, capture: bool) {
+        self.capture_payloads = capture;
+    }
+
+    fn find(&self, name: &str) -> Option<&dyn ModuleDecoder> {
+        self.decoders
+            .iter()
+            .rev()
+            .find(|decoder| decoder.name() == name)
+            .map(|decoder| decoder.as_ref())
+    }
+}
+
+pub fn read_module<R: Read>(
+    input: &mut R,
+    key: &[u8],
+    expiry: Option<u64>,
+    enc_type: u8,
+    modules: &ModuleRegistry,
+) -> RdbResult<RdbValue> {
+    let module = ModuleId::from(read_length64(input)?);
+    let mut reader = ModuleReader::new(input, enc_type == encoding_type::MODULE_2);
+
+    let value = match modules.find(&module.name) {
+        Some(decoder) => decoder.decode(&mut reader, module.version)?,
+        None if !reader.with_opcodes => {
+            return Err(RdbError::unsupported(format!(
+                "No decoder registered for module type {}",
+                module
+            )))
+        }
+        None if modules.capture_payloads => ModuleValue::Raw(reader.read_remaining()?),
+        None => ModuleValue::Skipped,
+    };
+    reader.finish()?;
+
+    Ok(RdbValue::Module {
+        key: key.to_vec(),
+        module,
+        value,
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Module),
+    })
+}
+
+/// Reads the auxiliary data a module stored outside of any key, following
+/// the `MODULE_AUX` opcode: the module ID, the opcode-typed `when` marker
+/// and an opcode-typed payload terminated by EOF.
+pub fn read_module_aux<R: Read>(input: &mut R) -> RdbResult<RdbValue> {
+    let module = ModuleId::from(read_length64(input)?);
+    let mut reader = ModuleReader::new(input, true);
+    let when = reader.read_unsigned()?;
+    let payload = reader.read_remaining()?;
+
+    Ok(RdbValue::ModuleAux {
+        module,
+        when,
+        payload,
+    })
+}
+
+/// Skips a `MODULE_2` object by walking its opcodes
+pub fn skip_module_2<R: Read>(input: &mut R) -> RdbResult<()> {
+    let _module_id = read_length64(input)?;
+    ModuleReader::new(input, true).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::rdb::skip_filtered_object;
+    use indexmap::IndexMap;
+    use std::io::Cursor;
+
+    // "hellotype" with encoding version 0
+    const HELLOTYPE_ID: u64 = 0x85e965a2dca97800;
+
+    fn payload() -> Vec<u8> {
+        let mut input = vec![0x81];
+        input.extend_from_slice(&HELLOTYPE_ID.to_be_bytes());
+        input.extend_from_slice(&[module_opcode::UINT as u8, 2]);
+        input.extend_from_slice(&[module_opcode::SINT as u8, 7]);
+        input.extend_from_slice(&[module_opcode::STRING as u8, 2, b'h', b'i']);
+        input.push(module_opcode::DOUBLE as u8);
+        input.extend_from_slice(&1.5f64.to_le_bytes());
+        input.push(module_opcode::EOF as u8);
+        input
+    }
+
+    struct HelloType;
+
+    impl ModuleDecoder for HelloType {
+        fn name(&self) -> &str {
+            "hellotype"
+        }
+
+        fn decode(&self, reader: &mut ModuleReader<'_>, _version: u64) -> RdbResult<ModuleValue> {
+            let mut properties = IndexMap::new();
+            properties.insert(
+                "len".to_string(),
+                ModuleField::UInt(reader.read_unsigned()?),
+            );
+            properties.insert(
+                "first".to_string(),
+                ModuleField::SInt(reader.read_signed()?),
+            );
+            Ok(ModuleValue::Custom(properties))
+        }
+    }
+
+    #[test]
+    fn test_module_id() {
+        let module = ModuleId::from(HELLOTYPE_ID);
+        assert_eq!(module.name, "hellotype");
+        assert_eq!(module.version, 0);
+    }
+
+    #[test]
+    fn test_read_module_skipped() {
+        let mut cursor = Cursor::new(payload());
+        let value = read_module(
+            &mut cursor,
+            b"key",
+            None,
+            encoding_type::MODULE_2,
+            &ModuleRegistry::default(),
+        )
+        .unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        let RdbValue::Module { module, value, .. } = value else {
+            panic!("Expected a module value, got {:?}", value);
+        };
+        assert_eq!(module.name, "hellotype");
+        assert_eq!(value, ModuleValue::Skipped);
+    }
+
+    #[test]
+    fn test_read_module_raw() {
+        let mut modules = ModuleRegistry::default();
+        modules.set_capture_payloads(true);
+
+        let mut cursor = Cursor::new(payload());
+        let value =
+            read_module(&mut cursor, b"key", None, encoding_type::MODULE_2, &modules).unwrap();
+
+        let RdbValue::Module { value, .. } = value else {
+            panic!("Expected a module value, got {:?}", value);
+        };
+        assert_eq!(
+            value,
+            ModuleValue::Raw(vec![
+                ModuleField::UInt(2),
+                ModuleField::SInt(7),
+                ModuleField::String(b"hi".to_vec()),
+                ModuleField::Double(1.5),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_read_module_with_decoder() {
+        let mut modules = ModuleRegistry::default();
+        modules.add(Box::new(HelloType));
+
+        let mut cursor = Cursor::new(payload());
+        let value =
+            read_module(&mut cursor, b"key", None, encoding_type::MODULE_2, &modules).unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        let RdbValue::Module { value, .. } = value else {
+            panic!("Expected a module value, got {:?}", value);
+        };
+        let ModuleValue::Custom(properties) = value else {
+            panic!("Expected custom properties, got {:?}", value);
+        };
+        assert_eq!(properties["len"], ModuleField::UInt(2));
+        assert_eq!(properties["first"], ModuleField::SInt(7));
+    }
+
+    #[test]
+    fn test_read_module_without_opcodes_requires_decoder() {
+        let mut cursor = Cursor::new(payload());
+        let result = read_module(
+            &mut cursor,
+            b"key",
+            None,
+            encoding_type::MODULE,
+            &ModuleRegistry::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_module_aux() {
+        let mut input = vec![0x81];
+        input.extend_from_slice(&HELLOTYPE_ID.to_be_bytes());
+        input.extend_from_slice(&[module_opcode::UINT as u8, 2]);
+        input.extend_from_slice(&[module_opcode::STRING as u8, 3, b'a', b'u', b'x']);
+        input.push(module_opcode::EOF as u8);
+        input.push(0xff);
+
+        let mut cursor = Cursor::new(input);
+        let value = read_module_aux(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64 - 1);
+
+        let RdbValue::ModuleAux {
+            module,
+            when,
+            payload,
+        } = value
+        else {
+            panic!("Expected module aux data, got {:?}", value);
+        };
+        assert_eq!(module.name, "hellotype");
+        assert_eq!(when, 2);
+        assert_eq!(payload, vec![ModuleField::String(b"aux".to_vec())]);
+    }
+
+    #[test]
+    fn test_filtered_module_is_read_by_its_decoder() {
+        let mut input = vec![0x81];
+        input.extend_from_slice(&HELLOTYPE_ID.to_be_bytes());
+        input.extend_from_slice(&[2, 7, 0xff]);
+        let mut modules = ModuleRegistry::default();
+        modules.add(Box::new(HelloType));
+
+        let mut rest = &input[..];
+        skip_filtered_object(&mut rest, encoding_type::MODULE, &modules).unwrap();
+        assert_eq!(rest, [0xff]);
+        let result = skip_filtered_object(
+            &mut &input[..],
+            encoding_type::MODULE,
+            &ModuleRegistry::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_skip_module_2() {
+        let mut cursor = Cursor::new(payload());
+        skip_module_2(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+    }
+}
@@ -0,0 +1,330 @@
+use super::{ModuleDecoder, ModuleReader};
+use crate::types::{ModuleField, ModuleValue, RdbError, RdbResult, TimeSeries};
+
+// Encoding versions of the RedisTimeSeries type
+const SIZE_IN_BYTES_VERSION: u64 = 1;
+const CHUNKS_VERSION: u64 = 2;
+const DUPLICATE_POLICY_VERSION: u64 = 3;
+
+const SERIES_OPT_UNCOMPRESSED: u64 = 0x1;
+const SAMPLE_SIZE: u64 = 16;
+
+// Field layouts of the sample containers: (i)nteger, (d)ouble, (s)tring.
+// `count` and `timestamp` are the indices of the sample count and the first
+// timestamp of a container, a sample stored on its own counts as one.
+struct Layout {
+    fields: &'static [u8],
+    count: Option<usize>,
+    timestamp: usize,
+}
+
+const SAMPLE_LAYOUT: Layout = Layout {
+    fields: b"id",
+    count: None,
+    timestamp: 0,
+};
+const UNCOMPRESSED_CHUNK_LAYOUT: Layout = Layout {
+    fields: b"iiis",
+    count: Some(1),
+    timestamp: 0,
+};
+const COMPRESSED_CHUNK_LAYOUT: Layout = Layout {
+    fields: b"iiidiiidiis",
+    count: Some(1),
+    timestamp: 4,
+};
+
+/// Decoder for RedisTimeSeries series (`TSDB-TYPE`).
+///
+/// The header holds retention, chunk size, options, last sample and labels,
+/// followed by the compaction rules and the sample chunks. The state stored
+/// for a compaction rule depends on its aggregation type, so the chunks are
+/// located by matching their layout from the end of the payload.
+pub struct TimeSeriesDecoder;
+
+impl ModuleDecoder for TimeSeriesDecoder {
+    fn name(&self) -> &str {
+        "TSDB-TYPE"
+    }
+
+    fn decode(&self, reader: &mut ModuleReader<'_>, version: u64) -> RdbResult<ModuleValue> {
+        let fields = reader.read_remaining()?;
+        let mut fields = Fields {
+            fields: &fields,
+            pos: 0,
+        };
+
+        let _key = fields.string()?;
+        let retention = fields.integer()?;
+        let mut chunk_size = fields.integer()?;
+        if version < SIZE_IN_BYTES_VERSION {
+            chunk_size = chunk_size.saturating_mul(SAMPLE_SIZE);
+        }
+        let options = fields.integer()?;
+        if version >= DUPLICATE_POLICY_VERSION {
+            let _duplicate_policy = fields.integer()?;
+        }
+        // Newer versions store the ignore thresholds, the only double in front of the last sample
+        if let Some(ModuleField::Double(_)) = fields.peek(1) {
+            let _ignore_max_time_diff = fields.integer()?;
+            let _ignore_max_value_diff = fields.double()?;
+        }
+        if fields.integer()? != 0 {
+            let _source_key = fields.string()?;
+        }
+        let last_timestamp = fields.integer()?;
+        let _last_value = fields.double()?;
+
+        let labels_count = fields.integer()?;
+        let mut labels = Vec::new();
+        for _ in 0..labels_count {
+            let name = fields.string()?;
+            let value = fields.string()?;
+            labels.push((name, value));
+        }
+        let rules = fields.integer()?;
+
+        let layout = if version < CHUNKS_VERSION {
+            &SAMPLE_LAYOUT
+        } else if options & SERIES_OPT_UNCOMPRESSED != 0 {
+            &UNCOMPRESSED_CHUNK_LAYOUT
+        } else {
+            &COMPRESSED_CHUNK_LAYOUT
+        };
+        let containers = find_containers(fields.remaining(), layout, rules == 0)?;
+
+        let mut samples = 0u64;
+        for container in containers.chunks(layout.fields.len()) {
+            samples = samples.saturating_add(match layout.count {
+                Some(index) => as_integer(&container[index]).unwrap_or(0),
+                None => 1,
+            });
+        }
+        let first_timestamp = containers
+            .get(layout.timestamp)
+            .and_then(as_integer)
+            .filter(|_| samples > 0);
+
+        Ok(ModuleValue::TimeSeries(TimeSeries {
+            retention,
+            chunk_size,
+            labels,
+            rules,
+            chunks: (containers.len() / layout.fields.len()) as u64,
+            samples,
+            first_timestamp,
+            last_timestamp,
+        }))
+    }
+}
+
+/// Finds the trailing `count, container...` section.
+/// Without compaction rules it has to start right away.
+fn find_containers<'a>(
+    rest: &'a [ModuleField],
+    layout: &Layout,
+    exact: bool,
+) -> RdbResult<&'a [ModuleField]> {
+    let width = layout.fields.len();
+    let max_count = rest.len().saturating_sub(1) / width;
+    for count in (0..=max_count).rev() {
+        let start = rest.len() - count * width;
+        let containers = &rest[start..];
+        if start == 0 || (exact && start != 1) {
+            continue;
+        }
+        if as_integer(&rest[start - 1]) == Some(count as u64)
+            && containers
+                .chunks(width)
+                .all(|container| matches_layout(container, layout))
+        {
+            return Ok(containers);
+        }
+    }
+
+    Err(RdbError::corrupt(
+        "TSDB-TYPE",
+        "Could not locate the sample chunks",
+    ))
+}
+
+fn matches_layout(container: &[ModuleField], layout: &Layout) -> bool {
+    container
+        .iter()
+        .zip(layout.fields)
+        .all(|(field, kind)| match kind {
+            b'i' => matches!(field, ModuleField::UInt(_) | ModuleField::SInt(_)),
+            b'd' => matches!(field, ModuleField::Double(_)),
+            _ => matches!(field, ModuleField::String(_)),
+        })
+}
+
+fn as_integer(field: &ModuleField) -> Option<u64> {
+    match field {
+        ModuleField::UInt(val) => Some(*val),
+        ModuleField::SInt(val) => Some(*val as u64),
+        _ => None,
+    }
+}
+
+struct Fields<'a> {
+    fields: &'a [ModuleField],
+    pos: usize,
+}
+
+impl Fields<'_> {
+    fn next(&mut self) -> RdbResult<&ModuleField> {
+        let field = self
+            .fields
+            .get(self.pos)
+            .ok_or_else(|| RdbError::corrupt("TSDB-TYPE", "Missing time series field"))?;
+        self.pos += 1;
+        Ok(field)
+    }
+
+    fn peek(&self, offset: usize) -> Option<&ModuleField> {
+        self.fields.get(self.pos + offset)
+    }
+
+    fn remaining(&self) -> &[ModuleField] {
+        &self.fields[self.pos..]
+    }
+
+    fn unexpected(field: &ModuleField, expected: &str) -> RdbError {
+        RdbError::corrupt(
+            "TSDB-TYPE",
+            format!("Expected {}, found {:?}", expected, field),
+        )
+    }
+
+    fn integer(&mut self) -> RdbResult<u64> {
+        let field = self.next()?;
+        as_integer(field).ok_or_else(|| Self::unexpected(field, "an integer"))
+    }
+
+    fn double(&mut self) -> RdbResult<f64> {
+        match self.next()? {
+            ModuleField::Double(val) => Ok(*val),
+            field => Err(Self::unexpected(field, "a double")),
+        }
+    }
+
+    fn string(&mut self) -> RdbResult<Vec<u8>> {
+        match self.next()? {
+            ModuleField::String(val) => Ok(val.clone()),
+            field => Err(Self::unexpected(field, "a string")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::module_opcode;
+    use std::io::Cursor;
+
+    fn uint(out: &mut Vec<u8>, val: u64) {
+        out.push(module_opcode::UINT as u8);
+        out.push(0x81);
+        out.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn sint(out: &mut Vec<u8>, val: i64) {
+        out.push(module_opcode::SINT as u8);
+        out.push(0x81);
+        out.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn double(out: &mut Vec<u8>, val: f64) {
+        out.push(module_opcode::DOUBLE as u8);
+        out.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn string(out: &mut Vec<u8>, val: &[u8]) {
+        out.extend_from_slice(&[module_opcode::STRING as u8, val.len() as u8]);
+        out.extend_from_slice(val);
+    }
+
+    fn compressed_chunk(out: &mut Vec<u8>, count: u64, base_timestamp: u64) {
+        uint(out, 4096);
+        uint(out, count);
+        uint(out, 100);
+        double(out, 1.0);
+        uint(out, base_timestamp);
+        uint(out, base_timestamp + count);
+        sint(out, 1);
+        double(out, 2.0);
+        uint(out, 3);
+        uint(out, 4);
+        string(out, &[0; 16]);
+    }
+
+    fn header(out: &mut Vec<u8>) {
+        string(out, b"temperature");
+        uint(out, 86_400_000);
+        uint(out, 4096);
+        uint(out, 0);
+        uint(out, 0);
+        uint(out, 0);
+        uint(out, 1_700_000_000_500);
+        double(out, 21.5);
+        uint(out, 1);
+        string(out, b"room");
+        string(out, b"kitchen");
+    }
+
+    fn decode(input: Vec<u8>, version: u64) -> ModuleValue {
+        let mut cursor = Cursor::new(input);
+        let mut reader = ModuleReader::new(&mut cursor, true);
+        TimeSeriesDecoder.decode(&mut reader, version).unwrap()
+    }
+
+    #[test]
+    fn test_decode_time_series() {
+        let mut input = Vec::new();
+        header(&mut input);
+        uint(&mut input, 0);
+        uint(&mut input, 2);
+        compressed_chunk(&mut input, 250, 1_700_000_000_000);
+        compressed_chunk(&mut input, 50, 1_700_000_000_300);
+        input.push(module_opcode::EOF as u8);
+
+        assert_eq!(
+            decode(input, 4),
+            ModuleValue::TimeSeries(TimeSeries {
+                retention: 86_400_000,
+                chunk_size: 4096,
+                labels: vec![(b"room".to_vec(), b"kitchen".to_vec())],
+                rules: 0,
+                chunks: 2,
+                samples: 300,
+                first_timestamp: Some(1_700_000_000_000),
+                last_timestamp: 1_700_000_000_500,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_time_series_with_rules() {
+        let mut input = Vec::new();
+        header(&mut input);
+        uint(&mut input, 1);
+        string(&mut input, b"temperature:avg");
+        uint(&mut input, 60_000);
+        uint(&mut input, 0);
+        uint(&mut input, 1);
+        uint(&mut input, 1_700_000_000_000);
+        double(&mut input, 42.0);
+        double(&mut input, 2.0);
+        uint(&mut input, 1);
+        compressed_chunk(&mut input, 10, 1_700_000_000_000);
+        input.push(module_opcode::EOF as u8);
+
+        let ModuleValue::TimeSeries(series) = decode(input, 5) else {
+            panic!("Expected a time series");
+        };
+        assert_eq!(series.rules, 1);
+        assert_eq!(series.chunks, 1);
+        assert_eq!(series.samples, 10);
+    }
+}
@@ -0,0 +1,339 @@
+use super::common::utils::read_blob;
+use super::common::CountingReader;
+use super::module::{ModuleDecoder, ModuleRegistry};
+use super::rdb::{self, DecoderState, Entry, KeyEntry};
+use crate::filter::Filter;
+use crate::formatter::Formatter;
+use crate::types::{FilteredKeys, LossSummary, RdbHeader, RdbResult, RdbValue, TruncationReport};
+use crate::Output;
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+
+// Keys each thread may have decoded ahead of the key to be delivered next
+const KEYS_IN_FLIGHT_PER_THREAD: usize = 64;
+
+/// Decoder for a dump held in memory, decoding keys on several threads.
+///
+/// The dump is scanned on the calling thread, recording where each key is
+/// stored and skipping over its value without decoding it. The values are
+/// decoded by a pool of threads, and other entries, such as the selection
+/// of a database, by the scan. The whole dump has to be held in memory, only
+/// the number of keys decoded ahead of the formatter is bounded, to 64 per
+/// thread. `RdbDecoder` with element streaming is to be used where memory
+/// is limited.
+pub struct ParallelDecoder<'a, F: Filter> {
+    data: &'a [u8],
+    reader: CountingReader<&'a [u8]>,
+    filter: F,
+    modules: ModuleRegistry,
+    state: DecoderState,
+    threads: usize,
+    ordered: bool,
+}
+
+// Entry recorded by the scan of the dump
+enum IndexEntry {
+    // Entry other than a key, or a key whose value is only delimited by decoding it
+    Value(RdbValue),
+    Key(IndexedKey),
+}
+
+// Key whose name and value are stored at `start..end` of the dump
+struct IndexedKey {
+    entry: KeyEntry,
+    database: u32,
+    start: usize,
+    end: usize,
+}
+
+impl<'a, F: Filter> ParallelDecoder<'a, F> {
+    pub fn new(data: &'a [u8], filter: F) -> RdbResult<Self> {
+        let mut reader = CountingReader::new(data);
+        let header = super::read_header(&mut reader)?;
+        Ok(Self {
+            data,
+            reader,
+            filter,
+            modules: ModuleRegistry::default(),
+            state: DecoderState {
+                header,
+                ..Default::default()
+            },
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            ordered: true,
+        })
+    }
+
+    /// Version and server flavor of the dump, see `RdbDecoder::header`
+    pub fn header(&self) -> RdbHeader {
+        self.state.header
+    }
+
+    /// Number of threads decoding values, the number of CPUs by default
+    pub fn threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Delivers keys in the order they are stored in the dump, the default,
+    /// or in the order they finish decoding. Keys are never delivered across
+    /// other entries, such as the selection of a database.
+    pub fn ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
+    }
+
+    /// Verifies the CRC64 checksum at the end of the dump, see `RdbDecoder::verify_checksum`
+    pub fn verify_checksum(&mut self, enabled: bool) {
+        self.reader.track_checksum(enabled);
+        self.state.verify_checksum = enabled;
+    }
+
+    /// Skips keys whose value fails to decode instead of stopping,
+    /// see `RdbDecoder::skip_corrupt_keys`
+    pub fn skip_corrupt_keys(&mut self, enabled: bool) {
+        self.state.skip_corrupt_keys = enabled;
+    }
+
+    /// Number of keys left out by the filter so far
+    pub fn filtered_keys(&self) -> FilteredKeys {
+        self.state.filtered_keys
+    }
+
+    /// How far the dump could be read, once decoding stopped at its truncated end
+    pub fn truncation(&self) -> Option<&TruncationReport> {
+        self.state.truncation.as_ref()
+    }
+
+    /// Registers a decoder for values of a specific module type
+    pub fn add_module_decoder(&mut self, decoder: Box<dyn ModuleDecoder>) {
+        self.modules.add(decoder);
+    }
+
+    /// Keeps the payload of module values without a registered decoder
+    /// as raw fields instead of skipping it
+    pub fn capture_module_payloads(&mut self, capture: bool) {
+        self.modules.set_capture_payloads(capture);
+    }
+
+    /// Decodes the dump and passes its values to the formatter,
+    /// see `RdbParser::parse`
+    pub fn parse<T: Formatter>(&mut self, formatter: T) -> RdbResult<LossSummary> {
+        let mut output = Output::new(formatter, self.state.skip_corrupt_keys);
+        // Keys failing to decode leave the scan at the next entry
+        if let Err(err) = self.decode(|value| output.push(value, true)) {
+            let _ = output.push(Err(err), false);
+        }
+        output.finish(self.truncation().cloned())
+    }
+
+    // Scans the dump and passes the values decoded by the threads to `deliver`,
+    // returning the error the scan stopped at
+    fn decode<D>(&mut self, mut deliver: D) -> RdbResult<()>
+    where
+        D: FnMut(RdbResult<RdbValue>) -> ControlFlow<()>,
+    {
+        let Self {
+            data,
+            reader,
+            filter,
+            modules,
+            state,
+            threads,
+            ordered,
+        } = self;
+        let data = *data;
+        let modules = &*modules;
+        let max_in_flight = *threads * KEYS_IN_FLIGHT_PER_THREAD;
+
+        let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, IndexedKey)>(max_in_flight);
+        let job_receiver = Mutex::new(job_receiver);
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..*threads {
+                let job_receiver = &job_receiver;
+                let result_sender = result_sender.clone();
+                scope.spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok((sequence, key)) = job else {
+                        break;
+                    };
+                    let value = decode_key(data, &key, modules);
+                    if result_sender.send((sequence, value)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(result_sender);
+
+            // Dropping the job sender and the results on return ends the threads
+            let job_sender = job_sender;
+            let mut results = Results {
+                receiver: result_receiver,
+                ordered: *ordered,
+                in_flight: 0,
+                next: 0,
+                waiting: BTreeMap::new(),
+            };
+            let mut sequence = 0;
+            while !state.reached_eof {
+                let entry = match scan_entry(reader, filter, modules, state) {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        let err = rdb::locate_error(err, reader.position(), state);
+                        state.reached_eof = true;
+                        if results.drain(&mut deliver).is_break() {
+                            return Ok(());
+                        }
+                        return Err(err);
+                    }
+                };
+                let flow = match entry {
+                    // Keys before other entries are delivered before them
+                    IndexEntry::Value(value) => match results.drain(&mut deliver) {
+                        ControlFlow::Continue(()) => deliver(Ok(value)),
+                        flow => flow,
+                    },
+                    IndexEntry::Key(key) => {
+                        let flow = results.wait_below(max_in_flight, &mut deliver);
+                        if flow.is_continue() {
+                            // The threads only stop once the sender is dropped
+                            job_sender.send((sequence, key)).unwrap();
+                            sequence += 1;
+                            results.in_flight += 1;
+                        }
+                        flow
+                    }
+                };
+                if flow.is_break() {
+                    return Ok(());
+                }
+            }
+            let _ = results.drain(&mut deliver);
+            Ok(())
+        })
+    }
+}
+
+// Values decoded by the threads, passed on once all keys before them are
+struct Results {
+    receiver: Receiver<(usize, RdbResult<RdbValue>)>,
+    ordered: bool,
+    // Keys sent to the threads and not delivered yet
+    in_flight: usize,
+    // Sequence number of the next key to deliver in order
+    next: usize,
+    // Keys decoded before a key preceding them
+    waiting: BTreeMap<usize, RdbResult<RdbValue>>,
+}
+
+impl Results {
+    // Waits for the next key to finish decoding and delivers all keys that can be
+    fn receive<D>(&mut self, deliver: &mut D) -> ControlFlow<()>
+    where
+        D: FnMut(RdbResult<RdbValue>) -> ControlFlow<()>,
+    {
+        let (sequence, value) = self
+            .receiver
+            .recv()
+            .expect("Decoding thread stopped before its key was decoded");
+        if !self.ordered {
+            self.in_flight -= 1;
+            return deliver(value);
+        }
+        self.waiting.insert(sequence, value);
+        while let Some(value) = self.waiting.remove(&self.next) {
+            self.next += 1;
+            self.in_flight -= 1;
+            deliver(value)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn wait_below<D>(&mut self, limit: usize, deliver: &mut D) -> ControlFlow<()>
+    where
+        D: FnMut(RdbResult<RdbValue>) -> ControlFlow<()>,
+    {
+        while self.in_flight >= limit {
+            self.receive(deliver)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn drain<D>(&mut self, deliver: &mut D) -> ControlFlow<()>
+    where
+        D: FnMut(RdbResult<RdbValue>) -> ControlFlow<()>,
+    {
+        self.wait_below(1, deliver)
+    }
+}
+
+// Reads the next entry, up to the end of the value of a key,
+// `None` if the key is filtered
+fn scan_entry<F: Filter>(
+    input: &mut CountingReader<&[u8]>,
+    filter: &F,
+    modules: &ModuleRegistry,
+    state: &mut DecoderState,
+) -> RdbResult<Option<IndexEntry>> {
+    let entry = match rdb::read_entry(input, state)? {
+        Entry::Value(value) => return Ok(Some(IndexEntry::Value(*value))),
+        Entry::Key(entry) => entry,
+    };
+    let value_type = entry.value_type;
+
+    if !filter.matches_db(state.current_database) {
+        rdb::skip_key_and_object(input, value_type, modules)?;
+        state.filtered_keys.database += 1;
+        return Ok(None);
+    }
+
+    let start = input.position() as usize;
+    let key = read_blob(input)?;
+    let database = Some(state.current_database);
+    if rdb::is_filtered(filter, state, value_type, &key) {
+        rdb::skip_filtered_object(input, value_type, modules)
+            .map_err(|err| err.at(input.position(), database, Some(&key)))?;
+        return Ok(None);
+    }
+
+    if !rdb::can_skip_object(value_type) {
+        let mut value = rdb::read_type(input, &key, value_type, entry.expiry, modules)
+            .map_err(|err| err.at(input.position(), database, Some(&key)))?;
+        if let Some(meta) = value.meta_mut() {
+            *meta = entry.meta(state.current_database, meta.encoding, input.position());
+        }
+        state.keys_read += 1;
+        return Ok(Some(IndexEntry::Value(value)));
+    }
+
+    rdb::skip_object(input, value_type)
+        .map_err(|err| err.at(input.position(), database, Some(&key)))?;
+    state.keys_read += 1;
+    Ok(Some(IndexEntry::Key(IndexedKey {
+        entry,
+        database: state.current_database,
+        start,
+        end: input.position() as usize,
+    })))
+}
+
+fn decode_key(data: &[u8], key: &IndexedKey, modules: &ModuleRegistry) -> RdbResult<RdbValue> {
+    let mut input = &data[key.start..key.end];
+    let name = read_blob(&mut input)?;
+    let entry = &key.entry;
+    let mut value = rdb::read_type(&mut input, &name, entry.value_type, entry.expiry, modules)
+        .map_err(|err| {
+            // The scan found the end of the value, it is corrupt rather than truncated
+            let position = key.end - input.len();
+            err.within_blob("value")
+                .at(position as u64, Some(key.database), Some(&name))
+        })?;
+    if let Some(meta) = value.meta_mut() {
+        *meta = entry.meta(key.database, meta.encoding, key.end as u64);
+    }
+    Ok(value)
+}
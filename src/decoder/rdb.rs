@@ -1,24 +1,40 @@
-use super::common::utils::{
-    read_blob, read_length, read_length_with_encoding, verify_magic, verify_version,
-};
-use super::{hash, list, set, sorted_set};
+use super::common::utils::{read_blob, read_length, read_length64, read_length_with_encoding};
+use super::common::{CountingReader, Skip};
+use super::elements::{self, PendingCollection};
+use super::header::check_operation;
+use super::module::{self, ModuleRegistry};
+use super::{hash, list, set, sorted_set, stream};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::constants::{encoding, encoding_type, op_code};
 use crate::filter::Filter;
-use crate::types::{RdbError, RdbResult, RdbValue};
+use crate::types::{
+    EncodingType, ErrorLocation, FilteredKeys, KeyMeta, RdbError, RdbHeader, RdbResult, RdbValue,
+    ServerFlavor, TruncationReport,
+};
 
 #[derive(Default)]
 pub(crate) struct DecoderState {
+    pub header: RdbHeader,
     pub last_expiretime: Option<u64>,
+    pub last_idle: Option<u64>,
+    pub last_freq: Option<u8>,
+    // Offset of the first opcode of the entry being read
+    pub entry_offset: Option<u64>,
     pub current_database: u32,
     pub reached_eof: bool,
-}
-
-pub(crate) fn verify_header<R: Read>(input: &mut R) -> RdbResult<()> {
-    verify_magic(input)?;
-    verify_version(input)
+    pub verify_checksum: bool,
+    pub skip_corrupt_keys: bool,
+    // Whether the last error left the input at the start of the next entry
+    pub recovered: bool,
+    pub keys_read: u64,
+    pub filtered_keys: FilteredKeys,
+    pub eof_marker: bool,
+    pub truncation: Option<TruncationReport>,
+    pub stream_elements: bool,
+    // Collection whose elements are being yielded
+    pub pending: Option<PendingCollection>,
 }
 
 pub(crate) fn read_type<R: Read>(
@@ -26,6 +42,7 @@ pub(crate) fn read_type<R: Read>(
     key: &[u8],
     value_type: u8,
     expiry: Option<u64>,
+    modules: &ModuleRegistry,
 ) -> RdbResult<RdbValue> {
     let result = match value_type {
         encoding_type::STRING => {
@@ -34,6 +51,7 @@ pub(crate) fn read_type<R: Read>(
                 key: key.to_vec(),
                 value,
                 expiry,
+                meta: KeyMeta::with_encoding(EncodingType::String),
             }
         }
         encoding_type::LIST => list::read_linked_list(input, key, expiry)?,
@@ -47,34 +65,30 @@ pub(crate) fn read_type<R: Read>(
         encoding_type::HASH_ZIPLIST => hash::read_hash_ziplist(input, key, expiry)?,
         encoding_type::LIST_QUICKLIST => list::read_quicklist(input, key, expiry)?,
         encoding_type::HASH_LIST_PACK => hash::read_hash_list_pack(input, key, expiry)?,
-        encoding_type::ZSET_2 => sorted_set::read_sorted_set(input, key, expiry, true)?,
-        encoding_type::LIST_QUICKLIST_2 => list::read_quicklist_2(input, key, expiry)?,
-        encoding_type::STREAM_LIST_PACKS => {
-            todo!("read_stream_list_packs v1 not implemented");
+        encoding_type::HASH_METADATA_PRE_GA | encoding_type::HASH_METADATA => {
+            hash::read_hash_metadata(input, key, expiry, value_type)?
         }
-        encoding_type::STREAM_LIST_PACKS_2 => {
-            todo!("read_stream_list_packs v2 not implemented");
+        encoding_type::HASH_LIST_PACK_EX_PRE_GA | encoding_type::HASH_LIST_PACK_EX => {
+            hash::read_hash_list_pack_ex(input, key, expiry, value_type)?
         }
-        encoding_type::STREAM_LIST_PACKS_3 => {
-            todo!("read_stream_list_packs v3 not implemented");
+        encoding_type::ZSET_2 => sorted_set::read_sorted_set(input, key, expiry, true)?,
+        encoding_type::LIST_QUICKLIST_2 => list::read_quicklist_2(input, key, expiry)?,
+        encoding_type::STREAM_LIST_PACKS
+        | encoding_type::STREAM_LIST_PACKS_2
+        | encoding_type::STREAM_LIST_PACKS_3 => {
+            stream::read_stream_list_packs(input, key, expiry, value_type)?
         }
         encoding_type::ZSET_LIST_PACK => sorted_set::read_sorted_set_listpack(input, key, expiry)?,
         encoding_type::SET_LIST_PACK => set::read_set_list_pack(input, key, expiry)?,
-        unknown_type => {
-            skip_object(input, unknown_type)?;
-            return Err(RdbError::MissingValue("skip"));
+        encoding_type::MODULE | encoding_type::MODULE_2 => {
+            module::read_module(input, key, expiry, value_type, modules)?
         }
+        unknown_type => return Err(unknown_type_error(unknown_type)),
     };
     Ok(result)
 }
 
-pub(crate) fn skip<R: Read>(input: &mut R, skip_bytes: usize) -> RdbResult<()> {
-    let mut buf = vec![0; skip_bytes];
-    input.read_exact(&mut buf).map_err(RdbError::Io)?;
-    Ok(())
-}
-
-pub(crate) fn skip_blob<R: Read>(input: &mut R) -> RdbResult<()> {
+pub(crate) fn skip_blob<R: Skip>(input: &mut R) -> RdbResult<()> {
     let (len, is_encoded) = read_length_with_encoding(input)?;
 
     let skip_bytes = if is_encoded {
@@ -88,20 +102,20 @@ pub(crate) fn skip_blob<R: Read>(input: &mut R) -> RdbResult<()> {
                 compressed_length
             }
             _ => {
-                return Err(RdbError::ParsingError {
-                    context: "skip_blob",
-                    message: format!("Unknown encoding value: {}", len),
-                });
+                return Err(RdbError::corrupt(
+                    "string",
+                    format!("Unknown encoding value: {}", len),
+                ));
             }
         }
     } else {
         len
     };
 
-    skip(input, skip_bytes as usize)
+    input.skip(u64::from(skip_bytes))
 }
 
-pub(crate) fn skip_object<R: Read>(input: &mut R, enc_type: u8) -> RdbResult<()> {
+pub(crate) fn skip_object<R: Skip>(input: &mut R, enc_type: u8) -> RdbResult<()> {
     let blobs_count = match enc_type {
         encoding_type::STRING
         | encoding_type::HASH_ZIPMAP
@@ -109,12 +123,51 @@ pub(crate) fn skip_object<R: Read>(input: &mut R, enc_type: u8) -> RdbResult<()>
         | encoding_type::SET_INTSET
         | encoding_type::ZSET_ZIPLIST
         | encoding_type::HASH_ZIPLIST
-        | encoding_type::HASH_LIST_PACK => 1,
+        | encoding_type::HASH_LIST_PACK
+        | encoding_type::ZSET_LIST_PACK
+        | encoding_type::SET_LIST_PACK => 1,
         encoding_type::LIST | encoding_type::SET | encoding_type::LIST_QUICKLIST => {
             read_length(input)?
         }
         encoding_type::ZSET | encoding_type::HASH => read_length(input)? * 2,
-        _ => return Err(RdbError::UnknownEncoding(enc_type)),
+        encoding_type::ZSET_2 => {
+            for _ in 0..read_length(input)? {
+                skip_blob(input)?;
+                // Binary double score
+                input.skip(8)?;
+            }
+            return Ok(());
+        }
+        encoding_type::LIST_QUICKLIST_2 => {
+            for _ in 0..read_length(input)? {
+                let _container_type = read_length(input)?;
+                skip_blob(input)?;
+            }
+            return Ok(());
+        }
+        encoding_type::STREAM_LIST_PACKS
+        | encoding_type::STREAM_LIST_PACKS_2
+        | encoding_type::STREAM_LIST_PACKS_3 => {
+            return stream::skip_stream_list_packs(input, enc_type)
+        }
+        encoding_type::MODULE_2 => return module::skip_module_2(input),
+        encoding_type::HASH_LIST_PACK_EX_PRE_GA => 1,
+        encoding_type::HASH_LIST_PACK_EX => {
+            input.skip(8)?;
+            1
+        }
+        encoding_type::HASH_METADATA_PRE_GA | encoding_type::HASH_METADATA => {
+            if enc_type == encoding_type::HASH_METADATA {
+                input.skip(8)?;
+            }
+            for _ in 0..read_length(input)? {
+                let _ttl = read_length64(input)?;
+                skip_blob(input)?;
+                skip_blob(input)?;
+            }
+            return Ok(());
+        }
+        _ => return Err(unknown_type_error(enc_type)),
     };
 
     for _ in 0..blobs_count {
@@ -123,82 +176,337 @@ pub(crate) fn skip_object<R: Read>(input: &mut R, enc_type: u8) -> RdbResult<()>
     Ok(())
 }
 
-pub(crate) fn skip_key_and_object<R: Read>(input: &mut R, enc_type: u8) -> RdbResult<()> {
+fn unknown_type_error(value_type: u8) -> RdbError {
+    RdbError::InvalidOperation {
+        operation: value_type,
+        message: format!("Unknown type {}", value_type),
+        location: ErrorLocation::default(),
+    }
+}
+
+// Values of legacy `MODULE` objects are only delimited by the decoder of
+// their module, all other values can be skipped without decoding them
+pub(crate) fn can_skip_object(enc_type: u8) -> bool {
+    enc_type != encoding_type::MODULE
+}
+
+/// Skips a value left out by the filter, decoding it only if the
+/// extent of its type is unknown otherwise
+pub(crate) fn skip_filtered_object<R: Skip>(
+    input: &mut R,
+    enc_type: u8,
+    modules: &ModuleRegistry,
+) -> RdbResult<()> {
+    if can_skip_object(enc_type) {
+        skip_object(input, enc_type)
+    } else {
+        read_type(input, &[], enc_type, None, modules).map(drop)
+    }
+}
+
+// Reads the raw object before decoding it, so that a value failing to
+// decode leaves the input at the next entry
+fn read_type_isolated<R: Read>(
+    input: &mut CountingReader<R>,
+    key: &[u8],
+    value_type: u8,
+    expiry: Option<u64>,
+    modules: &ModuleRegistry,
+    state: &mut DecoderState,
+) -> RdbResult<RdbValue> {
+    let start = input.position();
+    input.start_recording();
+    let skipped = skip_object(input, value_type);
+    let raw = input.stop_recording();
+    skipped?;
+
+    let mut object = io::Cursor::new(raw);
+    read_type(&mut object, key, value_type, expiry, modules).map_err(|err| {
+        state.recovered = true;
+        err.within_blob("value").at(
+            start + object.position(),
+            Some(state.current_database),
+            Some(key),
+        )
+    })
+}
+
+pub(crate) fn skip_key_and_object<R: Skip>(
+    input: &mut R,
+    enc_type: u8,
+    modules: &ModuleRegistry,
+) -> RdbResult<()> {
     skip_blob(input)?;
-    skip_object(input, enc_type)?;
+    skip_filtered_object(input, enc_type, modules)
+}
+
+// Dumps before version 5 have no checksum and servers with checksums
+// disabled write zero, neither can be verified
+fn verify_checksum(checksum: &[u8], actual: u64) -> RdbResult<()> {
+    let Ok(expected) = <[u8; 8]>::try_from(checksum) else {
+        return Ok(());
+    };
+    let expected = u64::from_le_bytes(expected);
+    if expected != 0 && expected != actual {
+        return Err(RdbError::ChecksumMismatch {
+            expected,
+            actual,
+            location: ErrorLocation::default(),
+        });
+    }
     Ok(())
 }
 
 pub(crate) fn process_next_operation<R: Read, F: Filter>(
-    input: &mut R,
+    input: &mut CountingReader<R>,
+    filter: &F,
+    modules: &ModuleRegistry,
+    state: &mut DecoderState,
+) -> RdbResult<RdbValue> {
+    read_next_operation(input, filter, modules, state)
+        .map_err(|err| locate_error(err, input.position(), state))
+}
+
+/// Adds the position decoding stopped at to an error and records
+/// whether decoding can go on after it
+pub(crate) fn locate_error(err: RdbError, position: u64, state: &mut DecoderState) -> RdbError {
+    // Without a known position of the next entry decoding cannot go on
+    let recovered = std::mem::take(&mut state.recovered);
+    if state.skip_corrupt_keys && !recovered {
+        state.reached_eof = true;
+    }
+    let err = err.at(position, Some(state.current_database), None);
+    // A key whose value was read in full is skipped, the dump goes on after it
+    if recovered {
+        return err;
+    }
+    if let RdbError::Truncated { location } = &err {
+        state.reached_eof = true;
+        state.truncation = Some(TruncationReport {
+            offset: position,
+            key: location.key.clone(),
+            keys_read: state.keys_read,
+            eof_marker: state.eof_marker,
+        });
+    }
+    err
+}
+
+/// Type and metadata of a key, read up to its name
+pub(crate) struct KeyEntry {
+    pub value_type: u8,
+    // Offset of the first opcode of the entry
+    pub offset: u64,
+    pub expiry: Option<u64>,
+    pub idle: Option<u64>,
+    pub freq: Option<u8>,
+}
+
+impl KeyEntry {
+    pub fn meta(&self, database: u32, encoding: EncodingType, end: u64) -> KeyMeta {
+        KeyMeta {
+            database,
+            expiry: self.expiry,
+            idle: self.idle,
+            freq: self.freq,
+            encoding,
+            offset: self.offset,
+            length: end - self.offset,
+        }
+    }
+}
+
+// Values other than keys are rare, so they are boxed to keep entries small
+pub(crate) enum Entry {
+    Value(Box<RdbValue>),
+    Key(KeyEntry),
+}
+
+fn read_next_operation<R: Read, F: Filter>(
+    input: &mut CountingReader<R>,
     filter: &F,
+    modules: &ModuleRegistry,
     state: &mut DecoderState,
 ) -> RdbResult<RdbValue> {
-    let next_op = match input.read_u8() {
-        Ok(op) => op,
-        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-            return Ok(RdbValue::Checksum(vec![]))
+    if let Some(mut pending) = state.pending.take() {
+        let database = Some(state.current_database);
+        let element = pending
+            .next_element(input)
+            .map_err(|err| err.at(input.position(), database, Some(&pending.key)))?;
+        if let Some(element) = element {
+            state.pending = Some(pending);
+            return Ok(RdbValue::Element(element));
         }
-        Err(e) => return Err(e.into()),
-    };
+        state.keys_read += 1;
+        return Ok(RdbValue::EndCollection);
+    }
 
-    match next_op {
-        op_code::SELECTDB => {
-            state.current_database = read_length(input)?;
-            Ok(RdbValue::SelectDb(state.current_database))
-        }
-        op_code::EOF => {
-            let mut checksum = Vec::new();
-            input.read_to_end(&mut checksum)?;
-            state.reached_eof = true;
-            Ok(RdbValue::Checksum(checksum))
-        }
-        op_code::EXPIRETIME_MS => {
-            state.last_expiretime = Some(input.read_u64::<LittleEndian>()?);
-            process_next_operation(input, filter, state)
-        }
-        op_code::EXPIRETIME => {
-            state.last_expiretime = Some(input.read_u32::<BigEndian>()? as u64 * 1000);
-            process_next_operation(input, filter, state)
-        }
-        op_code::RESIZEDB => {
-            let db_size = read_length(input)?;
-            let expires_size = read_length(input)?;
-            Ok(RdbValue::ResizeDb {
-                db_size,
-                expires_size,
-            })
-        }
-        op_code::AUX => {
-            let key = read_blob(input)?;
-            let value = read_blob(input)?;
-            Ok(RdbValue::AuxField { key, value })
+    loop {
+        let entry = match read_entry(input, state)? {
+            Entry::Value(value) => return Ok(*value),
+            Entry::Key(entry) => entry,
+        };
+        let value_type = entry.value_type;
+        let expiry = entry.expiry;
+
+        // Filtered keys are skipped without yielding anything for them
+        if !filter.matches_db(state.current_database) {
+            skip_key_and_object(input, value_type, modules)?;
+            state.filtered_keys.database += 1;
+            continue;
         }
-        op_code::MODULE_AUX => {
-            skip_blob(input)?;
-            process_next_operation(input, filter, state)
+
+        let key = read_blob(input)?;
+        let database = Some(state.current_database);
+        if is_filtered(filter, state, value_type, &key) {
+            skip_filtered_object(input, value_type, modules)
+                .map_err(|err| err.at(input.position(), database, Some(&key)))?;
+            continue;
         }
-        op_code::IDLE => {
-            let _idle_time = read_length(input)?;
-            process_next_operation(input, filter, state)
+
+        let mut value = if state.stream_elements && elements::is_read_lazily(value_type) {
+            elements::read_collection_start(input, &key, value_type, expiry).map(
+                |(start, pending)| {
+                    state.pending = Some(pending);
+                    start
+                },
+            )
+        } else if state.skip_corrupt_keys && can_skip_object(value_type) {
+            read_type_isolated(input, &key, value_type, expiry, modules, state)
+        } else {
+            read_type(input, &key, value_type, expiry, modules)
         }
-        op_code::FREQ => {
-            let _freq = input.read_u8()?;
-            process_next_operation(input, filter, state)
+        .map_err(|err| err.at(input.position(), database, Some(&key)))?;
+        if let Some(meta) = value.meta_mut() {
+            *meta = entry.meta(state.current_database, meta.encoding, input.position());
         }
-        value_type => {
-            if !filter.matches_db(state.current_database) {
-                skip_key_and_object(input, value_type)?;
-                return Ok(RdbValue::SelectDb(state.current_database));
-            }
+        // Collections decoded as a whole are yielded element by element as well
+        if state.stream_elements && state.pending.is_none() {
+            (value, state.pending) = elements::split_collection(value);
+        }
+        if state.pending.is_none() {
+            state.keys_read += 1;
+        }
+        return Ok(value);
+    }
+}
 
-            let key = read_blob(input)?;
-            if !filter.matches_type(value_type) || !filter.matches_key(&key) {
-                skip_object(input, value_type)?;
-                return Ok(RdbValue::SelectDb(state.current_database));
-            }
+/// Whether the filter leaves out a key of a selected database, counting it if so
+pub(crate) fn is_filtered<F: Filter>(
+    filter: &F,
+    state: &mut DecoderState,
+    value_type: u8,
+    key: &[u8],
+) -> bool {
+    if !filter.matches_type(value_type) {
+        state.filtered_keys.value_type += 1;
+        return true;
+    }
+    if !filter.matches_key(key) {
+        state.filtered_keys.key += 1;
+        return true;
+    }
+    false
+}
 
-            read_type(input, &key, value_type, state.last_expiretime)
-        }
+/// Reads entries up to the next value or key.
+/// Expiry and access metadata opcodes are followed by the key they belong to.
+pub(crate) fn read_entry<R: Read>(
+    input: &mut CountingReader<R>,
+    state: &mut DecoderState,
+) -> RdbResult<Entry> {
+    loop {
+        let op_offset = input.position();
+        // Dumps end with the EOF opcode, running out of data before it is a truncation
+        let next_op = input.read_u8()?;
+        check_operation(&state.header, next_op)?;
+
+        let value = match next_op {
+            op_code::SELECTDB => {
+                state.current_database = read_length(input)?;
+                RdbValue::SelectDb(state.current_database)
+            }
+            op_code::EOF => {
+                state.eof_marker = true;
+                let actual = input.checksum();
+                let mut checksum = Vec::new();
+                input.take(8).read_to_end(&mut checksum)?;
+                if state.header.has_checksum() && checksum.len() < 8 {
+                    return Err(RdbError::truncated());
+                }
+                state.reached_eof = true;
+                if state.verify_checksum {
+                    verify_checksum(&checksum, actual)?;
+                }
+                RdbValue::Checksum(checksum)
+            }
+            op_code::EXPIRETIME_MS => {
+                state.entry_offset.get_or_insert(op_offset);
+                state.last_expiretime = Some(input.read_u64::<LittleEndian>()?);
+                continue;
+            }
+            op_code::EXPIRETIME => {
+                state.entry_offset.get_or_insert(op_offset);
+                state.last_expiretime = Some(input.read_u32::<BigEndian>()? as u64 * 1000);
+                continue;
+            }
+            op_code::RESIZEDB => {
+                let db_size = read_length(input)?;
+                let expires_size = read_length(input)?;
+                RdbValue::ResizeDb {
+                    db_size,
+                    expires_size,
+                }
+            }
+            op_code::AUX => {
+                let key = read_blob(input)?;
+                let value = read_blob(input)?;
+                if key == b"valkey-ver" {
+                    state.header.flavor = ServerFlavor::Valkey;
+                }
+                RdbValue::AuxField { key, value }
+            }
+            op_code::MODULE_AUX => module::read_module_aux(input)?,
+            op_code::SLOT_INFO => {
+                let slot_id = read_length64(input)?;
+                let slot_size = read_length64(input)?;
+                let expires_slot_size = read_length64(input)?;
+                RdbValue::SlotInfo {
+                    slot_id,
+                    slot_size,
+                    expires_slot_size,
+                }
+            }
+            op_code::FUNCTION2 => {
+                let library_code = read_blob(input)?;
+                RdbValue::Function { library_code }
+            }
+            op_code::FUNCTION_PRE_GA => {
+                return Err(RdbError::unsupported(
+                    "Functions of Redis 7.0 release candidates are not supported",
+                ))
+            }
+            op_code::IDLE => {
+                state.entry_offset.get_or_insert(op_offset);
+                state.last_idle = Some(read_length64(input)?);
+                continue;
+            }
+            op_code::FREQ => {
+                state.entry_offset.get_or_insert(op_offset);
+                state.last_freq = Some(input.read_u8()?);
+                continue;
+            }
+            // Expiry and access metadata only apply to the key following them
+            value_type => {
+                return Ok(Entry::Key(KeyEntry {
+                    value_type,
+                    offset: state.entry_offset.take().unwrap_or(op_offset),
+                    expiry: state.last_expiretime.take(),
+                    idle: state.last_idle.take(),
+                    freq: state.last_freq.take(),
+                }))
+            }
+        };
+        return Ok(Entry::Value(Box::new(value)));
     }
 }
@@ -1,8 +1,9 @@
-use super::common::read_list_pack_entry_as_string;
-use super::common::utils::{read_blob, read_sequence};
-use crate::types::{RdbError, RdbResult, RdbValue};
+use super::common::read_list_pack_entries;
+use super::common::utils::{bounded_capacity, read_blob, read_sequence};
+use crate::types::{EncodingType, KeyMeta, RdbError, RdbResult, RdbValue};
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{Cursor, Read};
+use std::borrow::Cow;
+use std::io::Read;
 
 pub fn read_set<R: Read>(input: &mut R, key: &[u8], expiry: Option<u64>) -> RdbResult<RdbValue> {
     let values = read_sequence(input, |input| read_blob(input))?;
@@ -12,6 +13,7 @@ pub fn read_set<R: Read>(input: &mut R, key: &[u8], expiry: Option<u64>) -> RdbR
         key: key.to_vec(),
         members,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Hashtable),
     })
 }
 
@@ -21,29 +23,47 @@ pub fn read_set_intset<R: Read>(
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let intset = read_blob(input)?;
+    let intset_size = intset.len() as u64;
+    let members = read_intset_members(&intset)?;
 
-    let mut reader = Cursor::new(intset);
+    Ok(RdbValue::Set {
+        key: key.to_vec(),
+        members,
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Intset(intset_size)),
+    })
+}
+
+/// Reads the members of an intset held in memory as strings
+pub(crate) fn read_intset_members(intset: &[u8]) -> RdbResult<Vec<Vec<u8>>> {
+    read_intset(intset).map_err(|err| err.within_blob("intset"))
+}
+
+fn read_intset(intset: &[u8]) -> RdbResult<Vec<Vec<u8>>> {
+    let mut reader = intset;
     let byte_size = reader.read_u32::<LittleEndian>()?;
     let intset_length = reader.read_u32::<LittleEndian>()?;
 
-    let mut members = Vec::with_capacity(intset_length as usize);
+    if ![2, 4, 8].contains(&byte_size) {
+        return Err(RdbError::corrupt(
+            "intset",
+            format!("Unknown intset encoding: {}", byte_size),
+        ));
+    }
+
+    let mut members = Vec::with_capacity(bounded_capacity(intset_length.into()));
 
     for _ in 0..intset_length {
         let val = match byte_size {
             2 => reader.read_i16::<LittleEndian>()? as i64,
             4 => reader.read_i32::<LittleEndian>()? as i64,
-            8 => reader.read_i64::<LittleEndian>()?,
-            _ => panic!("unhandled byte size in intset: {}", byte_size),
+            _ => reader.read_i64::<LittleEndian>()?,
         };
 
         members.push(val.to_string().as_bytes().to_vec());
     }
 
-    Ok(RdbValue::Set {
-        key: key.to_vec(),
-        members: members.into_iter().collect(),
-        expiry,
-    })
+    Ok(members)
 }
 
 pub fn read_set_list_pack<R: Read>(
@@ -52,32 +72,16 @@ pub fn read_set_list_pack<R: Read>(
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let listpack = read_blob(input)?;
-    let mut reader = Cursor::new(listpack);
-
-    // Read total bytes and number of elements
-    let total_bytes = reader.read_u32::<LittleEndian>()?;
-    let num_elements = reader.read_u16::<LittleEndian>()?;
-
-    let mut members = Vec::with_capacity(num_elements as usize);
-
-    // Read until we reach the end of the listpack
-    while reader.position() < total_bytes as u64 - 1 {
-        let entry = read_list_pack_entry_as_string(&mut reader)?;
-        members.push(entry);
-    }
-
-    // Verify end byte
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_set_list_pack",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
-    }
+    let listpack_size = listpack.len() as u64;
+    let members = read_list_pack_entries(&listpack)?
+        .into_iter()
+        .map(Cow::into_owned)
+        .collect();
 
     Ok(RdbValue::Set {
         key: key.to_vec(),
-        members: members.into_iter().collect(),
+        members,
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::ListPack(listpack_size)),
     })
 }
@@ -0,0 +1,495 @@
+use super::common::utils::{
+    int_to_vec, read_length, read_length64, read_length_with_encoding, take,
+};
+use super::common::{read_list_pack_entries, read_ziplist_entries, CountingReader};
+use super::hash::{
+    field_expires_at, read_hash_list_pack_entries, read_hash_list_pack_ex_entries,
+    read_hash_ziplist_entries,
+};
+use super::module::{ModuleDecoder, ModuleRegistry};
+use super::rdb::{self, DecoderState, Entry, KeyEntry};
+use super::set::read_intset_members;
+use super::sorted_set::{
+    read_score, read_sorted_set_listpack_entries, read_sorted_set_ziplist_entries,
+};
+use crate::constants::{encoding, encoding_type};
+use crate::filter::Filter;
+use crate::types::{
+    Blob, BorrowedValue, EncodingType, FilteredKeys, KeyMeta, RdbError, RdbHeader, RdbResult,
+    TruncationReport,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+
+/// Decoder for a dump held in memory, such as a memory-mapped file,
+/// yielding values that borrow their strings from it instead of copying them.
+///
+/// It decodes the same entries as `RdbDecoder`, which is to be used for
+/// dumps read from a stream.
+pub struct SliceDecoder<'a, F: Filter> {
+    reader: CountingReader<&'a [u8]>,
+    filter: F,
+    modules: ModuleRegistry,
+    state: DecoderState,
+}
+
+impl<'a, F: Filter> SliceDecoder<'a, F> {
+    pub fn new(data: &'a [u8], filter: F) -> RdbResult<Self> {
+        let mut reader = CountingReader::new(data);
+        let header = super::read_header(&mut reader)?;
+        Ok(Self {
+            reader,
+            filter,
+            modules: ModuleRegistry::default(),
+            state: DecoderState {
+                header,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Version and server flavor of the dump, see `RdbDecoder::header`
+    pub fn header(&self) -> RdbHeader {
+        self.state.header
+    }
+
+    /// Verifies the CRC64 checksum at the end of the dump, see `RdbDecoder::verify_checksum`
+    pub fn verify_checksum(&mut self, enabled: bool) {
+        self.reader.track_checksum(enabled);
+        self.state.verify_checksum = enabled;
+    }
+
+    /// Skips keys whose value fails to decode instead of stopping,
+    /// see `RdbDecoder::skip_corrupt_keys`
+    pub fn skip_corrupt_keys(&mut self, enabled: bool) {
+        self.state.skip_corrupt_keys = enabled;
+    }
+
+    /// Whether all entries were read, or decoding stopped at an error
+    /// it cannot recover from
+    pub fn is_finished(&self) -> bool {
+        self.state.reached_eof
+    }
+
+    /// Number of keys left out by the filter so far
+    pub fn filtered_keys(&self) -> FilteredKeys {
+        self.state.filtered_keys
+    }
+
+    /// How far the dump could be read, once decoding stopped at its truncated end
+    pub fn truncation(&self) -> Option<&TruncationReport> {
+        self.state.truncation.as_ref()
+    }
+
+    /// Registers a decoder for values of a specific module type
+    pub fn add_module_decoder(&mut self, decoder: Box<dyn ModuleDecoder>) {
+        self.modules.add(decoder);
+    }
+
+    /// Keeps the payload of module values without a registered decoder
+    /// as raw fields instead of skipping it
+    pub fn capture_module_payloads(&mut self, capture: bool) {
+        self.modules.set_capture_payloads(capture);
+    }
+
+    fn read_next(&mut self) -> RdbResult<BorrowedValue<'a>> {
+        loop {
+            let entry = match rdb::read_entry(&mut self.reader, &mut self.state)? {
+                Entry::Value(value) => return Ok(BorrowedValue::Owned(*value)),
+                Entry::Key(entry) => entry,
+            };
+
+            let mut input = self.reader.remaining();
+            let value = read_key(
+                &mut input,
+                self.reader.position(),
+                &entry,
+                &self.filter,
+                &self.modules,
+                &mut self.state,
+            );
+            let read = self.reader.remaining().len() - input.len();
+            self.reader.advance(read);
+            if let Some(value) = value? {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl<'a, F: Filter> Iterator for SliceDecoder<'a, F> {
+    type Item = RdbResult<BorrowedValue<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state.reached_eof {
+            return None;
+        }
+        Some(
+            self.read_next()
+                .map_err(|err| rdb::locate_error(err, self.reader.position(), &mut self.state)),
+        )
+    }
+}
+
+// Reads the name and value of a key starting at `offset`, `None` if it is filtered
+fn read_key<'a, F: Filter>(
+    input: &mut &'a [u8],
+    offset: u64,
+    entry: &KeyEntry,
+    filter: &F,
+    modules: &ModuleRegistry,
+    state: &mut DecoderState,
+) -> RdbResult<Option<BorrowedValue<'a>>> {
+    let start = input.len();
+    let position = |input: &[u8]| offset + (start - input.len()) as u64;
+    let value_type = entry.value_type;
+
+    if !filter.matches_db(state.current_database) {
+        rdb::skip_key_and_object(input, value_type, modules)?;
+        state.filtered_keys.database += 1;
+        return Ok(None);
+    }
+
+    let key = read_blob(input)?.into_bytes()?;
+    let database = Some(state.current_database);
+    if rdb::is_filtered(filter, state, value_type, &key) {
+        rdb::skip_filtered_object(input, value_type, modules)
+            .map_err(|err| err.at(position(input), database, Some(&key)))?;
+        return Ok(None);
+    }
+
+    let mut value = if state.skip_corrupt_keys && rdb::can_skip_object(value_type) {
+        // The extent of the value is known before decoding it, so that
+        // a value failing to decode leaves the input at the next entry
+        let mut rest = *input;
+        rdb::skip_object(&mut rest, value_type)
+            .map_err(|err| err.at(position(rest), database, Some(&key)))?;
+        let mut object = &input[..input.len() - rest.len()];
+        let object_offset = position(input);
+        let object_length = object.len();
+        *input = rest;
+        read_value(&mut object, &key, value_type, entry.expiry, modules).map_err(|err| {
+            state.recovered = true;
+            let read = object_length - object.len();
+            err.within_blob("value")
+                .at(object_offset + read as u64, database, Some(&key))
+        })?
+    } else {
+        read_value(input, &key, value_type, entry.expiry, modules)
+            .map_err(|err| err.at(position(input), database, Some(&key)))?
+    };
+    if let Some(meta) = value.meta_mut() {
+        *meta = entry.meta(state.current_database, meta.encoding, position(input));
+    }
+    state.keys_read += 1;
+    Ok(Some(value))
+}
+
+fn read_value<'a>(
+    input: &mut &'a [u8],
+    key: &Cow<'a, [u8]>,
+    value_type: u8,
+    expiry: Option<u64>,
+    modules: &ModuleRegistry,
+) -> RdbResult<BorrowedValue<'a>> {
+    let key = key.clone();
+    let value = match value_type {
+        encoding_type::STRING => BorrowedValue::String {
+            key,
+            value: read_blob(input)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::String),
+        },
+        encoding_type::LIST => BorrowedValue::List {
+            key,
+            values: read_sequence(input, read_blob)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::LinkedList),
+        },
+        encoding_type::SET => BorrowedValue::Set {
+            key,
+            members: read_sequence(input, read_string)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::Hashtable),
+        },
+        encoding_type::ZSET | encoding_type::ZSET_2 => {
+            let is_zset2 = value_type == encoding_type::ZSET_2;
+            BorrowedValue::SortedSet {
+                key,
+                values: read_sequence(input, |input| {
+                    let member = read_string(input)?;
+                    Ok((read_score(input, is_zset2)?, member))
+                })?,
+                expiry,
+                meta: KeyMeta::with_encoding(if is_zset2 {
+                    EncodingType::ZSet2
+                } else {
+                    EncodingType::Skiplist
+                }),
+            }
+        }
+        encoding_type::HASH => BorrowedValue::Hash {
+            key,
+            values: read_sequence(input, |input| Ok((read_string(input)?, read_blob(input)?)))?,
+            field_expiry: IndexMap::new(),
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::Hashtable),
+        },
+        encoding_type::HASH_METADATA_PRE_GA | encoding_type::HASH_METADATA => {
+            let min_expire = if value_type == encoding_type::HASH_METADATA {
+                Some(input.read_u64::<LittleEndian>()?)
+            } else {
+                None
+            };
+            let mut field_expiry = IndexMap::new();
+            let values = read_sequence(input, |input| {
+                let ttl = read_length64(input)?;
+                let field = read_string(input)?;
+                if let Some(expires_at) = field_expires_at(ttl, min_expire) {
+                    field_expiry.insert(field.clone(), expires_at);
+                }
+                Ok((field, read_blob(input)?))
+            })?;
+            BorrowedValue::Hash {
+                key,
+                values,
+                field_expiry,
+                expiry,
+                meta: KeyMeta::with_encoding(EncodingType::Hashtable),
+            }
+        }
+        encoding_type::LIST_ZIPLIST
+        | encoding_type::SET_INTSET
+        | encoding_type::SET_LIST_PACK
+        | encoding_type::ZSET_ZIPLIST
+        | encoding_type::ZSET_LIST_PACK
+        | encoding_type::HASH_ZIPLIST
+        | encoding_type::HASH_LIST_PACK
+        | encoding_type::HASH_LIST_PACK_EX_PRE_GA
+        | encoding_type::HASH_LIST_PACK_EX => {
+            if value_type == encoding_type::HASH_LIST_PACK_EX {
+                let _min_expire = input.read_u64::<LittleEndian>()?;
+            }
+            match read_blob(input)?.into_bytes()? {
+                Cow::Borrowed(blob) => read_compact(blob, key, value_type, expiry)?,
+                // Compressed encodings are decoded from their decompressed copy
+                Cow::Owned(blob) => read_compact(&blob, key, value_type, expiry)?
+                    .into_owned()?
+                    .into(),
+            }
+        }
+        encoding_type::LIST_QUICKLIST => {
+            let mut values = Vec::new();
+            for _ in 0..read_length(input)? {
+                let ziplist = read_blob(input)?.into_bytes()?;
+                let entries = read_entries(ziplist, read_ziplist_entries)?;
+                values.extend(entries.into_iter().map(Blob::Bytes));
+            }
+            BorrowedValue::List {
+                key,
+                values,
+                expiry,
+                meta: KeyMeta::with_encoding(EncodingType::Quicklist),
+            }
+        }
+        encoding_type::LIST_QUICKLIST_2 => {
+            let mut values = Vec::new();
+            for _ in 0..read_length(input)? {
+                let container_type = read_length(input)?;
+                match container_type {
+                    // QUICKLIST_NODE_CONTAINER_PLAIN
+                    1 => values.push(read_blob(input)?),
+                    // QUICKLIST_NODE_CONTAINER_PACKED
+                    2 => {
+                        let listpack = read_blob(input)?.into_bytes()?;
+                        let entries = read_entries(listpack, read_list_pack_entries)?;
+                        values.extend(entries.into_iter().map(Blob::Bytes));
+                    }
+                    _ => {
+                        return Err(RdbError::corrupt(
+                            "quicklist",
+                            format!("Unknown encoding value: {}", container_type),
+                        ))
+                    }
+                }
+            }
+            BorrowedValue::List {
+                key,
+                values,
+                expiry,
+                meta: KeyMeta::with_encoding(EncodingType::Quicklist2),
+            }
+        }
+        // Zipmaps, streams and module values are decoded into owned data
+        _ => rdb::read_type(input, &key, value_type, expiry, modules)?.into(),
+    };
+    Ok(value)
+}
+
+// Decodes a value of a compact encoding from the blob holding it,
+// borrowing its strings from the blob
+fn read_compact<'a>(
+    blob: &'a [u8],
+    key: Cow<'a, [u8]>,
+    value_type: u8,
+    expiry: Option<u64>,
+) -> RdbResult<BorrowedValue<'a>> {
+    let size = blob.len() as u64;
+    let value = match value_type {
+        encoding_type::LIST_ZIPLIST => BorrowedValue::List {
+            key,
+            values: read_ziplist_entries(blob)?
+                .into_iter()
+                .map(Blob::Bytes)
+                .collect(),
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::Ziplist(size)),
+        },
+        encoding_type::SET_INTSET => BorrowedValue::Set {
+            key,
+            members: read_intset_members(blob)?
+                .into_iter()
+                .map(Cow::Owned)
+                .collect(),
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::Intset(size)),
+        },
+        encoding_type::SET_LIST_PACK => BorrowedValue::Set {
+            key,
+            members: read_list_pack_entries(blob)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::ListPack(size)),
+        },
+        encoding_type::ZSET_ZIPLIST => BorrowedValue::SortedSet {
+            key,
+            values: read_sorted_set_ziplist_entries(blob)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::Ziplist(size)),
+        },
+        encoding_type::ZSET_LIST_PACK => BorrowedValue::SortedSet {
+            key,
+            values: read_sorted_set_listpack_entries(blob)?,
+            expiry,
+            meta: KeyMeta::with_encoding(EncodingType::ListPack(size)),
+        },
+        encoding_type::HASH_ZIPLIST | encoding_type::HASH_LIST_PACK => {
+            let (values, encoding) = if value_type == encoding_type::HASH_ZIPLIST {
+                (
+                    read_hash_ziplist_entries(blob)?,
+                    EncodingType::Ziplist(size),
+                )
+            } else {
+                (
+                    read_hash_list_pack_entries(blob)?,
+                    EncodingType::ListPack(size),
+                )
+            };
+            BorrowedValue::Hash {
+                key,
+                values: values
+                    .into_iter()
+                    .map(|(field, value)| (field, Blob::Bytes(value)))
+                    .collect(),
+                field_expiry: IndexMap::new(),
+                expiry,
+                meta: KeyMeta::with_encoding(encoding),
+            }
+        }
+        _ => {
+            let (values, field_expiry) = read_hash_list_pack_ex_entries(blob)?;
+            BorrowedValue::Hash {
+                key,
+                values: values
+                    .into_iter()
+                    .map(|(field, value)| (field, Blob::Bytes(value)))
+                    .collect(),
+                field_expiry: field_expiry.into_iter().collect(),
+                expiry,
+                meta: KeyMeta::with_encoding(EncodingType::ListPack(size)),
+            }
+        }
+    };
+    Ok(value)
+}
+
+type EntryReader = fn(&[u8]) -> RdbResult<Vec<Cow<'_, [u8]>>>;
+
+// Decodes the entries of a compact encoding with `read`, borrowing them
+// from the dump unless the blob holding them is compressed
+fn read_entries<'a>(blob: Cow<'a, [u8]>, read: EntryReader) -> RdbResult<Vec<Cow<'a, [u8]>>> {
+    match blob {
+        Cow::Borrowed(blob) => read(blob),
+        Cow::Owned(blob) => Ok(read(&blob)?
+            .into_iter()
+            .map(|entry| Cow::Owned(entry.into_owned()))
+            .collect()),
+    }
+}
+
+fn read_sequence<'a, T, F>(input: &mut &'a [u8], mut read: F) -> RdbResult<Vec<T>>
+where
+    F: FnMut(&mut &'a [u8]) -> RdbResult<T>,
+{
+    let len = read_length(input)?;
+    // Every element takes at least one byte
+    let mut values = Vec::with_capacity((len as usize).min(input.len()));
+    for _ in 0..len {
+        values.push(read(input)?);
+    }
+    Ok(values)
+}
+
+/// Reads a string without copying it, leaving it compressed
+pub(crate) fn read_blob<'a>(input: &mut &'a [u8]) -> RdbResult<Blob<'a>> {
+    let (length, is_encoded) = read_length_with_encoding(input)?;
+    if !is_encoded {
+        return Ok(Blob::Bytes(Cow::Borrowed(take(input, length as usize)?)));
+    }
+
+    let blob = match length {
+        encoding::INT8 => int_to_vec(i32::from(input.read_i8()?)),
+        encoding::INT16 => int_to_vec(i32::from(input.read_i16::<LittleEndian>()?)),
+        encoding::INT32 => int_to_vec(input.read_i32::<LittleEndian>()?),
+        encoding::LZF => {
+            let compressed_length = read_length(input)?;
+            let length = read_length(input)? as usize;
+            let compressed = take(input, compressed_length as usize)?;
+            return Ok(Blob::Lzf { compressed, length });
+        }
+        _ => {
+            return Err(RdbError::corrupt(
+                "string",
+                format!("Unknown encoding value: {}", length),
+            ));
+        }
+    };
+    Ok(Blob::Bytes(Cow::Owned(blob)))
+}
+
+fn read_string<'a>(input: &mut &'a [u8]) -> RdbResult<Cow<'a, [u8]>> {
+    read_blob(input)?.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_blob() {
+        let data = b"\x05hello\xc0\x2a\xc3\x05\x06\x01ab\x40\x01";
+        let mut input = &data[..];
+
+        let blob = read_blob(&mut input).unwrap();
+        assert!(matches!(blob, Blob::Bytes(Cow::Borrowed(b"hello"))));
+        let blob = read_blob(&mut input).unwrap();
+        assert_eq!(blob.bytes().unwrap().as_ref(), b"42");
+        let blob = read_blob(&mut input).unwrap();
+        assert!(blob.is_compressed());
+        assert_eq!(blob.len(), 6);
+        assert_eq!(blob.bytes().unwrap().as_ref(), b"ababab");
+        assert!(input.is_empty());
+
+        assert!(read_blob(&mut &b"\x05hel"[..]).is_err());
+    }
+}
@@ -1,10 +1,9 @@
-use super::common::utils::{read_blob, read_exact, read_length};
-use super::common::{read_list_pack_length, read_ziplist_entry_string, read_ziplist_metadata};
-use crate::decoder::common::read_list_pack_entry_as_string;
-use crate::types::{RdbError, RdbResult, RdbValue};
+use super::common::utils::{bounded_capacity, parse_score, read_blob, read_exact, read_length};
+use super::common::{read_list_pack_entry_as_bytes, read_list_pack_length, read_ziplist_entries};
+use crate::types::{EncodingType, KeyMeta, RdbError, RdbResult, RdbValue};
 use byteorder::ReadBytesExt;
-use std::io::{Cursor, Read};
-use std::str;
+use std::borrow::Cow;
+use std::io::Read;
 
 pub fn read_sorted_set<R: Read>(
     input: &mut R,
@@ -13,29 +12,10 @@ pub fn read_sorted_set<R: Read>(
     is_zset2: bool,
 ) -> RdbResult<RdbValue> {
     let mut set_items = read_length(input)?;
-    let mut values = Vec::with_capacity(set_items as usize);
+    let mut values = Vec::with_capacity(bounded_capacity(set_items.into()));
 
     while set_items > 0 {
-        let val = read_blob(input)?;
-
-        let score = if is_zset2 {
-            // ZSET2 format uses binary encoding of float64
-            input.read_f64::<byteorder::LittleEndian>()?
-        } else {
-            // Original format uses string representation
-            let score_length = input.read_u8()?;
-            match score_length {
-                253 => f64::NAN,
-                254 => f64::INFINITY,
-                255 => f64::NEG_INFINITY,
-                _ => {
-                    let tmp = read_exact(input, score_length as usize)?;
-                    String::from_utf8_lossy(&tmp).parse::<f64>().unwrap()
-                }
-            }
-        };
-
-        values.push((score, val));
+        values.push(read_sorted_set_entry(input, is_zset2)?);
         set_items -= 1;
     }
 
@@ -43,82 +23,139 @@ pub fn read_sorted_set<R: Read>(
         key: key.to_vec(),
         values,
         expiry,
+        meta: KeyMeta::with_encoding(if is_zset2 {
+            EncodingType::ZSet2
+        } else {
+            EncodingType::Skiplist
+        }),
     })
 }
 
+/// Reads a member of a skiplist encoded sorted set and its score
+pub(crate) fn read_sorted_set_entry<R: Read>(
+    input: &mut R,
+    is_zset2: bool,
+) -> RdbResult<(f64, Vec<u8>)> {
+    let val = read_blob(input)?;
+    let score = read_score(input, is_zset2)?;
+    Ok((score, val))
+}
+
+/// Reads the score following a member of a skiplist encoded sorted set
+pub(crate) fn read_score<R: Read>(input: &mut R, is_zset2: bool) -> RdbResult<f64> {
+    if is_zset2 {
+        // ZSET2 format uses binary encoding of float64
+        return Ok(input.read_f64::<byteorder::LittleEndian>()?);
+    }
+    // Original format uses string representation
+    let score_length = input.read_u8()?;
+    let score = match score_length {
+        253 => f64::NAN,
+        254 => f64::INFINITY,
+        255 => f64::NEG_INFINITY,
+        _ => {
+            let tmp = read_exact(input, score_length as usize)?;
+            parse_score("skiplist", &tmp)?
+        }
+    };
+    Ok(score)
+}
+
 pub fn read_sorted_set_ziplist<R: Read>(
     input: &mut R,
     key: &[u8],
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let ziplist = read_blob(input)?;
-    let mut reader = Cursor::new(ziplist);
-    let (_zlbytes, _zltail, zllen) = read_ziplist_metadata(&mut reader)?;
-
-    assert!(zllen % 2 == 0);
-    let zllen = zllen / 2;
-    let mut values = Vec::with_capacity(zllen as usize);
-
-    for _ in 0..zllen {
-        let entry = read_ziplist_entry_string(&mut reader)?;
-        let score = read_ziplist_entry_string(&mut reader)?;
-        let score = str::from_utf8(&score).unwrap().parse::<f64>().unwrap();
-        values.push((score, entry));
-    }
-
-    let last_byte = reader.read_u8()?;
-    if last_byte != 0xFF {
-        return Err(RdbError::ParsingError {
-            context: "read_sortedset_ziplist",
-            message: format!("Unknown encoding value: {}", last_byte),
-        });
-    }
+    let ziplist_size = ziplist.len() as u64;
+    let values = read_sorted_set_ziplist_entries(&ziplist)?;
 
     Ok(RdbValue::SortedSet {
         key: key.to_vec(),
-        values,
+        values: into_owned_members(values),
         expiry,
+        meta: KeyMeta::with_encoding(EncodingType::Ziplist(ziplist_size)),
     })
 }
 
+/// Reads the members and scores of a sorted set ziplist held in memory,
+/// borrowing members from it
+pub(crate) fn read_sorted_set_ziplist_entries(
+    ziplist: &[u8],
+) -> RdbResult<Vec<(f64, Cow<'_, [u8]>)>> {
+    let entries = read_ziplist_entries(ziplist)?;
+    if !entries.len().is_multiple_of(2) {
+        return Err(RdbError::corrupt(
+            "ziplist",
+            format!("Odd number of ziplist entries: {}", entries.len()),
+        ));
+    }
+
+    let mut values = Vec::with_capacity(entries.len() / 2);
+    let mut entries = entries.into_iter();
+    while let (Some(member), Some(score)) = (entries.next(), entries.next()) {
+        let score = parse_score("ziplist", &score)?;
+        values.push((score, member));
+    }
+    Ok(values)
+}
+
 pub fn read_sorted_set_listpack<R: Read>(
     input: &mut R,
     key: &[u8],
     expiry: Option<u64>,
 ) -> RdbResult<RdbValue> {
     let listpack = read_blob(input)?;
-    let mut reader = Cursor::new(&listpack);
+    let listpack_size = listpack.len() as u64;
+    let values = read_sorted_set_listpack_entries(&listpack)?;
+
+    Ok(RdbValue::SortedSet {
+        key: key.to_vec(),
+        values: into_owned_members(values),
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::ListPack(listpack_size)),
+    })
+}
+
+/// Reads the members and scores of a sorted set listpack held in memory,
+/// borrowing members from it
+pub(crate) fn read_sorted_set_listpack_entries(
+    listpack: &[u8],
+) -> RdbResult<Vec<(f64, Cow<'_, [u8]>)>> {
+    read_listpack_entries(listpack).map_err(|err| err.within_blob("listpack"))
+}
+
+fn read_listpack_entries(listpack: &[u8]) -> RdbResult<Vec<(f64, Cow<'_, [u8]>)>> {
     let mut values = Vec::new();
 
     // Read number of elements (size)
-    let buf = reader.get_ref();
     let mut cursor = 0;
-    let size = read_list_pack_length(buf, &mut cursor);
-    reader.set_position(cursor as u64);
+    let size = read_list_pack_length(listpack, &mut cursor)?;
 
-    assert!(size % 2 == 0);
+    if !size.is_multiple_of(2) {
+        return Err(RdbError::corrupt(
+            "listpack",
+            format!("Odd number of listpack entries: {}", size),
+        ));
+    }
     let num_entries = size / 2;
 
+    let mut reader = &listpack[cursor..];
     for _ in 0..num_entries {
-        let member = read_list_pack_entry_as_string(&mut reader)?;
-        let score_str = read_list_pack_entry_as_string(&mut reader)?;
-
-        let score = String::from_utf8_lossy(&score_str)
-            .parse::<f64>()
-            .map_err(|_| RdbError::ParsingError {
-                context: "read_sorted_set_listpack",
-                message: format!(
-                    "Failed to parse score: {:?}",
-                    String::from_utf8_lossy(&score_str)
-                ),
-            })?;
+        let member = read_list_pack_entry_as_bytes(&mut reader)?;
+        let score_str = read_list_pack_entry_as_bytes(&mut reader)?;
+
+        let score = parse_score("listpack", &score_str)?;
 
         values.push((score, member));
     }
 
-    Ok(RdbValue::SortedSet {
-        key: key.to_vec(),
-        values,
-        expiry,
-    })
+    Ok(values)
+}
+
+fn into_owned_members(values: Vec<(f64, Cow<'_, [u8]>)>) -> Vec<(f64, Vec<u8>)> {
+    values
+        .into_iter()
+        .map(|(score, member)| (score, member.into_owned()))
+        .collect()
 }
@@ -0,0 +1,514 @@
+use super::common::utils::{bounded_capacity, read_blob, read_length, read_length64};
+use super::common::{
+    read_list_pack_entry_as_int, read_list_pack_entry_as_string, read_list_pack_length, Skip,
+};
+use super::rdb::skip_blob;
+use crate::constants::encoding_type;
+use crate::types::{
+    EncodingType, KeyMeta, RdbError, RdbResult, RdbValue, Stream, StreamConsumer,
+    StreamConsumerGroup, StreamEntry, StreamId, StreamPendingEntry,
+};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+
+const STREAM_ITEM_FLAG_DELETED: i64 = 1 << 0;
+const STREAM_ITEM_FLAG_SAME_FIELDS: i64 = 1 << 1;
+
+pub fn read_stream_list_packs<R: Read>(
+    input: &mut R,
+    key: &[u8],
+    expiry: Option<u64>,
+    enc_type: u8,
+) -> RdbResult<RdbValue> {
+    let nodes = read_length(input)?;
+    let mut entries = Vec::new();
+
+    for _ in 0..nodes {
+        let node_key = read_blob(input)?;
+        let master_id = read_raw_stream_id(&mut Cursor::new(node_key))
+            .map_err(|err| err.within_blob("stream"))?;
+        let listpack = read_blob(input)?;
+        read_stream_node(&listpack, master_id, &mut entries)
+            .map_err(|err| err.within_blob("stream"))?;
+    }
+
+    let length = read_length64(input)?;
+    let last_id = read_stream_id(input)?;
+
+    let (first_id, max_deleted_id, entries_added) =
+        if enc_type >= encoding_type::STREAM_LIST_PACKS_2 {
+            let first_id = read_stream_id(input)?;
+            let max_deleted_id = read_stream_id(input)?;
+            let entries_added = read_length64(input)?;
+            (Some(first_id), Some(max_deleted_id), Some(entries_added))
+        } else {
+            (None, None, None)
+        };
+
+    let groups = read_consumer_groups(input, enc_type)?;
+
+    Ok(RdbValue::Stream {
+        key: key.to_vec(),
+        stream: Stream {
+            entries,
+            length,
+            last_id,
+            first_id,
+            max_deleted_id,
+            entries_added,
+            groups,
+        },
+        expiry,
+        meta: KeyMeta::with_encoding(EncodingType::StreamListPacks),
+    })
+}
+
+/// Skips a stream, following the same layout as `read_stream_list_packs`
+pub(crate) fn skip_stream_list_packs<R: Skip>(input: &mut R, enc_type: u8) -> RdbResult<()> {
+    for _ in 0..read_length(input)? {
+        // Node key and listpack
+        skip_blob(input)?;
+        skip_blob(input)?;
+    }
+
+    let _length = read_length64(input)?;
+    let _last_id = read_stream_id(input)?;
+    if enc_type >= encoding_type::STREAM_LIST_PACKS_2 {
+        let _first_id = read_stream_id(input)?;
+        let _max_deleted_id = read_stream_id(input)?;
+        let _entries_added = read_length64(input)?;
+    }
+
+    for _ in 0..read_length64(input)? {
+        skip_blob(input)?;
+        let _last_delivered_id = read_stream_id(input)?;
+        if enc_type >= encoding_type::STREAM_LIST_PACKS_2 {
+            let _entries_read = read_length64(input)?;
+        }
+
+        for _ in 0..read_length64(input)? {
+            // Raw ID and delivery time
+            input.skip(16 + 8)?;
+            let _delivery_count = read_length64(input)?;
+        }
+
+        for _ in 0..read_length64(input)? {
+            skip_blob(input)?;
+            // Seen time, and active time since version 3
+            if enc_type >= encoding_type::STREAM_LIST_PACKS_3 {
+                input.skip(8 + 8)?;
+            } else {
+                input.skip(8)?;
+            }
+            for _ in 0..read_length64(input)? {
+                input.skip(16)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a stream ID stored as two length-encoded integers
+fn read_stream_id<R: Read>(input: &mut R) -> RdbResult<StreamId> {
+    let ms = read_length64(input)?;
+    let seq = read_length64(input)?;
+    Ok(StreamId { ms, seq })
+}
+
+/// Reads a stream ID stored as 16 raw big-endian bytes
+fn read_raw_stream_id<R: Read>(input: &mut R) -> RdbResult<StreamId> {
+    let ms = input.read_u64::<BigEndian>()?;
+    let seq = input.read_u64::<BigEndian>()?;
+    Ok(StreamId { ms, seq })
+}
+
+/// Walks a single listpack node of the stream radix tree.
+///
+/// The node starts with a master entry holding the number of valid and
+/// deleted entries and the master fields. Every following entry stores its
+/// ID as a delta to the master ID and either references the master fields
+/// or carries its own field names.
+fn read_stream_node(
+    listpack: &[u8],
+    master_id: StreamId,
+    entries: &mut Vec<StreamEntry>,
+) -> RdbResult<()> {
+    let mut cursor = 0;
+    let _size = read_list_pack_length(listpack, &mut cursor)?;
+    let mut reader = Cursor::new(listpack);
+    reader.set_position(cursor as u64);
+
+    let count = read_list_pack_entry_as_int(&mut reader)?;
+    let deleted = read_list_pack_entry_as_int(&mut reader)?;
+    let num_master_fields = read_list_pack_entry_as_int(&mut reader)?;
+
+    let mut master_fields = Vec::with_capacity(bounded_capacity(num_master_fields as u64));
+    for _ in 0..num_master_fields {
+        master_fields.push(read_list_pack_entry_as_string(&mut reader)?);
+    }
+
+    // The master entry is terminated by a zero
+    let _master_terminator = read_list_pack_entry_as_int(&mut reader)?;
+
+    for _ in 0..count.saturating_add(deleted) {
+        let flags = read_list_pack_entry_as_int(&mut reader)?;
+        let ms_diff = read_list_pack_entry_as_int(&mut reader)?;
+        let seq_diff = read_list_pack_entry_as_int(&mut reader)?;
+        let id = StreamId {
+            ms: master_id.ms.wrapping_add(ms_diff as u64),
+            seq: master_id.seq.wrapping_add(seq_diff as u64),
+        };
+
+        let fields = if flags & STREAM_ITEM_FLAG_SAME_FIELDS != 0 {
+            let mut fields = Vec::with_capacity(master_fields.len());
+            for field in &master_fields {
+                let value = read_list_pack_entry_as_string(&mut reader)?;
+                fields.push((field.clone(), value));
+            }
+            fields
+        } else {
+            let num_fields = read_list_pack_entry_as_int(&mut reader)?;
+            let mut fields = Vec::with_capacity(bounded_capacity(num_fields as u64));
+            for _ in 0..num_fields {
+                let field = read_list_pack_entry_as_string(&mut reader)?;
+                let value = read_list_pack_entry_as_string(&mut reader)?;
+                fields.push((field, value));
+            }
+            fields
+        };
+
+        // Number of listpack elements of this entry, used for backwards traversal
+        let _lp_count = read_list_pack_entry_as_int(&mut reader)?;
+
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            entries.push(StreamEntry { id, fields });
+        }
+    }
+
+    let last_byte = reader.read_u8()?;
+    if last_byte != 0xFF {
+        return Err(RdbError::corrupt(
+            "stream",
+            format!("Unknown encoding value: {}", last_byte),
+        ));
+    }
+
+    Ok(())
+}
+
+fn read_consumer_groups<R: Read>(
+    input: &mut R,
+    enc_type: u8,
+) -> RdbResult<Vec<StreamConsumerGroup>> {
+    let groups_count = read_length64(input)?;
+    let mut groups = Vec::new();
+
+    for _ in 0..groups_count {
+        let name = read_blob(input)?;
+        let last_delivered_id = read_stream_id(input)?;
+        let entries_read = if enc_type >= encoding_type::STREAM_LIST_PACKS_2 {
+            Some(read_length64(input)?)
+        } else {
+            None
+        };
+
+        // Global pending entries list of the group
+        let pel_size = read_length64(input)?;
+        let mut pending = Vec::new();
+        for _ in 0..pel_size {
+            let id = read_raw_stream_id(input)?;
+            let delivery_time = input.read_u64::<LittleEndian>()?;
+            let delivery_count = read_length64(input)?;
+            pending.push(StreamPendingEntry {
+                id,
+                delivery_time,
+                delivery_count,
+            });
+        }
+
+        let pending_ids: HashSet<StreamId> = pending.iter().map(|nack| nack.id).collect();
+        let consumers_count = read_length64(input)?;
+        let mut consumers = Vec::new();
+        for _ in 0..consumers_count {
+            let name = read_blob(input)?;
+            let seen_time = input.read_u64::<LittleEndian>()?;
+            let active_time = if enc_type >= encoding_type::STREAM_LIST_PACKS_3 {
+                Some(input.read_u64::<LittleEndian>()?)
+            } else {
+                None
+            };
+
+            // Consumer PELs only reference entries of the global PEL
+            let pel_size = read_length64(input)?;
+            let mut consumer_pending = Vec::new();
+            for _ in 0..pel_size {
+                let id = read_raw_stream_id(input)?;
+                if !pending_ids.contains(&id) {
+                    return Err(RdbError::corrupt(
+                        "stream",
+                        format!("Consumer pending entry {} not found in group PEL", id),
+                    ));
+                }
+                consumer_pending.push(id);
+            }
+
+            consumers.push(StreamConsumer {
+                name,
+                seen_time,
+                active_time,
+                pending: consumer_pending,
+            });
+        }
+
+        groups.push(StreamConsumerGroup {
+            name,
+            last_delivered_id,
+            entries_read,
+            pending,
+            consumers,
+        });
+    }
+
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    enum Lp {
+        Int(u8),
+        Str(&'static [u8]),
+    }
+
+    fn blob(data: &[u8]) -> Vec<u8> {
+        assert!(data.len() < 1 << 14);
+        let mut out = if data.len() < 64 {
+            vec![data.len() as u8]
+        } else {
+            vec![0x40 | (data.len() >> 8) as u8, data.len() as u8]
+        };
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn length64(value: u64) -> Vec<u8> {
+        let mut out = vec![0x81];
+        out.extend_from_slice(&value.to_be_bytes());
+        out
+    }
+
+    fn list_pack(entries: &[Lp]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for entry in entries {
+            match entry {
+                Lp::Int(val) => body.extend_from_slice(&[*val & 0x7F, 1]),
+                Lp::Str(val) => {
+                    body.push(0x80 | val.len() as u8);
+                    body.extend_from_slice(val);
+                    body.push(1 + val.len() as u8);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        out.extend_from_slice(&(body.len() as u32 + 7).to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&body);
+        out.push(0xFF);
+        out
+    }
+
+    const MASTER_MS: u64 = 1_700_000_000_000;
+
+    fn stream_nodes() -> Vec<u8> {
+        let mut master_id = MASTER_MS.to_be_bytes().to_vec();
+        master_id.extend_from_slice(&0u64.to_be_bytes());
+
+        let listpack = list_pack(&[
+            // master entry: count, deleted, master fields, terminator
+            Lp::Int(2),
+            Lp::Int(1),
+            Lp::Int(2),
+            Lp::Str(b"a"),
+            Lp::Str(b"b"),
+            Lp::Int(0),
+            // entry with the master fields
+            Lp::Int(2),
+            Lp::Int(0),
+            Lp::Int(0),
+            Lp::Str(b"1"),
+            Lp::Str(b"2"),
+            Lp::Int(5),
+            // deleted entry
+            Lp::Int(3),
+            Lp::Int(1),
+            Lp::Int(0),
+            Lp::Str(b"3"),
+            Lp::Str(b"4"),
+            Lp::Int(5),
+            // entry with its own fields
+            Lp::Int(0),
+            Lp::Int(2),
+            Lp::Int(5),
+            Lp::Int(1),
+            Lp::Str(b"c"),
+            Lp::Int(42),
+            Lp::Int(6),
+        ]);
+
+        let mut out = vec![1];
+        out.extend(blob(&master_id));
+        out.extend(blob(&listpack));
+        out
+    }
+
+    #[test]
+    fn test_read_stream_list_packs_2() {
+        let mut input = stream_nodes();
+        input.push(2);
+        input.extend(length64(MASTER_MS + 2));
+        input.push(5);
+        input.extend(length64(MASTER_MS));
+        input.push(0);
+        input.extend(length64(MASTER_MS + 1));
+        input.push(0);
+        input.push(3);
+        input.push(0);
+
+        let mut cursor = Cursor::new(input);
+        let value = read_stream_list_packs(
+            &mut cursor,
+            b"mystream",
+            None,
+            encoding_type::STREAM_LIST_PACKS_2,
+        )
+        .unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        let RdbValue::Stream { key, stream, .. } = value else {
+            panic!("Expected a stream, got {:?}", value);
+        };
+        assert_eq!(key, b"mystream");
+        assert_eq!(stream.length, 2);
+        assert_eq!(stream.entries.len(), 2);
+        assert_eq!(stream.entries[0].id.to_string(), "1700000000000-0");
+        assert_eq!(
+            stream.entries[0].fields,
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ]
+        );
+        assert_eq!(stream.entries[1].id.to_string(), "1700000000002-5");
+        assert_eq!(
+            stream.entries[1].fields,
+            vec![(b"c".to_vec(), b"42".to_vec())]
+        );
+        assert_eq!(stream.last_id.to_string(), "1700000000002-5");
+        assert_eq!(stream.first_id.unwrap().to_string(), "1700000000000-0");
+        assert_eq!(
+            stream.max_deleted_id.unwrap().to_string(),
+            "1700000000001-0"
+        );
+        assert_eq!(stream.entries_added, Some(3));
+    }
+
+    #[test]
+    fn test_read_stream_list_packs_1() {
+        let mut input = stream_nodes();
+        input.push(2);
+        input.extend(length64(MASTER_MS + 2));
+        input.push(5);
+        input.push(0);
+
+        let mut cursor = Cursor::new(input);
+        let value = read_stream_list_packs(
+            &mut cursor,
+            b"mystream",
+            Some(1),
+            encoding_type::STREAM_LIST_PACKS,
+        )
+        .unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        let RdbValue::Stream { stream, expiry, .. } = value else {
+            panic!("Expected a stream, got {:?}", value);
+        };
+        assert_eq!(expiry, Some(1));
+        assert_eq!(stream.entries.len(), 2);
+        assert_eq!(stream.first_id, None);
+        assert_eq!(stream.max_deleted_id, None);
+        assert_eq!(stream.entries_added, None);
+    }
+
+    fn raw_id(ms: u64, seq: u64) -> Vec<u8> {
+        let mut out = ms.to_be_bytes().to_vec();
+        out.extend_from_slice(&seq.to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn test_read_stream_consumer_groups() {
+        let mut input = stream_nodes();
+        input.push(2);
+        input.extend(length64(MASTER_MS + 2));
+        input.push(5);
+        input.extend(length64(MASTER_MS));
+        input.push(0);
+        input.extend(length64(MASTER_MS + 1));
+        input.push(0);
+        input.push(3);
+
+        // one group with two pending entries, one owned by a consumer
+        input.push(1);
+        input.extend(blob(b"workers"));
+        input.extend(length64(MASTER_MS + 2));
+        input.push(5);
+        input.push(2);
+        input.push(2);
+        input.extend(raw_id(MASTER_MS, 0));
+        input.extend_from_slice(&1_700_000_000_100u64.to_le_bytes());
+        input.push(1);
+        input.extend(raw_id(MASTER_MS + 2, 5));
+        input.extend_from_slice(&1_700_000_000_200u64.to_le_bytes());
+        input.push(3);
+        input.push(1);
+        input.extend(blob(b"alice"));
+        input.extend_from_slice(&1_700_000_000_300u64.to_le_bytes());
+        input.extend_from_slice(&1_700_000_000_250u64.to_le_bytes());
+        input.push(1);
+        input.extend(raw_id(MASTER_MS + 2, 5));
+
+        let mut cursor = Cursor::new(input);
+        let value = read_stream_list_packs(
+            &mut cursor,
+            b"mystream",
+            None,
+            encoding_type::STREAM_LIST_PACKS_3,
+        )
+        .unwrap();
+        assert_eq!(cursor.position(), cursor.get_ref().len() as u64);
+
+        let RdbValue::Stream { stream, .. } = value else {
+            panic!("Expected a stream, got {:?}", value);
+        };
+        assert_eq!(stream.groups.len(), 1);
+        let group = &stream.groups[0];
+        assert_eq!(group.name, b"workers");
+        assert_eq!(group.last_delivered_id.to_string(), "1700000000002-5");
+        assert_eq!(group.entries_read, Some(2));
+        assert_eq!(group.pending.len(), 2);
+        assert_eq!(group.pending[1].id.to_string(), "1700000000002-5");
+        assert_eq!(group.pending[1].delivery_time, 1_700_000_000_200);
+        assert_eq!(group.pending[1].delivery_count, 3);
+        assert_eq!(group.consumers.len(), 1);
+        let consumer = &group.consumers[0];
+        assert_eq!(consumer.name, b"alice");
+        assert_eq!(consumer.seen_time, 1_700_000_000_300);
+        assert_eq!(consumer.active_time, Some(1_700_000_000_250));
+        assert_eq!(consumer.pending, vec![group.pending[1].id]);
+    }
+}
@@ -43,7 +43,7 @@ impl Filter for Simple {
         if self.databases.is_empty() {
             true
         } else {
-            self.databases.iter().any(|&x| x == db)
+            self.databases.contains(&db)
         }
     }
 
@@ -53,7 +53,7 @@ impl Filter for Simple {
         }
 
         let typ = Type::from_encoding(enc_type).unwrap();
-        self.types.iter().any(|x| *x == typ)
+        self.types.contains(&typ)
     }
 
     fn matches_key(&self, key: &[u8]) -> bool {
@@ -1,6 +1,10 @@
 #![allow(unused_must_use)]
 use super::write_str;
 use crate::formatter::Formatter;
+use crate::types::{
+    CollectionElement, CollectionKind, KeyMeta, ModuleField, ModuleId, ModuleValue, Stream,
+    StreamConsumerGroup, StreamId,
+};
 use indexmap::IndexMap;
 use rustc_serialize::json;
 use std::io;
@@ -12,9 +16,16 @@ pub struct JSON {
     out: Box<dyn Write + 'static>,
     is_first_db: bool,
     has_databases: bool,
+    has_functions: bool,
     is_first_key_in_db: bool,
     elements_in_key: u32,
     element_index: u32,
+    meta: KeyMeta,
+    // Expiry of the fields of the hash written next
+    field_expiry: IndexMap<Vec<u8>, u64>,
+    show_encoding: bool,
+    show_access_metadata: bool,
+    collection: Option<CollectionKind>,
 }
 
 impl JSON {
@@ -31,13 +42,29 @@ impl JSON {
             out,
             is_first_db: true,
             has_databases: false,
+            has_functions: false,
             is_first_key_in_db: true,
             elements_in_key: 0,
             element_index: 0,
+            meta: KeyMeta::default(),
+            field_expiry: IndexMap::new(),
+            show_encoding: false,
+            show_access_metadata: false,
+            collection: None,
         }
     }
 
-    fn start_key(&mut self, length: u32) {
+    /// Write the encoding of every key next to its value
+    pub fn show_encoding(&mut self, enabled: bool) {
+        self.show_encoding = enabled;
+    }
+
+    /// Write the LRU idle time and LFU frequency of keys storing them next to their value
+    pub fn show_access_metadata(&mut self, enabled: bool) {
+        self.show_access_metadata = enabled;
+    }
+
+    fn start_key(&mut self, key: &[u8], length: u32) {
         if !self.is_first_key_in_db {
             write_str(&mut self.out, ",");
         }
@@ -45,9 +72,55 @@ impl JSON {
         self.is_first_key_in_db = false;
         self.elements_in_key = length;
         self.element_index = 0;
+
+        self.write_key(key);
+        write_str(&mut self.out, ":");
+        // Keys with metadata are written as an object holding the value
+        if self.has_meta() {
+            write_str(&mut self.out, "{\"value\":");
+        }
     }
 
-    fn end_key(&mut self) {}
+    fn end_key(&mut self) {
+        if self.has_meta() {
+            if self.show_access_metadata {
+                if let Some(idle) = self.meta.idle {
+                    write_str(&mut self.out, &format!(",\"idle\":{}", idle));
+                }
+                if let Some(freq) = self.meta.freq {
+                    write_str(&mut self.out, &format!(",\"freq\":{}", freq));
+                }
+            }
+            if self.show_encoding {
+                let encoding = self.meta.encoding;
+                write_str(
+                    &mut self.out,
+                    &format!(",\"encoding\":\"{}\"", encoding.name()),
+                );
+                if let Some(size) = encoding.size() {
+                    write_str(&mut self.out, &format!(",\"encoding_size\":{}", size));
+                }
+            }
+            write_str(&mut self.out, "}");
+        }
+        self.meta = KeyMeta::default();
+    }
+
+    fn has_meta(&self) -> bool {
+        self.show_encoding
+            || (self.show_access_metadata && (self.meta.idle.is_some() || self.meta.freq.is_some()))
+    }
+
+    // Function libraries are stored before any database and are written
+    // as a leading `{"functions":[...]}` section
+    fn end_functions(&mut self) -> bool {
+        if self.has_functions {
+            write_str(&mut self.out, "]}");
+            self.has_functions = false;
+            return true;
+        }
+        false
+    }
 
     fn write_comma(&mut self) {
         if self.element_index > 0 {
@@ -62,6 +135,76 @@ impl JSON {
     fn write_value(&mut self, value: &[u8]) {
         self.out.write_all(encode_to_ascii(value).as_bytes());
     }
+    fn write_module_field(&mut self, field: &ModuleField) {
+        match field {
+            ModuleField::SInt(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::UInt(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::Float(val) => self.write_float(*val as f64),
+            ModuleField::Double(val) => self.write_float(*val),
+            ModuleField::String(val) => self.write_value(val),
+        }
+    }
+    fn write_float(&mut self, val: f64) {
+        // JSON has no representation for NaN and infinity
+        if val.is_finite() {
+            write_str(&mut self.out, &val.to_string());
+        } else {
+            write_str(&mut self.out, &format!("\"{}\"", val));
+        }
+    }
+    fn write_stream_id(&mut self, name: &str, id: &StreamId) {
+        write_str(&mut self.out, &format!(",\"{}\":\"{}\"", name, id));
+    }
+    fn write_stream_group(&mut self, group: &StreamConsumerGroup) {
+        write_str(&mut self.out, "{\"name\":");
+        self.write_value(&group.name);
+        self.write_stream_id("last_id", &group.last_delivered_id);
+        // An entries-read counter of -1 marks it as unknown
+        if let Some(entries_read) = group.entries_read.filter(|&n| n != u64::MAX) {
+            write_str(
+                &mut self.out,
+                &format!(",\"entries_read\":{}", entries_read),
+            );
+        }
+        write_str(&mut self.out, ",\"pending\":[");
+        for (i, nack) in group.pending.iter().enumerate() {
+            if i > 0 {
+                write_str(&mut self.out, ",");
+            }
+            write_str(
+                &mut self.out,
+                &format!(
+                    "{{\"id\":\"{}\",\"delivery_time\":{},\"delivery_count\":{}}}",
+                    nack.id, nack.delivery_time, nack.delivery_count
+                ),
+            );
+        }
+        write_str(&mut self.out, "],\"consumers\":[");
+        for (i, consumer) in group.consumers.iter().enumerate() {
+            if i > 0 {
+                write_str(&mut self.out, ",");
+            }
+            write_str(&mut self.out, "{\"name\":");
+            self.write_value(&consumer.name);
+            write_str(
+                &mut self.out,
+                &format!(",\"seen_time\":{}", consumer.seen_time),
+            );
+            if let Some(active_time) = consumer.active_time {
+                write_str(&mut self.out, &format!(",\"active_time\":{}", active_time));
+            }
+            let pending: Vec<String> = consumer
+                .pending
+                .iter()
+                .map(|id| format!("\"{}\"", id))
+                .collect();
+            write_str(
+                &mut self.out,
+                &format!(",\"pending\":[{}]}}", pending.join(",")),
+            );
+        }
+        write_str(&mut self.out, "]}");
+    }
 }
 
 fn encode_to_ascii(value: &[u8]) -> String {
@@ -93,12 +236,15 @@ impl Formatter for JSON {
         if self.has_databases {
             write_str(&mut self.out, "}");
         }
+        self.end_functions();
         write_str(&mut self.out, "]\n");
     }
 
     fn start_database(&mut self, _db_number: u32) {
         if !self.is_first_db {
             write_str(&mut self.out, "},");
+        } else if self.end_functions() {
+            write_str(&mut self.out, ",");
         }
 
         write_str(&mut self.out, "{");
@@ -107,62 +253,263 @@ impl Formatter for JSON {
         self.is_first_key_in_db = true;
     }
 
+    fn function(&mut self, library_code: &[u8]) {
+        if self.has_functions {
+            write_str(&mut self.out, ",");
+        } else {
+            write_str(&mut self.out, "{\"functions\":[");
+            self.has_functions = true;
+        }
+        self.write_value(library_code);
+    }
+
+    fn key_meta(&mut self, meta: &KeyMeta) {
+        self.meta = meta.clone();
+    }
+
     fn string(&mut self, key: &[u8], value: &[u8], _expiry: &Option<u64>) {
-        self.start_key(0);
-        self.write_key(key);
-        write_str(&mut self.out, ":");
+        self.start_key(key, 0);
         self.write_value(value);
+        self.end_key();
+    }
+
+    fn hash_field_expiry(&mut self, _key: &[u8], field_expiry: &IndexMap<Vec<u8>, u64>) {
+        self.field_expiry = field_expiry.clone();
     }
 
     fn hash(&mut self, key: &[u8], values: &IndexMap<Vec<u8>, Vec<u8>>, _expiry: &Option<u64>) {
-        self.start_key(values.len() as u32);
-        self.write_key(key);
-        write_str(&mut self.out, ":{");
+        let field_expiry = std::mem::take(&mut self.field_expiry);
+        self.start_key(key, values.len() as u32);
+        write_str(&mut self.out, "{");
         for (field, value) in values {
             self.write_comma();
             self.write_key(field);
             write_str(&mut self.out, ":");
-            self.write_value(value);
+            // Hashes with field TTLs write every field as an object
+            if field_expiry.is_empty() {
+                self.write_value(value);
+            } else {
+                write_str(&mut self.out, "{\"value\":");
+                self.write_value(value);
+                if let Some(expires_at) = field_expiry.get(field) {
+                    write_str(&mut self.out, &format!(",\"expires_at\":{}", expires_at));
+                }
+                write_str(&mut self.out, "}");
+            }
         }
-        self.end_key();
         write_str(&mut self.out, "}");
+        self.end_key();
     }
 
     fn set(&mut self, key: &[u8], values: &[Vec<u8>], _expiry: &Option<u64>) {
-        self.start_key(values.len() as u32);
-        self.write_key(key);
-        write_str(&mut self.out, ":[");
+        self.start_key(key, values.len() as u32);
+        write_str(&mut self.out, "[");
         for value in values {
             self.write_comma();
             self.write_value(value);
         }
-        self.end_key();
         write_str(&mut self.out, "]");
+        self.end_key();
     }
 
     fn list(&mut self, key: &[u8], values: &[Vec<u8>], _expiry: &Option<u64>) {
-        self.start_key(values.len() as u32);
-        self.write_key(key);
-        write_str(&mut self.out, ":[");
+        self.start_key(key, values.len() as u32);
+        write_str(&mut self.out, "[");
         for value in values {
             self.write_comma();
             self.write_value(value);
         }
-        self.end_key();
         write_str(&mut self.out, "]");
+        self.end_key();
     }
 
     fn sorted_set(&mut self, key: &[u8], values: &[(f64, Vec<u8>)], _expiry: &Option<u64>) {
-        self.start_key(values.len() as u32);
-        self.write_key(key);
-        write_str(&mut self.out, ":{");
+        self.start_key(key, values.len() as u32);
+        write_str(&mut self.out, "{");
         for (score, member) in values {
             self.write_comma();
             self.write_key(member);
             write_str(&mut self.out, ":");
             self.write_value(score.to_string().as_bytes());
         }
+        write_str(&mut self.out, "}");
         self.end_key();
+    }
+
+    fn start_collection(
+        &mut self,
+        key: &[u8],
+        kind: CollectionKind,
+        length: Option<u64>,
+        _expiry: &Option<u64>,
+    ) {
+        self.start_key(key, length.unwrap_or(0) as u32);
+        match kind {
+            CollectionKind::List | CollectionKind::Set => write_str(&mut self.out, "["),
+            CollectionKind::SortedSet | CollectionKind::Hash { .. } => {
+                write_str(&mut self.out, "{")
+            }
+        }
+        self.collection = Some(kind);
+    }
+
+    fn element(&mut self, element: &CollectionElement) {
+        self.write_comma();
+        match element {
+            CollectionElement::Member(value) => self.write_value(value),
+            CollectionElement::Field {
+                field,
+                value,
+                expires_at,
+            } => {
+                self.write_key(field);
+                write_str(&mut self.out, ":");
+                // Fields of hashes with field TTLs are written as objects
+                if self.collection != Some(CollectionKind::Hash { field_expiry: true }) {
+                    self.write_value(value);
+                    return;
+                }
+                write_str(&mut self.out, "{\"value\":");
+                self.write_value(value);
+                if let Some(expires_at) = expires_at {
+                    write_str(&mut self.out, &format!(",\"expires_at\":{}", expires_at));
+                }
+                write_str(&mut self.out, "}");
+            }
+            CollectionElement::Scored { score, member } => {
+                self.write_key(member);
+                write_str(&mut self.out, ":");
+                self.write_value(score.to_string().as_bytes());
+            }
+        }
+    }
+
+    fn end_collection(&mut self) {
+        match self.collection.take() {
+            Some(CollectionKind::List | CollectionKind::Set) => write_str(&mut self.out, "]"),
+            Some(_) => write_str(&mut self.out, "}"),
+            None => return,
+        }
+        self.end_key();
+    }
+
+    fn stream(&mut self, key: &[u8], stream: &Stream, _expiry: &Option<u64>) {
+        self.start_key(key, stream.entries.len() as u32);
+        write_str(&mut self.out, "{\"entries\":[");
+        for entry in &stream.entries {
+            self.write_comma();
+            write_str(
+                &mut self.out,
+                &format!("{{\"id\":\"{}\",\"fields\":{{", entry.id),
+            );
+            for (i, (field, value)) in entry.fields.iter().enumerate() {
+                if i > 0 {
+                    write_str(&mut self.out, ",");
+                }
+                self.write_key(field);
+                write_str(&mut self.out, ":");
+                self.write_value(value);
+            }
+            write_str(&mut self.out, "}}");
+        }
+        write_str(&mut self.out, "]");
+        write_str(&mut self.out, &format!(",\"length\":{}", stream.length));
+        self.write_stream_id("last_id", &stream.last_id);
+        if let Some(first_id) = &stream.first_id {
+            self.write_stream_id("first_id", first_id);
+        }
+        if let Some(max_deleted_id) = &stream.max_deleted_id {
+            self.write_stream_id("max_deleted_id", max_deleted_id);
+        }
+        if let Some(entries_added) = stream.entries_added {
+            write_str(
+                &mut self.out,
+                &format!(",\"entries_added\":{}", entries_added),
+            );
+        }
+        // Streams without consumer groups are left without a "groups" field
+        if !stream.groups.is_empty() {
+            write_str(&mut self.out, ",\"groups\":[");
+            for (i, group) in stream.groups.iter().enumerate() {
+                if i > 0 {
+                    write_str(&mut self.out, ",");
+                }
+                self.write_stream_group(group);
+            }
+            write_str(&mut self.out, "]");
+        }
+        write_str(&mut self.out, "}");
+        self.end_key();
+    }
+
+    fn module(
+        &mut self,
+        key: &[u8],
+        module: &ModuleId,
+        value: &ModuleValue,
+        _expiry: &Option<u64>,
+    ) {
+        self.start_key(key, 0);
+        write_str(&mut self.out, "{\"module\":");
+        self.write_value(module.name.as_bytes());
+        write_str(
+            &mut self.out,
+            &format!(",\"version\":{},\"value\":", module.version),
+        );
+        match value {
+            ModuleValue::Skipped => write_str(&mut self.out, "null"),
+            ModuleValue::Raw(fields) => {
+                write_str(&mut self.out, "[");
+                for field in fields {
+                    self.write_comma();
+                    self.write_module_field(field);
+                }
+                write_str(&mut self.out, "]");
+            }
+            ModuleValue::Custom(properties) => {
+                write_str(&mut self.out, "{");
+                for (name, field) in properties {
+                    self.write_comma();
+                    self.write_key(name.as_bytes());
+                    write_str(&mut self.out, ":");
+                    self.write_module_field(field);
+                }
+                write_str(&mut self.out, "}");
+            }
+            ModuleValue::Json(document) => write_str(&mut self.out, document),
+            summary => {
+                write_str(&mut self.out, "{");
+                for (name, field) in summary.summary() {
+                    self.write_comma();
+                    write_str(&mut self.out, &format!("\"{}\":", name));
+                    self.write_module_field(&field);
+                }
+                if let ModuleValue::TopK(topk) = summary {
+                    write_str(&mut self.out, ",\"items\":[");
+                    for (i, item) in topk.items.iter().enumerate() {
+                        if i > 0 {
+                            write_str(&mut self.out, ",");
+                        }
+                        self.write_value(item);
+                    }
+                    write_str(&mut self.out, "]");
+                }
+                if let ModuleValue::TimeSeries(series) = summary {
+                    write_str(&mut self.out, ",\"labels\":{");
+                    for (i, (name, value)) in series.labels.iter().enumerate() {
+                        if i > 0 {
+                            write_str(&mut self.out, ",");
+                        }
+                        self.write_key(name);
+                        write_str(&mut self.out, ":");
+                        self.write_value(value);
+                    }
+                    write_str(&mut self.out, "}");
+                }
+                write_str(&mut self.out, "}");
+            }
+        }
         write_str(&mut self.out, "}");
+        self.end_key();
     }
 }
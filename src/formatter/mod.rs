@@ -6,13 +6,18 @@ pub use self::json::JSON;
 pub use self::nil::Nil;
 pub use self::plain::Plain;
 pub use self::protocol::Protocol;
+pub use self::stats::Stats;
 
-use super::types::RdbValue;
+use super::types::{
+    CollectionElement, CollectionKind, KeyMeta, LossSummary, ModuleField, ModuleId, ModuleValue,
+    RdbError, RdbValue, Stream,
+};
 
 pub mod json;
 pub mod nil;
 pub mod plain;
 pub mod protocol;
+pub mod stats;
 
 pub fn write_str<W: Write>(out: &mut W, data: &str) {
     out.write_all(data.as_bytes()).unwrap();
@@ -29,9 +34,19 @@ pub trait Formatter {
 
     fn resizedb(&mut self, db_size: u32, expires_size: u32) {}
     fn aux_field(&mut self, key: &[u8], value: &[u8]) {}
+    fn module_aux(&mut self, module: &ModuleId, when: u64, payload: &[ModuleField]) {}
+    fn function(&mut self, library_code: &[u8]) {}
+    fn slot_info(&mut self, slot_id: u64, slot_size: u64, expires_slot_size: u64) {}
+
+    /// Called with the metadata of a key right before the key itself
+    fn key_meta(&mut self, meta: &KeyMeta) {}
 
     fn string(&mut self, key: &[u8], value: &[u8], expiry: &Option<u64>) {}
 
+    /// Called with the expiry of the fields of a hash having field TTLs
+    /// right before `hash` for the hash itself
+    fn hash_field_expiry(&mut self, key: &[u8], field_expiry: &IndexMap<Vec<u8>, u64>) {}
+
     fn hash(&mut self, key: &[u8], values: &IndexMap<Vec<u8>, Vec<u8>>, expiry: &Option<u64>) {}
 
     fn set(&mut self, key: &[u8], values: &[Vec<u8>], expiry: &Option<u64>) {}
@@ -40,12 +55,44 @@ pub trait Formatter {
 
     fn sorted_set(&mut self, key: &[u8], values: &[(f64, Vec<u8>)], expiry: &Option<u64>) {}
 
+    fn stream(&mut self, key: &[u8], stream: &Stream, expiry: &Option<u64>) {}
+
+    fn module(&mut self, key: &[u8], module: &ModuleId, value: &ModuleValue, expiry: &Option<u64>) {
+    }
+
+    /// Called instead of `hash`, `set`, `list` or `sorted_set` when elements
+    /// are streamed, followed by `element` for every element of the key and
+    /// `end_collection`
+    fn start_collection(
+        &mut self,
+        key: &[u8],
+        kind: CollectionKind,
+        length: Option<u64>,
+        expiry: &Option<u64>,
+    ) {
+    }
+
+    fn element(&mut self, element: &CollectionElement) {}
+
+    fn end_collection(&mut self) {}
+
+    /// Called for a key that failed to decode when corrupt keys are skipped
+    fn skipped_key(&mut self, error: &RdbError) {}
+
+    /// Called before `end_rdb` when corrupt keys are skipped or the dump is truncated
+    fn loss_summary(&mut self, summary: &LossSummary) {}
+
     fn format(&mut self, value: &RdbValue) -> std::io::Result<()> {
+        if let Some(meta) = value.meta() {
+            self.key_meta(meta);
+        }
+
         match value {
             RdbValue::Set {
                 key,
                 members,
                 expiry,
+                ..
             } => {
                 self.set(key, members, expiry);
                 Ok(())
@@ -53,8 +100,13 @@ pub trait Formatter {
             RdbValue::Hash {
                 key,
                 values,
+                field_expiry,
                 expiry,
+                ..
             } => {
+                if !field_expiry.is_empty() {
+                    self.hash_field_expiry(key, field_expiry);
+                }
                 self.hash(key, values, expiry);
                 Ok(())
             }
@@ -62,6 +114,7 @@ pub trait Formatter {
                 key,
                 values,
                 expiry,
+                ..
             } => {
                 self.list(key, values, expiry);
                 Ok(())
@@ -70,14 +123,54 @@ pub trait Formatter {
                 key,
                 values,
                 expiry,
+                ..
             } => {
                 self.sorted_set(key, values, expiry);
                 Ok(())
             }
-            RdbValue::String { key, value, expiry } => {
+            RdbValue::String {
+                key, value, expiry, ..
+            } => {
                 self.string(key, value, expiry);
                 Ok(())
             }
+            RdbValue::Stream {
+                key,
+                stream,
+                expiry,
+                ..
+            } => {
+                self.stream(key, stream, expiry);
+                Ok(())
+            }
+            RdbValue::Module {
+                key,
+                module,
+                value,
+                expiry,
+                ..
+            } => {
+                self.module(key, module, value, expiry);
+                Ok(())
+            }
+            RdbValue::StartCollection {
+                key,
+                kind,
+                length,
+                expiry,
+                ..
+            } => {
+                self.start_collection(key, *kind, *length, expiry);
+                Ok(())
+            }
+            RdbValue::Element(element) => {
+                self.element(element);
+                Ok(())
+            }
+            RdbValue::EndCollection => {
+                self.end_collection();
+                Ok(())
+            }
             RdbValue::SelectDb(db_number) => {
                 self.start_database(*db_number);
                 Ok(())
@@ -90,6 +183,26 @@ pub trait Formatter {
                 self.aux_field(key, value);
                 Ok(())
             }
+            RdbValue::ModuleAux {
+                module,
+                when,
+                payload,
+            } => {
+                self.module_aux(module, *when, payload);
+                Ok(())
+            }
+            RdbValue::SlotInfo {
+                slot_id,
+                slot_size,
+                expires_slot_size,
+            } => {
+                self.slot_info(*slot_id, *slot_size, *expires_slot_size);
+                Ok(())
+            }
+            RdbValue::Function { library_code } => {
+                self.function(library_code);
+                Ok(())
+            }
             RdbValue::Checksum(checksum) => {
                 self.checksum(checksum);
                 Ok(())
@@ -103,6 +216,7 @@ pub enum FormatterType {
     Plain(Plain),
     Nil(Nil),
     Protocol(Protocol),
+    Stats(Stats),
 }
 
 impl Formatter for FormatterType {
@@ -112,6 +226,7 @@ impl Formatter for FormatterType {
             Self::Plain(f) => f.format(value),
             Self::Nil(f) => f.format(value),
             Self::Protocol(f) => f.format(value),
+            Self::Stats(f) => f.format(value),
         }
     }
 
@@ -121,6 +236,7 @@ impl Formatter for FormatterType {
             Self::Plain(f) => f.start_rdb(),
             Self::Nil(f) => f.start_rdb(),
             Self::Protocol(f) => f.start_rdb(),
+            Self::Stats(f) => f.start_rdb(),
         }
     }
 
@@ -130,6 +246,27 @@ impl Formatter for FormatterType {
             Self::Plain(f) => f.end_rdb(),
             Self::Nil(f) => f.end_rdb(),
             Self::Protocol(f) => f.end_rdb(),
+            Self::Stats(f) => f.end_rdb(),
+        }
+    }
+
+    fn skipped_key(&mut self, error: &RdbError) {
+        match self {
+            Self::Json(f) => f.skipped_key(error),
+            Self::Plain(f) => f.skipped_key(error),
+            Self::Nil(f) => f.skipped_key(error),
+            Self::Protocol(f) => f.skipped_key(error),
+            Self::Stats(f) => f.skipped_key(error),
+        }
+    }
+
+    fn loss_summary(&mut self, summary: &LossSummary) {
+        match self {
+            Self::Json(f) => f.loss_summary(summary),
+            Self::Plain(f) => f.loss_summary(summary),
+            Self::Nil(f) => f.loss_summary(summary),
+            Self::Protocol(f) => f.loss_summary(summary),
+            Self::Stats(f) => f.loss_summary(summary),
         }
     }
 }
@@ -1,6 +1,10 @@
 #![allow(unused_must_use)]
 use super::write_str;
 use crate::formatter::Formatter;
+use crate::types::{
+    CollectionElement, CollectionKind, KeyMeta, ModuleField, ModuleId, ModuleValue, Stream,
+    StreamConsumerGroup, StreamId,
+};
 use indexmap::IndexMap;
 use rustc_serialize::hex::ToHex;
 use std::io;
@@ -10,6 +14,12 @@ use std::path::PathBuf;
 pub struct Plain {
     out: Box<dyn Write + 'static>,
     dbnum: u32,
+    meta: KeyMeta,
+    show_encoding: bool,
+    show_access_metadata: bool,
+    // Key and type of the collection being streamed, and the index of its next element
+    collection: Option<(Vec<u8>, CollectionKind)>,
+    element_index: usize,
 }
 
 impl Plain {
@@ -22,13 +32,59 @@ impl Plain {
             None => Box::new(io::stdout()),
         };
 
-        Plain { out, dbnum: 0 }
+        Plain {
+            out,
+            dbnum: 0,
+            meta: KeyMeta::default(),
+            show_encoding: false,
+            show_access_metadata: false,
+            collection: None,
+            element_index: 0,
+        }
+    }
+
+    /// Write the encoding of every key on a line in front of it
+    pub fn show_encoding(&mut self, enabled: bool) {
+        self.show_encoding = enabled;
+    }
+
+    /// Write the LRU idle time and LFU frequency of keys storing them on a line in front of them
+    pub fn show_access_metadata(&mut self, enabled: bool) {
+        self.show_access_metadata = enabled;
     }
 
     fn write_line_start(&mut self) {
         write_str(&mut self.out, &format!("db={} ", self.dbnum));
     }
 
+    // Key metadata is written on its own line in front of the key
+    fn key_meta_line(&mut self, key: &[u8]) {
+        let meta = std::mem::take(&mut self.meta);
+        let mut fields = Vec::new();
+        if self.show_access_metadata {
+            if let Some(idle) = meta.idle {
+                fields.push(format!("idle={}", idle));
+            }
+            if let Some(freq) = meta.freq {
+                fields.push(format!("freq={}", freq));
+            }
+        }
+        if self.show_encoding {
+            fields.push(format!("encoding={}", meta.encoding.name()));
+            if let Some(size) = meta.encoding.size() {
+                fields.push(format!("encoding_size={}", size));
+            }
+        }
+        if fields.is_empty() {
+            return;
+        }
+
+        self.write_line_start();
+        self.out.write_all(key);
+        write_str(&mut self.out, &format!(" ({})\n", fields.join(", ")));
+        self.out.flush();
+    }
+
     fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) {
         self.write_line_start();
 
@@ -74,10 +130,95 @@ impl Plain {
         write_str(&mut self.out, "}\n");
         self.out.flush();
     }
+
+    fn module_element(&mut self, key: &[u8], module: &ModuleId, name: &str, field: &ModuleField) {
+        self.write_line_start();
+
+        self.out.write_all(key);
+        write_str(&mut self.out, &format!(" <{}> {} -> ", module, name));
+        self.write_module_field(field);
+        write_str(&mut self.out, "\n");
+        self.out.flush();
+    }
+
+    fn write_module_field(&mut self, field: &ModuleField) {
+        match field {
+            ModuleField::SInt(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::UInt(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::Float(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::Double(val) => write_str(&mut self.out, &val.to_string()),
+            ModuleField::String(val) => {
+                self.out.write_all(val);
+            }
+        }
+    }
+
+    fn stream_element(&mut self, key: &[u8], id: &StreamId, field: &[u8], value: &[u8]) {
+        self.write_line_start();
+
+        self.out.write_all(key);
+        write_str(&mut self.out, &format!("[{}]", id));
+        write_str(&mut self.out, " . ");
+        self.out.write_all(field);
+        write_str(&mut self.out, " -> ");
+        self.out.write_all(value);
+        write_str(&mut self.out, "\n");
+        self.out.flush();
+    }
+
+    // A line for the group, then one for each pending entry and each consumer
+    fn stream_group(&mut self, key: &[u8], group: &StreamConsumerGroup) {
+        let mut fields = vec![format!("last_id={}", group.last_delivered_id)];
+        // An entries-read counter of -1 marks it as unknown
+        if let Some(entries_read) = group.entries_read.filter(|&n| n != u64::MAX) {
+            fields.push(format!("entries_read={}", entries_read));
+        }
+        self.stream_group_start(key, group);
+        write_str(&mut self.out, &format!(" ({})\n", fields.join(", ")));
+
+        for nack in &group.pending {
+            self.stream_group_start(key, group);
+            write_str(
+                &mut self.out,
+                &format!(
+                    "[{}] -> (delivery_time={}, delivery_count={})\n",
+                    nack.id, nack.delivery_time, nack.delivery_count
+                ),
+            );
+        }
+
+        for consumer in &group.consumers {
+            let mut fields = vec![format!("seen_time={}", consumer.seen_time)];
+            if let Some(active_time) = consumer.active_time {
+                fields.push(format!("active_time={}", active_time));
+            }
+            let pending: Vec<String> = consumer.pending.iter().map(|id| id.to_string()).collect();
+            fields.push(format!("pending=[{}]", pending.join(" ")));
+
+            self.stream_group_start(key, group);
+            write_str(&mut self.out, " . ");
+            self.out.write_all(&consumer.name);
+            write_str(&mut self.out, &format!(" ({})\n", fields.join(", ")));
+        }
+        self.out.flush();
+    }
+
+    fn stream_group_start(&mut self, key: &[u8], group: &StreamConsumerGroup) {
+        self.write_line_start();
+
+        self.out.write_all(key);
+        write_str(&mut self.out, " group ");
+        self.out.write_all(&group.name);
+    }
 }
 
 impl Formatter for Plain {
+    fn key_meta(&mut self, meta: &KeyMeta) {
+        self.meta = meta.clone();
+    }
+
     fn string(&mut self, key: &[u8], value: &[u8], _expiry: &Option<u64>) {
+        self.key_meta_line(key);
         self.write_line_start();
         self.out.write_all(key);
         write_str(&mut self.out, " -> ");
@@ -87,29 +228,132 @@ impl Formatter for Plain {
     }
 
     fn hash(&mut self, key: &[u8], values: &IndexMap<Vec<u8>, Vec<u8>>, _expiry: &Option<u64>) {
+        self.key_meta_line(key);
         for (field, value) in values {
             self.hash_element(key, field, value);
         }
     }
 
     fn set(&mut self, key: &[u8], values: &[Vec<u8>], _expiry: &Option<u64>) {
+        self.key_meta_line(key);
         for value in values {
             self.set_element(key, value);
         }
     }
 
     fn list(&mut self, key: &[u8], values: &[Vec<u8>], _expiry: &Option<u64>) {
+        self.key_meta_line(key);
         for (i, value) in values.iter().enumerate() {
             self.list_element(i, key, value);
         }
     }
 
     fn sorted_set(&mut self, key: &[u8], values: &[(f64, Vec<u8>)], _expiry: &Option<u64>) {
+        self.key_meta_line(key);
         for (i, (score, member)) in values.iter().enumerate() {
             self.sorted_set_element(i, key, *score, member);
         }
     }
 
+    fn start_collection(
+        &mut self,
+        key: &[u8],
+        kind: CollectionKind,
+        _length: Option<u64>,
+        _expiry: &Option<u64>,
+    ) {
+        self.key_meta_line(key);
+        self.collection = Some((key.to_vec(), kind));
+        self.element_index = 0;
+    }
+
+    fn element(&mut self, element: &CollectionElement) {
+        let Some((key, kind)) = self.collection.take() else {
+            return;
+        };
+        let index = self.element_index;
+        match element {
+            CollectionElement::Member(value) if kind == CollectionKind::Set => {
+                self.set_element(&key, value)
+            }
+            CollectionElement::Member(value) => self.list_element(index, &key, value),
+            CollectionElement::Field { field, value, .. } => self.hash_element(&key, field, value),
+            CollectionElement::Scored { score, member } => {
+                self.sorted_set_element(index, &key, *score, member)
+            }
+        }
+        self.element_index += 1;
+        self.collection = Some((key, kind));
+    }
+
+    fn end_collection(&mut self) {
+        self.collection = None;
+    }
+
+    fn stream(&mut self, key: &[u8], stream: &Stream, _expiry: &Option<u64>) {
+        self.key_meta_line(key);
+        for entry in &stream.entries {
+            for (field, value) in &entry.fields {
+                self.stream_element(key, &entry.id, field, value);
+            }
+        }
+        for group in &stream.groups {
+            self.stream_group(key, group);
+        }
+    }
+
+    fn module(
+        &mut self,
+        key: &[u8],
+        module: &ModuleId,
+        value: &ModuleValue,
+        _expiry: &Option<u64>,
+    ) {
+        self.key_meta_line(key);
+        match value {
+            ModuleValue::Skipped => {
+                self.write_line_start();
+                self.out.write_all(key);
+                write_str(&mut self.out, &format!(" <{}>\n", module));
+                self.out.flush();
+            }
+            ModuleValue::Raw(fields) => {
+                for (i, field) in fields.iter().enumerate() {
+                    self.module_element(key, module, &format!("[{}]", i), field);
+                }
+            }
+            ModuleValue::Custom(properties) => {
+                for (name, field) in properties {
+                    self.module_element(key, module, name, field);
+                }
+            }
+            ModuleValue::Json(document) => {
+                self.write_line_start();
+                self.out.write_all(key);
+                write_str(&mut self.out, &format!(" <{}> -> {}\n", module, document));
+                self.out.flush();
+            }
+            summary => {
+                for (name, field) in summary.summary() {
+                    self.module_element(key, module, name, &field);
+                }
+                if let ModuleValue::TopK(topk) = summary {
+                    for (i, item) in topk.items.iter().enumerate() {
+                        let field = ModuleField::String(item.clone());
+                        self.module_element(key, module, &format!("items[{}]", i), &field);
+                    }
+                }
+                if let ModuleValue::TimeSeries(series) = summary {
+                    for (name, value) in &series.labels {
+                        let name = format!("labels.{}", String::from_utf8_lossy(name));
+                        let field = ModuleField::String(value.clone());
+                        self.module_element(key, module, &name, &field);
+                    }
+                }
+            }
+        }
+    }
+
     fn checksum(&mut self, checksum: &[u8]) {
         if !checksum.is_empty() {
             write_str(&mut self.out, "checksum ");
@@ -130,4 +374,35 @@ impl Formatter for Plain {
         write_str(&mut self.out, "\n");
         self.out.flush();
     }
+
+    fn slot_info(&mut self, slot_id: u64, slot_size: u64, expires_slot_size: u64) {
+        write_str(
+            &mut self.out,
+            &format!(
+                "slot {} -> keys={} expires={}\n",
+                slot_id, slot_size, expires_slot_size
+            ),
+        );
+        self.out.flush();
+    }
+
+    fn function(&mut self, library_code: &[u8]) {
+        write_str(&mut self.out, "function -> ");
+        self.out.write_all(library_code);
+        write_str(&mut self.out, "\n");
+        self.out.flush();
+    }
+
+    fn module_aux(&mut self, module: &ModuleId, when: u64, payload: &[ModuleField]) {
+        write_str(
+            &mut self.out,
+            &format!("module-aux <{}> when={}", module, when),
+        );
+        for field in payload {
+            write_str(&mut self.out, " ");
+            self.write_module_field(field);
+        }
+        write_str(&mut self.out, "\n");
+        self.out.flush();
+    }
 }
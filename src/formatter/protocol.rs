@@ -2,14 +2,77 @@
 use indexmap::IndexMap;
 
 use super::write_str;
+use crate::constants::{constant, encoding_type};
+use crate::crc64::crc64;
 use crate::formatter::Formatter;
+use crate::types::{
+    CollectionElement, CollectionKind, KeyMeta, ModuleId, ModuleValue, Stream, StreamConsumerGroup,
+    StreamEntry, StreamId, StreamPendingEntry,
+};
+use std::collections::HashMap;
 use std::io;
 use std::io::Write;
 use std::path::PathBuf;
 
+// RDB version written into DUMP payloads, the one of Redis 5.0, the first
+// server accepting RESTORE with IDLETIME/FREQ, so that every such server
+// accepts the payloads
+const DUMP_PAYLOAD_VERSION: u16 = 9;
+
 pub struct Protocol {
     out: Box<dyn Write + 'static>,
     last_expiry: Option<u64>,
+    restore_access_metadata: bool,
+    meta: KeyMeta,
+    // Expiry of the fields of the hash written next
+    field_expiry: IndexMap<Vec<u8>, u64>,
+    // Key and type of the collection being streamed
+    collection: Option<(Vec<u8>, CollectionKind)>,
+}
+
+// Serialized value in the format of the DUMP command, as accepted by RESTORE
+struct DumpPayload {
+    buf: Vec<u8>,
+}
+
+impl DumpPayload {
+    fn new(value_type: u8) -> DumpPayload {
+        DumpPayload {
+            buf: vec![value_type],
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let length = length as u64;
+        if length < 1 << 6 {
+            self.buf.push(length as u8);
+        } else if length < 1 << 14 {
+            self.buf.push(0x40 | (length >> 8) as u8);
+            self.buf.push(length as u8);
+        } else if length <= u32::MAX as u64 {
+            self.buf.push(constant::RDB_32BITLEN);
+            self.buf.extend((length as u32).to_be_bytes());
+        } else {
+            self.buf.push(constant::RDB_64BITLEN);
+            self.buf.extend(length.to_be_bytes());
+        }
+    }
+
+    fn string(&mut self, value: &[u8]) {
+        self.length(value.len());
+        self.buf.extend(value);
+    }
+
+    fn double(&mut self, value: f64) {
+        self.buf.extend(value.to_le_bytes());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.extend(DUMP_PAYLOAD_VERSION.to_le_bytes());
+        let checksum = crc64(&self.buf);
+        self.buf.extend(checksum.to_le_bytes());
+        self.buf
+    }
 }
 
 impl Protocol {
@@ -25,8 +88,21 @@ impl Protocol {
         Protocol {
             out,
             last_expiry: None,
+            restore_access_metadata: false,
+            meta: KeyMeta::default(),
+            field_expiry: IndexMap::new(),
+            collection: None,
         }
     }
+
+    /// Restore keys with an LRU idle time or LFU frequency through `RESTORE`,
+    /// so the target server keeps their access metadata.
+    ///
+    /// Hashes with field TTLs, streams, module values and collections
+    /// streamed element by element are still written with regular commands.
+    pub fn restore_access_metadata(&mut self, enabled: bool) {
+        self.restore_access_metadata = enabled;
+    }
 }
 
 impl Protocol {
@@ -43,6 +119,42 @@ impl Protocol {
         }
     }
 
+    // Metadata of the current key, if it is to be restored with RESTORE
+    fn access_metadata(&mut self) -> Option<KeyMeta> {
+        let meta = std::mem::take(&mut self.meta);
+        if self.restore_access_metadata && (meta.idle.is_some() || meta.freq.is_some()) {
+            Some(meta)
+        } else {
+            None
+        }
+    }
+
+    fn restore(&mut self, key: &[u8], payload: DumpPayload, expiry: &Option<u64>, meta: &KeyMeta) {
+        let payload = payload.finish();
+        let ttl = expiry.unwrap_or(0).to_string();
+        let mut args = vec![
+            "RESTORE".as_bytes(),
+            key,
+            ttl.as_bytes(),
+            &payload,
+            "REPLACE".as_bytes(),
+        ];
+        if expiry.is_some() {
+            args.push("ABSTTL".as_bytes());
+        }
+        // RESTORE refuses IDLETIME and FREQ together, dumps only store one of them
+        let idle = meta.idle.map(|idle| idle.to_string());
+        let freq = meta.freq.map(|freq| freq.to_string());
+        if let Some(idle) = &idle {
+            args.push("IDLETIME".as_bytes());
+            args.push(idle.as_bytes());
+        } else if let Some(freq) = &freq {
+            args.push("FREQ".as_bytes());
+            args.push(freq.as_bytes());
+        }
+        self.emit(args);
+    }
+
     fn pre_expire(&mut self, expiry: &Option<u64>) {
         self.last_expiry = *expiry;
     }
@@ -70,6 +182,27 @@ impl Protocol {
     fn hash_element(&mut self, key: &[u8], field: &[u8], value: &[u8]) {
         self.emit(vec!["HSET".as_bytes(), key, field, value]);
     }
+    fn write_field_expiry(&mut self, key: &[u8], field_expiry: &IndexMap<Vec<u8>, u64>) {
+        // Fields sharing an expiry are set with a single command
+        let mut fields_by_expiry: IndexMap<u64, Vec<&[u8]>> = IndexMap::new();
+        for (field, expires_at) in field_expiry {
+            fields_by_expiry.entry(*expires_at).or_default().push(field);
+        }
+
+        for (expires_at, fields) in fields_by_expiry {
+            let expires_at = expires_at.to_string();
+            let count = fields.len().to_string();
+            let mut args = vec![
+                "HPEXPIREAT".as_bytes(),
+                key,
+                expires_at.as_bytes(),
+                "FIELDS".as_bytes(),
+                count.as_bytes(),
+            ];
+            args.extend(fields);
+            self.emit(args);
+        }
+    }
 
     fn start_set(&mut self, expiry: &Option<u64>) {
         self.pre_expire(expiry);
@@ -101,22 +234,176 @@ impl Protocol {
         let score = score.to_string();
         self.emit(vec!["ZADD".as_bytes(), key, score.as_bytes(), member]);
     }
+
+    fn start_stream(&mut self, expiry: &Option<u64>) {
+        self.pre_expire(expiry);
+    }
+    fn end_stream(&mut self, key: &[u8], stream: &Stream) {
+        if stream.entries.is_empty() {
+            // XSETID needs an existing key, so create the stream with a
+            // placeholder entry that is trimmed away right away
+            let id = if stream.last_id == StreamId::default() {
+                StreamId { ms: 0, seq: 1 }
+            } else {
+                stream.last_id
+            };
+            let id = id.to_string();
+            self.emit(vec![
+                "XADD".as_bytes(),
+                key,
+                "MAXLEN".as_bytes(),
+                "0".as_bytes(),
+                id.as_bytes(),
+                "".as_bytes(),
+                "".as_bytes(),
+            ]);
+        }
+
+        let last_id = stream.last_id.to_string();
+        let mut args = vec!["XSETID".as_bytes(), key, last_id.as_bytes()];
+        let entries_added = stream.entries_added.map(|n| n.to_string());
+        if let Some(entries_added) = &entries_added {
+            args.push("ENTRIESADDED".as_bytes());
+            args.push(entries_added.as_bytes());
+        }
+        let max_deleted_id = stream.max_deleted_id.map(|id| id.to_string());
+        if let Some(max_deleted_id) = &max_deleted_id {
+            args.push("MAXDELETEDID".as_bytes());
+            args.push(max_deleted_id.as_bytes());
+        }
+        self.emit(args);
+
+        for group in &stream.groups {
+            self.stream_group(key, group);
+        }
+
+        self.post_expire(key);
+    }
+    fn stream_group(&mut self, key: &[u8], group: &StreamConsumerGroup) {
+        let last_delivered_id = group.last_delivered_id.to_string();
+        let mut args = vec![
+            "XGROUP".as_bytes(),
+            "CREATE".as_bytes(),
+            key,
+            &group.name,
+            last_delivered_id.as_bytes(),
+        ];
+        // An entries-read counter of -1 marks it as unknown
+        let entries_read = group
+            .entries_read
+            .filter(|&n| n != u64::MAX)
+            .map(|n| n.to_string());
+        if let Some(entries_read) = &entries_read {
+            args.push("ENTRIESREAD".as_bytes());
+            args.push(entries_read.as_bytes());
+        }
+        self.emit(args);
+
+        let pending: HashMap<StreamId, &StreamPendingEntry> =
+            group.pending.iter().map(|nack| (nack.id, nack)).collect();
+        for consumer in &group.consumers {
+            self.emit(vec![
+                "XGROUP".as_bytes(),
+                "CREATECONSUMER".as_bytes(),
+                key,
+                &group.name,
+                &consumer.name,
+            ]);
+            for id in &consumer.pending {
+                let Some(nack) = pending.get(id) else {
+                    continue;
+                };
+                let id = id.to_string();
+                let delivery_time = nack.delivery_time.to_string();
+                let delivery_count = nack.delivery_count.to_string();
+                self.emit(vec![
+                    "XCLAIM".as_bytes(),
+                    key,
+                    &group.name,
+                    &consumer.name,
+                    "0".as_bytes(),
+                    id.as_bytes(),
+                    "TIME".as_bytes(),
+                    delivery_time.as_bytes(),
+                    "RETRYCOUNT".as_bytes(),
+                    delivery_count.as_bytes(),
+                    "FORCE".as_bytes(),
+                    "JUSTID".as_bytes(),
+                ]);
+            }
+        }
+    }
+    fn stream_element(&mut self, key: &[u8], entry: &StreamEntry) {
+        let id = entry.id.to_string();
+        let mut args = vec!["XADD".as_bytes(), key, id.as_bytes()];
+        for (field, value) in &entry.fields {
+            args.push(field);
+            args.push(value);
+        }
+        self.emit(args);
+    }
 }
 
 impl Formatter for Protocol {
+    fn function(&mut self, library_code: &[u8]) {
+        self.emit(vec![
+            "FUNCTION".as_bytes(),
+            "LOAD".as_bytes(),
+            "REPLACE".as_bytes(),
+            library_code,
+        ]);
+    }
+
+    fn key_meta(&mut self, meta: &KeyMeta) {
+        self.meta = meta.clone();
+    }
+
     fn string(&mut self, key: &[u8], value: &[u8], _expiry: &Option<u64>) {
+        if let Some(meta) = self.access_metadata() {
+            let mut payload = DumpPayload::new(encoding_type::STRING);
+            payload.string(value);
+            self.restore(key, payload, _expiry, &meta);
+            return;
+        }
         self.set(key, value, _expiry);
     }
 
+    fn hash_field_expiry(&mut self, _key: &[u8], field_expiry: &IndexMap<Vec<u8>, u64>) {
+        self.field_expiry = field_expiry.clone();
+    }
+
     fn hash(&mut self, key: &[u8], values: &IndexMap<Vec<u8>, Vec<u8>>, expiry: &Option<u64>) {
+        let field_expiry = std::mem::take(&mut self.field_expiry);
+        if let Some(meta) = self.access_metadata() {
+            if field_expiry.is_empty() {
+                let mut payload = DumpPayload::new(encoding_type::HASH);
+                payload.length(values.len());
+                for (field, value) in values {
+                    payload.string(field);
+                    payload.string(value);
+                }
+                self.restore(key, payload, expiry, &meta);
+                return;
+            }
+        }
         self.start_hash(expiry);
         for (field, value) in values {
             self.hash_element(key, field, value);
         }
+        self.write_field_expiry(key, &field_expiry);
         self.end_hash(key);
     }
 
     fn set(&mut self, key: &[u8], values: &[Vec<u8>], expiry: &Option<u64>) {
+        if let Some(meta) = self.access_metadata() {
+            let mut payload = DumpPayload::new(encoding_type::SET);
+            payload.length(values.len());
+            for value in values {
+                payload.string(value);
+            }
+            self.restore(key, payload, expiry, &meta);
+            return;
+        }
         self.start_set(expiry);
         for value in values {
             self.set_element(key, value);
@@ -125,6 +412,15 @@ impl Formatter for Protocol {
     }
 
     fn list(&mut self, key: &[u8], values: &[Vec<u8>], expiry: &Option<u64>) {
+        if let Some(meta) = self.access_metadata() {
+            let mut payload = DumpPayload::new(encoding_type::LIST);
+            payload.length(values.len());
+            for value in values {
+                payload.string(value);
+            }
+            self.restore(key, payload, expiry, &meta);
+            return;
+        }
         self.start_list(expiry);
         for value in values {
             self.list_element(key, value);
@@ -133,6 +429,16 @@ impl Formatter for Protocol {
     }
 
     fn sorted_set(&mut self, key: &[u8], values: &[(f64, Vec<u8>)], expiry: &Option<u64>) {
+        if let Some(meta) = self.access_metadata() {
+            let mut payload = DumpPayload::new(encoding_type::ZSET_2);
+            payload.length(values.len());
+            for (score, member) in values {
+                payload.string(member);
+                payload.double(*score);
+            }
+            self.restore(key, payload, expiry, &meta);
+            return;
+        }
         self.start_sorted_set(expiry);
         for (score, member) in values {
             self.sorted_set_element(key, *score, member);
@@ -140,6 +446,81 @@ impl Formatter for Protocol {
         self.end_sorted_set(key);
     }
 
+    fn start_collection(
+        &mut self,
+        key: &[u8],
+        kind: CollectionKind,
+        _length: Option<u64>,
+        expiry: &Option<u64>,
+    ) {
+        // RESTORE needs the whole value at once
+        self.meta = KeyMeta::default();
+        self.pre_expire(expiry);
+        self.collection = Some((key.to_vec(), kind));
+    }
+
+    fn element(&mut self, element: &CollectionElement) {
+        let Some((key, kind)) = self.collection.take() else {
+            return;
+        };
+        match element {
+            CollectionElement::Member(value) if kind == CollectionKind::Set => {
+                self.set_element(&key, value)
+            }
+            CollectionElement::Member(value) => self.list_element(&key, value),
+            CollectionElement::Field {
+                field,
+                value,
+                expires_at,
+            } => {
+                self.hash_element(&key, field, value);
+                // Fields are not collected, so each TTL is set on its own
+                if let Some(expires_at) = expires_at {
+                    let field_expiry = IndexMap::from([(field.clone(), *expires_at)]);
+                    self.write_field_expiry(&key, &field_expiry);
+                }
+            }
+            CollectionElement::Scored { score, member } => {
+                self.sorted_set_element(&key, *score, member)
+            }
+        }
+        self.collection = Some((key, kind));
+    }
+
+    fn end_collection(&mut self) {
+        if let Some((key, _)) = self.collection.take() {
+            self.post_expire(&key);
+        }
+    }
+
+    fn stream(&mut self, key: &[u8], stream: &Stream, expiry: &Option<u64>) {
+        self.start_stream(expiry);
+        for entry in &stream.entries {
+            self.stream_element(key, entry);
+        }
+        self.end_stream(key, stream);
+    }
+
+    fn module(
+        &mut self,
+        key: &[u8],
+        _module: &ModuleId,
+        value: &ModuleValue,
+        expiry: &Option<u64>,
+    ) {
+        // Only values with a known command representation can be restored
+        if let ModuleValue::Json(document) = value {
+            self.pre_expire(expiry);
+            self.emit(vec![
+                "JSON.SET".as_bytes(),
+                key,
+                "$".as_bytes(),
+                document.as_bytes(),
+            ]);
+            self.post_expire(key);
+        }
+    }
+
     fn start_database(&mut self, db_number: u32) {
         let db = db_number.to_string();
         self.emit(vec!["SELECT".as_bytes(), db.as_bytes()])
@@ -0,0 +1,218 @@
+#![allow(unused_must_use)]
+use super::write_str;
+use crate::formatter::Formatter;
+use crate::types::{
+    CollectionKind, LossSummary, ModuleField, ModuleId, ModuleValue, RdbError, Stream,
+};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Default)]
+struct DatabaseStats {
+    keys: u64,
+    expires: u64,
+    types: BTreeMap<&'static str, u64>,
+    modules: BTreeMap<String, ModuleStats>,
+}
+
+// Summary properties of module values that add up across keys
+const SUMMED_MODULE_PROPERTIES: &[&str] = &[
+    "capacity", "items", "deletes", "filters", "bytes", "count", "chunks", "samples",
+];
+
+#[derive(Default)]
+struct ModuleStats {
+    keys: u64,
+    totals: IndexMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct SlotStats {
+    keys: u64,
+    expires: u64,
+}
+
+/// Collects key counts while parsing and prints a summary at the end.
+///
+/// Keys are counted per database and type, module values also per module
+/// type along with totals of their summaries. Dumps of cluster nodes also
+/// report the number of keys per slot, which is taken from the slot info
+/// stored in the dump instead of hashing every key.
+pub struct Stats {
+    out: Box<dyn Write + 'static>,
+    dbnum: u32,
+    databases: BTreeMap<u32, DatabaseStats>,
+    slots: BTreeMap<u64, SlotStats>,
+    functions: u64,
+    skipped_keys: u64,
+    unread_from: Option<u64>,
+}
+
+impl Stats {
+    pub fn new(file_path: Option<PathBuf>) -> Stats {
+        let out: Box<dyn Write> = match file_path {
+            Some(path) => match std::fs::File::create(path) {
+                Ok(file) => Box::new(file),
+                Err(_) => Box::new(io::stdout()),
+            },
+            None => Box::new(io::stdout()),
+        };
+
+        Stats {
+            out,
+            dbnum: 0,
+            databases: BTreeMap::new(),
+            slots: BTreeMap::new(),
+            functions: 0,
+            skipped_keys: 0,
+            unread_from: None,
+        }
+    }
+
+    fn count_key(&mut self, type_name: &'static str, expiry: &Option<u64>) {
+        let db = self.databases.entry(self.dbnum).or_default();
+        db.keys += 1;
+        if expiry.is_some() {
+            db.expires += 1;
+        }
+        *db.types.entry(type_name).or_default() += 1;
+    }
+}
+
+impl Formatter for Stats {
+    fn end_rdb(&mut self) {
+        for (dbnum, db) in &self.databases {
+            write_str(
+                &mut self.out,
+                &format!("db={} keys={} expires={}\n", dbnum, db.keys, db.expires),
+            );
+            for (type_name, keys) in &db.types {
+                write_str(
+                    &mut self.out,
+                    &format!("db={} type={} keys={}\n", dbnum, type_name, keys),
+                );
+            }
+            for (module_name, module) in &db.modules {
+                let mut line = format!("db={} module={} keys={}", dbnum, module_name, module.keys);
+                for (name, total) in &module.totals {
+                    line.push_str(&format!(" {}={}", name, total));
+                }
+                write_str(&mut self.out, &format!("{}\n", line));
+            }
+        }
+        for (slot_id, slot) in &self.slots {
+            write_str(
+                &mut self.out,
+                &format!(
+                    "slot={} keys={} expires={}\n",
+                    slot_id, slot.keys, slot.expires
+                ),
+            );
+        }
+        if self.functions > 0 {
+            write_str(&mut self.out, &format!("functions={}\n", self.functions));
+        }
+        if self.skipped_keys > 0 {
+            write_str(
+                &mut self.out,
+                &format!("skipped_keys={}\n", self.skipped_keys),
+            );
+        }
+        if let Some(offset) = self.unread_from {
+            write_str(&mut self.out, &format!("unread_from={}\n", offset));
+        }
+        self.out.flush();
+    }
+
+    fn start_database(&mut self, db_number: u32) {
+        self.dbnum = db_number;
+    }
+
+    fn slot_info(&mut self, slot_id: u64, slot_size: u64, expires_slot_size: u64) {
+        let slot = self.slots.entry(slot_id).or_default();
+        slot.keys += slot_size;
+        slot.expires += expires_slot_size;
+    }
+
+    fn function(&mut self, _library_code: &[u8]) {
+        self.functions += 1;
+    }
+
+    fn skipped_key(&mut self, _error: &RdbError) {
+        self.skipped_keys += 1;
+    }
+
+    fn loss_summary(&mut self, summary: &LossSummary) {
+        self.unread_from = match &summary.truncation {
+            Some(truncation) => Some(truncation.offset),
+            None => summary
+                .fatal_error
+                .as_ref()
+                .and_then(|err| err.location().offset),
+        };
+    }
+
+    fn string(&mut self, _key: &[u8], _value: &[u8], expiry: &Option<u64>) {
+        self.count_key("string", expiry);
+    }
+
+    fn hash(&mut self, _key: &[u8], _values: &IndexMap<Vec<u8>, Vec<u8>>, expiry: &Option<u64>) {
+        self.count_key("hash", expiry);
+    }
+
+    fn set(&mut self, _key: &[u8], _values: &[Vec<u8>], expiry: &Option<u64>) {
+        self.count_key("set", expiry);
+    }
+
+    fn list(&mut self, _key: &[u8], _values: &[Vec<u8>], expiry: &Option<u64>) {
+        self.count_key("list", expiry);
+    }
+
+    fn sorted_set(&mut self, _key: &[u8], _values: &[(f64, Vec<u8>)], expiry: &Option<u64>) {
+        self.count_key("sortedset", expiry);
+    }
+
+    fn start_collection(
+        &mut self,
+        _key: &[u8],
+        kind: CollectionKind,
+        _length: Option<u64>,
+        expiry: &Option<u64>,
+    ) {
+        let type_name = match kind {
+            CollectionKind::List => "list",
+            CollectionKind::Set => "set",
+            CollectionKind::SortedSet => "sortedset",
+            CollectionKind::Hash { .. } => "hash",
+        };
+        self.count_key(type_name, expiry);
+    }
+
+    fn stream(&mut self, _key: &[u8], _stream: &Stream, expiry: &Option<u64>) {
+        self.count_key("stream", expiry);
+    }
+
+    fn module(
+        &mut self,
+        _key: &[u8],
+        module: &ModuleId,
+        value: &ModuleValue,
+        expiry: &Option<u64>,
+    ) {
+        self.count_key("module", expiry);
+        let db = self.databases.entry(self.dbnum).or_default();
+        let stats = db.modules.entry(module.name.clone()).or_default();
+        stats.keys += 1;
+        for (name, field) in value.summary() {
+            if let ModuleField::UInt(val) = field {
+                if SUMMED_MODULE_PROPERTIES.contains(&name) {
+                    let total = stats.totals.entry(name).or_default();
+                    *total = total.saturating_add(val);
+                }
+            }
+        }
+    }
+}
@@ -77,33 +77,57 @@ use pyo3::exceptions::PyValueError;
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-use std::io::Read;
+use std::io::{Read, Seek};
+use std::ops::ControlFlow;
+use std::sync::mpsc;
+use std::thread;
 
 #[doc(hidden)]
-pub use types::{RdbError, RdbOk, RdbResult, Type};
+pub use types::{
+    Blob, BorrowedValue, CollectionElement, CollectionKind, ErrorLocation, FilteredKeys,
+    LossSummary, RdbError, RdbHeader, RdbOk, RdbResult, ServerFlavor, TruncationReport, Type,
+};
 
 pub mod constants;
+mod crc64;
 pub mod decoder;
 pub mod filter;
 pub mod formatter;
 pub mod types;
 
-pub use decoder::RdbDecoder;
+pub use decoder::{ModuleDecoder, ModuleReader, ParallelDecoder, RdbDecoder, SliceDecoder};
 pub use filter::{Filter, Simple};
 pub use formatter::{Formatter, FormatterType};
+use types::RdbValue;
 
 // Main entry point for parsing RDB files
 pub struct RdbParser<R: Read, L: Filter, F: Formatter> {
     decoder: RdbDecoder<R, L>,
     formatter: Option<F>,
+    skip_corrupt_keys: bool,
+    pipeline: Option<Pipeline<R, L, F>>,
 }
 
+// Parses with the decoder on a separate thread, set only for decoders that can
+// be sent to it, along with the number of values decoded ahead of the formatter
+type Pipeline<R, L, F> = (
+    fn(RdbDecoder<R, L>, Output<F>, usize) -> RdbResult<LossSummary>,
+    usize,
+);
+
 impl<R: Read, L: Filter, F: Formatter> RdbParser<R, L, F> {
     pub fn builder() -> RdbParserBuilder<R, L, F> {
         RdbParserBuilder {
             reader: None,
             filter: None,
             formatter: None,
+            module_decoders: Vec::new(),
+            capture_module_payloads: false,
+            verify_checksum: false,
+            skip_corrupt_keys: false,
+            stream_elements: false,
+            enable_seek: None,
+            pipeline: None,
         }
     }
 
@@ -112,11 +136,21 @@ impl<R: Read, L: Filter, F: Formatter> RdbParser<R, L, F> {
     //}
 }
 
+// Enables seeking on a decoder, set only for seekable readers
+type EnableSeek<R, L> = fn(&mut RdbDecoder<R, L>) -> RdbResult<()>;
+
 #[derive(Default)]
 pub struct RdbParserBuilder<R: Read, L: Filter, F: Formatter> {
     reader: Option<R>,
     filter: Option<L>,
     formatter: Option<F>,
+    module_decoders: Vec<Box<dyn ModuleDecoder>>,
+    capture_module_payloads: bool,
+    verify_checksum: bool,
+    skip_corrupt_keys: bool,
+    stream_elements: bool,
+    enable_seek: Option<EnableSeek<R, L>>,
+    pipeline: Option<Pipeline<R, L, F>>,
 }
 
 impl<R: Read, L: Filter + Default, F: Formatter> RdbParserBuilder<R, L, F> {
@@ -124,9 +158,23 @@ impl<R: Read, L: Filter + Default, F: Formatter> RdbParserBuilder<R, L, F> {
         let reader = self.reader.unwrap();
         let filter = self.filter.unwrap_or_default();
         let formatter = self.formatter;
+        let mut decoder = RdbDecoder::new(reader, filter).unwrap();
+        if let Some(enable_seek) = self.enable_seek {
+            // Readers that cannot seek, such as pipes, are read sequentially
+            let _ = enable_seek(&mut decoder);
+        }
+        for module_decoder in self.module_decoders {
+            decoder.add_module_decoder(module_decoder);
+        }
+        decoder.capture_module_payloads(self.capture_module_payloads);
+        decoder.verify_checksum(self.verify_checksum);
+        decoder.skip_corrupt_keys(self.skip_corrupt_keys);
+        decoder.stream_elements(self.stream_elements);
         RdbParser {
-            decoder: RdbDecoder::new(reader, filter).unwrap(),
+            decoder,
             formatter,
+            skip_corrupt_keys: self.skip_corrupt_keys,
+            pipeline: self.pipeline,
         }
     }
 
@@ -135,6 +183,18 @@ impl<R: Read, L: Filter + Default, F: Formatter> RdbParserBuilder<R, L, F> {
         self
     }
 
+    /// Reads from a seekable reader, seeking over the values of filtered
+    /// keys, see `RdbDecoder::skip_by_seeking`. Falls back to reading them
+    /// when seeking fails, such as on a pipe opened as a file.
+    pub fn with_seekable_reader(mut self, reader: R) -> Self
+    where
+        R: Seek,
+    {
+        self.reader = Some(reader);
+        self.enable_seek = Some(RdbDecoder::skip_by_seeking);
+        self
+    }
+
     pub fn with_filter(mut self, filter: L) -> Self {
         self.filter = Some(filter);
         self
@@ -144,18 +204,183 @@ impl<R: Read, L: Filter + Default, F: Formatter> RdbParserBuilder<R, L, F> {
         self.formatter = Some(formatter);
         self
     }
+
+    pub fn with_module_decoder<D: ModuleDecoder + 'static>(mut self, decoder: D) -> Self {
+        self.module_decoders.push(Box::new(decoder));
+        self
+    }
+
+    pub fn with_module_payloads(mut self, capture: bool) -> Self {
+        self.capture_module_payloads = capture;
+        self
+    }
+
+    pub fn with_checksum_verification(mut self, verify: bool) -> Self {
+        self.verify_checksum = verify;
+        self
+    }
+
+    /// Reports keys failing to decode to the formatter and goes on with the
+    /// next key, see `RdbDecoder::skip_corrupt_keys`
+    pub fn with_skip_corrupt_keys(mut self, skip: bool) -> Self {
+        self.skip_corrupt_keys = skip;
+        self
+    }
+
+    /// Passes collections to the formatter element by element, see
+    /// `RdbDecoder::stream_elements`
+    pub fn with_element_streaming(mut self, enabled: bool) -> Self {
+        self.stream_elements = enabled;
+        self
+    }
+
+    /// Decodes on a separate thread while the formatter runs on the calling
+    /// one, with up to `capacity` values decoded ahead of the formatter
+    pub fn with_pipelining(mut self, capacity: usize) -> Self
+    where
+        R: Send,
+        L: Send,
+    {
+        self.pipeline = Some((parse_pipelined, capacity));
+        self
+    }
 }
 
 impl<R: Read, L: Filter, F: Formatter> RdbParser<R, L, F> {
-    pub fn parse(self) -> RdbResult<()> {
-        if let Some(mut formatter) = self.formatter {
-            formatter.start_rdb();
-            for value in self.decoder {
-                formatter.format(&value?)?;
+    /// Version and server flavor of the dump
+    pub fn header(&self) -> RdbHeader {
+        self.decoder.header()
+    }
+
+    /// Parses the dump, returning what was lost when corrupt keys are skipped
+    pub fn parse(self) -> RdbResult<LossSummary> {
+        let mut decoder = self.decoder;
+        let Some(formatter) = self.formatter else {
+            return Ok(LossSummary::default());
+        };
+        let mut output = Output::new(formatter, self.skip_corrupt_keys);
+        if let Some((parse_pipelined, capacity)) = self.pipeline {
+            return parse_pipelined(decoder, output, capacity);
+        }
+        while let Some(value) = decoder.next() {
+            if output.push(value, !decoder.is_finished()).is_break() {
+                break;
             }
-            formatter.end_rdb();
         }
-        Ok(())
+        output.finish(decoder.truncation().cloned())
+    }
+}
+
+fn parse_pipelined<R: Read + Send, L: Filter + Send, F: Formatter>(
+    mut decoder: RdbDecoder<R, L>,
+    mut output: Output<F>,
+    capacity: usize,
+) -> RdbResult<LossSummary> {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let truncation = thread::scope(|scope| {
+        let decoding = scope.spawn(move || {
+            while let Some(value) = decoder.next() {
+                // The formatter stops receiving once parsing has to stop
+                if sender.send((value, !decoder.is_finished())).is_err() {
+                    break;
+                }
+            }
+            decoder.truncation().cloned()
+        });
+
+        let receiver = receiver;
+        for (value, recoverable) in receiver.iter() {
+            if output.push(value, recoverable).is_break() {
+                break;
+            }
+        }
+        drop(receiver);
+        decoding
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+    output.finish(truncation)
+}
+
+// Passes decoded values to the formatter, keeping track of what could not be read
+pub(crate) struct Output<F: Formatter> {
+    formatter: F,
+    skip_corrupt_keys: bool,
+    summary: LossSummary,
+    in_collection: bool,
+    // Failure to write the output, which ends parsing right away
+    format_error: Option<RdbError>,
+}
+
+impl<F: Formatter> Output<F> {
+    pub fn new(mut formatter: F, skip_corrupt_keys: bool) -> Output<F> {
+        formatter.start_rdb();
+        Output {
+            formatter,
+            skip_corrupt_keys,
+            summary: LossSummary::default(),
+            in_collection: false,
+            format_error: None,
+        }
+    }
+
+    /// Formats a value, or reports a key failing to decode if decoding can
+    /// go on after it. Breaks once parsing has to stop.
+    pub fn push(&mut self, value: RdbResult<RdbValue>, recoverable: bool) -> ControlFlow<()> {
+        match value {
+            Ok(value) => {
+                match value {
+                    RdbValue::StartCollection { .. } => self.in_collection = true,
+                    RdbValue::EndCollection => self.in_collection = false,
+                    _ => {}
+                }
+                if let Err(err) = self.formatter.format(&value) {
+                    self.format_error = Some(err.into());
+                    return ControlFlow::Break(());
+                }
+            }
+            Err(err) if self.skip_corrupt_keys && recoverable => {
+                self.formatter.skipped_key(&err);
+                self.summary.skipped_keys.push(err);
+            }
+            Err(err) => {
+                self.summary.fatal_error = Some(err);
+                return ControlFlow::Break(());
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Ends the output once decoding stopped, failing on the error that
+    /// stopped it unless corrupt keys are skipped
+    pub fn finish(mut self, truncation: Option<TruncationReport>) -> RdbResult<LossSummary> {
+        if let Some(err) = self.format_error {
+            return Err(err);
+        }
+        let skip_corrupt_keys = self.skip_corrupt_keys;
+        let mut summary = self.summary;
+
+        // All keys before the end of a truncated dump are complete,
+        // so their output is finished before reporting the truncation
+        summary.truncation = truncation;
+        match summary.fatal_error.take() {
+            Some(err) if !skip_corrupt_keys && summary.truncation.is_none() => return Err(err),
+            fatal_error => summary.fatal_error = fatal_error,
+        }
+        // A streamed collection cut off by the error ends with the elements read
+        if self.in_collection {
+            self.formatter.format(&RdbValue::EndCollection)?;
+        }
+        if skip_corrupt_keys || summary.truncation.is_some() {
+            self.formatter.loss_summary(&summary);
+        }
+        self.formatter.end_rdb();
+        if !skip_corrupt_keys {
+            if let Some(err) = summary.fatal_error.take() {
+                return Err(err);
+            }
+        }
+        Ok(summary)
     }
 }
 
@@ -169,7 +394,8 @@ pub fn parse<R: Read, L: Filter + Default, F: Formatter>(
         .with_filter(filter)
         .with_formatter(formatter)
         .build();
-    parser.parse()
+    parser.parse()?;
+    Ok(())
 }
 
 #[cfg(feature = "python")]
@@ -198,8 +424,10 @@ impl PyRdbDecoder {
             filter.add_type(t);
         }
 
-        let decoder = RdbDecoder::new(file, filter)
+        let mut decoder = RdbDecoder::new(file, filter)
             .map_err(|e| PyValueError::new_err(format!("Failed to create decoder: {}", e)))?;
+        // Files that cannot seek, such as named pipes, are read sequentially
+        let _ = decoder.skip_by_seeking();
 
         Ok(PyRdbDecoder { decoder })
     }
@@ -1,9 +1,12 @@
 use clap::Parser;
 use regex::Regex;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::path::PathBuf;
 
+// Values decoded ahead of the formatter with --pipelined
+const PIPELINE_CAPACITY: usize = 1024;
+
 #[derive(Parser)]
 #[command(name = "rdb")]
 #[command(override_usage = "rdb [options] dump.rdb")]
@@ -11,7 +14,7 @@ struct Cli {
     /// Path to the RDB dump file
     dump_file: PathBuf,
 
-    /// Format to output. Valid: json, plain, nil, protocol
+    /// Format to output. Valid: json, plain, nil, protocol, stats
     #[arg(short, long, value_name = "FORMAT")]
     format: Option<String>,
 
@@ -30,6 +33,44 @@ struct Cli {
     /// Output file path. If not specified, writes to stdout
     #[arg(short = 'o', long = "output", value_name = "FILE")]
     output: Option<PathBuf>,
+
+    /// Verify the CRC64 checksum at the end of the dump
+    #[arg(long = "verify-checksum")]
+    verify_checksum: bool,
+
+    /// Skip keys that fail to decode and report them on stderr. Exits with
+    /// status 2 if keys were skipped, 3 if the dump could not be read to its end
+    #[arg(long = "skip-corrupt-keys")]
+    skip_corrupt_keys: bool,
+
+    /// Write collections element by element, keeping memory bounded for huge keys
+    #[arg(long = "stream-elements")]
+    stream_elements: bool,
+
+    /// Decode keys on the given number of threads. Reads the whole dump into memory,
+    /// use --stream-elements instead to bound memory use
+    #[arg(long = "threads", value_name = "N", conflicts_with = "stream_elements")]
+    threads: Option<usize>,
+
+    /// With --threads: write keys in the order they finish decoding
+    #[arg(long = "unordered", requires = "threads")]
+    unordered: bool,
+
+    /// Decode on a separate thread while writing the output
+    #[arg(long = "pipelined", conflicts_with = "threads")]
+    pipelined: bool,
+
+    /// JSON and plain formats: show the encoding of every key
+    #[arg(long = "show-encoding")]
+    show_encoding: bool,
+
+    /// JSON and plain formats: show the LRU idle time and LFU frequency of keys storing them
+    #[arg(long = "show-access-metadata")]
+    show_access_metadata: bool,
+
+    /// Protocol format: restore keys with LRU/LFU metadata using RESTORE ... IDLETIME/FREQ
+    #[arg(long = "restore-access-metadata")]
+    restore_access_metadata: bool,
 }
 
 fn parse_type(type_str: &str) -> Option<rdb::Type> {
@@ -39,6 +80,8 @@ fn parse_type(type_str: &str) -> Option<rdb::Type> {
         "set" => Some(rdb::Type::Set),
         "sortedset" | "sorted-set" | "sorted_set" => Some(rdb::Type::SortedSet),
         "hash" => Some(rdb::Type::Hash),
+        "stream" => Some(rdb::Type::Stream),
+        "module" => Some(rdb::Type::Module),
         _ => None,
     }
 }
@@ -82,19 +125,86 @@ pub fn main() {
             std::process::exit(1);
         }
     };
-    let reader = BufReader::new(file);
+    let mut reader = BufReader::new(file);
 
     // Parse with the specified formatter
     let formatter: rdb::FormatterType = match cli.format.as_deref().unwrap_or("json") {
-        "json" => rdb::FormatterType::Json(rdb::formatter::JSON::new(cli.output)),
-        "plain" => rdb::FormatterType::Plain(rdb::formatter::Plain::new(cli.output)),
+        "json" => {
+            let mut json = rdb::formatter::JSON::new(cli.output);
+            json.show_encoding(cli.show_encoding);
+            json.show_access_metadata(cli.show_access_metadata);
+            rdb::FormatterType::Json(json)
+        }
+        "plain" => {
+            let mut plain = rdb::formatter::Plain::new(cli.output);
+            plain.show_encoding(cli.show_encoding);
+            plain.show_access_metadata(cli.show_access_metadata);
+            rdb::FormatterType::Plain(plain)
+        }
         "nil" => rdb::FormatterType::Nil(rdb::formatter::Nil::new(cli.output)),
-        "protocol" => rdb::FormatterType::Protocol(rdb::formatter::Protocol::new(cli.output)),
+        "protocol" => {
+            let mut protocol = rdb::formatter::Protocol::new(cli.output);
+            protocol.restore_access_metadata(cli.restore_access_metadata);
+            rdb::FormatterType::Protocol(protocol)
+        }
+        "stats" => rdb::FormatterType::Stats(rdb::formatter::Stats::new(cli.output)),
         f => {
             println!("Unknown format: {}\n", f);
             std::process::exit(1);
         }
     };
 
-    rdb::parse(reader, formatter, filter).expect("Failed to parse RDB file");
+    let summary = match cli.threads {
+        Some(threads) => {
+            let mut data = Vec::new();
+            if let Err(err) = reader.read_to_end(&mut data) {
+                println!("Failed to read file: {:?}\n", err);
+                std::process::exit(1);
+            }
+            rdb::ParallelDecoder::new(&data, filter).and_then(|mut decoder| {
+                decoder.threads(threads);
+                decoder.ordered(!cli.unordered);
+                decoder.verify_checksum(cli.verify_checksum);
+                decoder.skip_corrupt_keys(cli.skip_corrupt_keys);
+                decoder.parse(formatter)
+            })
+        }
+        None => {
+            let mut builder = rdb::RdbParser::builder()
+                .with_seekable_reader(reader)
+                .with_filter(filter)
+                .with_formatter(formatter)
+                .with_checksum_verification(cli.verify_checksum)
+                .with_skip_corrupt_keys(cli.skip_corrupt_keys)
+                .with_element_streaming(cli.stream_elements);
+            if cli.pipelined {
+                builder = builder.with_pipelining(PIPELINE_CAPACITY);
+            }
+            builder.build().parse()
+        }
+    };
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("Failed to parse RDB file: {}\n", err);
+            std::process::exit(1);
+        }
+    };
+
+    for err in &summary.skipped_keys {
+        eprintln!("Skipped key: {}", err);
+    }
+    if let Some(err) = &summary.fatal_error {
+        eprintln!("Stopped parsing: {}", err);
+    }
+    if let Some(truncation) = &summary.truncation {
+        eprintln!("{}", truncation);
+    }
+    if summary.fatal_error.is_some() || summary.truncation.is_some() {
+        std::process::exit(3);
+    }
+    if !summary.skipped_keys.is_empty() {
+        eprintln!("Corrupt keys skipped: {}", summary.skipped_keys.len());
+        std::process::exit(2);
+    }
 }
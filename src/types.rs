@@ -1,28 +1,262 @@
+use std::borrow::Cow;
+use std::fmt;
+
 use thiserror::Error;
 
 use indexmap::IndexMap;
 
-use crate::constants::encoding_type;
+use crate::constants::{encoding_type, version};
 
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 #[cfg(feature = "python")]
-use pyo3::types::PyDict;
+use pyo3::types::{PyDict, PyList};
+
+/// Position in the dump at which an error was detected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorLocation {
+    /// Absolute byte offset in the dump
+    pub offset: Option<u64>,
+    /// Database selected when the error occurred
+    pub database: Option<u32>,
+    /// Key of the value being decoded
+    pub key: Option<Vec<u8>>,
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        if let Some(database) = self.database {
+            write!(f, " in db {}", database)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, ", key {:?}", String::from_utf8_lossy(key))?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum RdbError {
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
-    #[error("No value found after {0}")]
-    MissingValue(&'static str),
-    #[error("Unknown encoding type: {0}")]
-    UnknownEncoding(u8),
-    #[error("Parsing error in {context}: {message}")]
-    ParsingError {
-        context: &'static str,
+    #[error("IO error{location}: {source}")]
+    Io {
+        source: std::io::Error,
+        location: ErrorLocation,
+    },
+    #[error(
+        "Not an RDB file, magic string is {:?}",
+        String::from_utf8_lossy(found)
+    )]
+    BadMagic {
+        found: Vec<u8>,
+        location: ErrorLocation,
+    },
+    #[error("Unsupported RDB version {found}")]
+    UnsupportedVersion {
+        found: String,
+        location: ErrorLocation,
+    },
+    #[error("Dump is truncated{location}")]
+    Truncated { location: ErrorLocation },
+    #[error("Corrupt {encoding}{location}: {message}")]
+    CorruptEncoding {
+        encoding: &'static str,
+        message: String,
+        location: ErrorLocation,
+    },
+    #[error("Failed to decompress LZF data{location}: {message}")]
+    Decompression {
         message: String,
+        location: ErrorLocation,
     },
+    #[error("{message}{location}")]
+    InvalidOperation {
+        operation: u8,
+        message: String,
+        location: ErrorLocation,
+    },
+    #[error("Unsupported content{location}: {message}")]
+    Unsupported {
+        message: String,
+        location: ErrorLocation,
+    },
+    #[error("Checksum mismatch: dump stores {expected:#018x}, data hashes to {actual:#018x}")]
+    ChecksumMismatch {
+        expected: u64,
+        actual: u64,
+        location: ErrorLocation,
+    },
+}
+
+impl From<std::io::Error> for RdbError {
+    fn from(source: std::io::Error) -> Self {
+        if source.kind() == std::io::ErrorKind::UnexpectedEof {
+            return RdbError::truncated();
+        }
+        RdbError::Io {
+            source,
+            location: ErrorLocation::default(),
+        }
+    }
+}
+
+impl RdbError {
+    /// Error for data that does not follow the given encoding,
+    /// meant for module decoders as well
+    pub fn corrupt(encoding: &'static str, message: impl Into<String>) -> Self {
+        RdbError::CorruptEncoding {
+            encoding,
+            message: message.into(),
+            location: ErrorLocation::default(),
+        }
+    }
+
+    pub(crate) fn truncated() -> Self {
+        RdbError::Truncated {
+            location: ErrorLocation::default(),
+        }
+    }
+
+    // Data missing from a blob read in full means that its encoding is
+    // corrupt, only running out of the dump itself is a truncation
+    pub(crate) fn within_blob(self, encoding: &'static str) -> Self {
+        match self {
+            RdbError::Truncated { .. } => RdbError::corrupt(encoding, "Entry overruns its blob"),
+            err => err,
+        }
+    }
+
+    pub(crate) fn unsupported(message: impl Into<String>) -> Self {
+        RdbError::Unsupported {
+            message: message.into(),
+            location: ErrorLocation::default(),
+        }
+    }
+
+    /// Stable name of the error category, e.g. for metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            RdbError::Io { .. } => "io",
+            RdbError::BadMagic { .. } => "bad_magic",
+            RdbError::UnsupportedVersion { .. } => "unsupported_version",
+            RdbError::Truncated { .. } => "truncated",
+            RdbError::CorruptEncoding { .. } => "corrupt_encoding",
+            RdbError::Decompression { .. } => "decompression",
+            RdbError::InvalidOperation { .. } => "invalid_operation",
+            RdbError::Unsupported { .. } => "unsupported",
+            RdbError::ChecksumMismatch { .. } => "checksum_mismatch",
+        }
+    }
+
+    /// Where in the dump the error was detected
+    pub fn location(&self) -> &ErrorLocation {
+        match self {
+            RdbError::Io { location, .. }
+            | RdbError::BadMagic { location, .. }
+            | RdbError::UnsupportedVersion { location, .. }
+            | RdbError::Truncated { location }
+            | RdbError::CorruptEncoding { location, .. }
+            | RdbError::Decompression { location, .. }
+            | RdbError::InvalidOperation { location, .. }
+            | RdbError::Unsupported { location, .. }
+            | RdbError::ChecksumMismatch { location, .. } => location,
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut ErrorLocation {
+        match self {
+            RdbError::Io { location, .. }
+            | RdbError::BadMagic { location, .. }
+            | RdbError::UnsupportedVersion { location, .. }
+            | RdbError::Truncated { location }
+            | RdbError::CorruptEncoding { location, .. }
+            | RdbError::Decompression { location, .. }
+            | RdbError::InvalidOperation { location, .. }
+            | RdbError::Unsupported { location, .. }
+            | RdbError::ChecksumMismatch { location, .. } => location,
+        }
+    }
+
+    /// Records where the error occurred, keeping a location set closer to its cause
+    pub(crate) fn at(mut self, offset: u64, database: Option<u32>, key: Option<&[u8]>) -> Self {
+        let location = self.location_mut();
+        if location.offset.is_none() {
+            location.offset = Some(offset);
+            location.database = database;
+        }
+        if location.key.is_none() {
+            location.key = key.map(|key| key.to_vec());
+        }
+        self
+    }
+}
+
+/// Number of keys left out by the filter, per reason
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilteredKeys {
+    /// Keys in a database not matching the filter
+    pub database: u64,
+    /// Keys of a type not matching the filter
+    pub value_type: u64,
+    /// Keys not matching the filter themselves
+    pub key: u64,
+}
+
+impl FilteredKeys {
+    pub fn total(&self) -> u64 {
+        self.database + self.value_type + self.key
+    }
+}
+
+/// What was lost parsing a damaged dump with corrupt keys skipped
+#[derive(Debug, Default)]
+pub struct LossSummary {
+    /// Errors of the skipped keys, each locating the key it occurred at
+    pub skipped_keys: Vec<RdbError>,
+    /// Error that ended parsing before the end of the dump
+    pub fatal_error: Option<RdbError>,
+    /// How far the dump could be read, if it is truncated
+    pub truncation: Option<TruncationReport>,
+}
+
+impl LossSummary {
+    /// Whether the whole dump was read without skipping any key
+    pub fn is_empty(&self) -> bool {
+        self.skipped_keys.is_empty() && self.fatal_error.is_none() && self.truncation.is_none()
+    }
+}
+
+/// How far a truncated dump could be read
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TruncationReport {
+    /// Offset at which the data ended
+    pub offset: u64,
+    /// Key whose value was cut off, if the data ended within a key
+    pub key: Option<Vec<u8>>,
+    /// Number of keys decoded completely
+    pub keys_read: u64,
+    /// Whether the EOF marker was read, the data then ends within the checksum following it
+    pub eof_marker: bool,
 }
+
+impl fmt::Display for TruncationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Data ends at offset {}", self.offset)?;
+        if let Some(key) = &self.key {
+            write!(f, " within key {:?}", String::from_utf8_lossy(key))?;
+        }
+        write!(f, " after {} complete keys", self.keys_read)?;
+        if !self.eof_marker {
+            write!(f, ", the EOF marker is missing")?;
+        } else {
+            write!(f, ", the checksum is incomplete")?;
+        }
+        Ok(())
+    }
+}
+
 pub type RdbResult<T> = Result<T, RdbError>;
 
 pub type RdbOk = RdbResult<()>;
@@ -45,7 +279,11 @@ impl Type {
             encoding_type::HASH
             | encoding_type::HASH_ZIPMAP
             | encoding_type::HASH_ZIPLIST
-            | encoding_type::HASH_LIST_PACK => Ok(Type::Hash),
+            | encoding_type::HASH_LIST_PACK
+            | encoding_type::HASH_METADATA_PRE_GA
+            | encoding_type::HASH_LIST_PACK_EX_PRE_GA
+            | encoding_type::HASH_METADATA
+            | encoding_type::HASH_LIST_PACK_EX => Ok(Type::Hash),
             encoding_type::LIST
             | encoding_type::LIST_ZIPLIST
             | encoding_type::LIST_QUICKLIST
@@ -61,13 +299,49 @@ impl Type {
             | encoding_type::STREAM_LIST_PACKS_2
             | encoding_type::STREAM_LIST_PACKS_3 => Ok(Type::Stream),
             encoding_type::MODULE | encoding_type::MODULE_2 => Ok(Type::Module),
-            _ => Err(RdbError::UnknownEncoding(enc_type)),
+            _ => Err(RdbError::InvalidOperation {
+                operation: enc_type,
+                message: "Unknown object type".to_string(),
+                location: ErrorLocation::default(),
+            }),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Server that wrote a dump
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ServerFlavor {
+    #[default]
+    Redis,
+    Valkey,
+}
+
+/// RDB version and server flavor of a dump.
+///
+/// The flavor is derived from the magic string and the version number
+/// and refined by the `valkey-ver` aux field, as Valkey 8 and older write
+/// the same header as Redis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdbHeader {
+    pub version: u32,
+    pub flavor: ServerFlavor,
+}
+
+impl Default for RdbHeader {
+    fn default() -> RdbHeader {
+        RdbHeader {
+            version: version::SUPPORTED_MAXIMUM,
+            flavor: ServerFlavor::Redis,
+        }
+    }
+}
+
+/// Encoding a value is stored with in the dump.
+/// Compact encodings carry the size of the ziplist, listpack, intset or
+/// zipmap in bytes, after decompression.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub enum EncodingType {
+    #[default]
     String,
     LinkedList,
     Hashtable,
@@ -79,6 +353,288 @@ pub enum EncodingType {
     Quicklist2,
     ZSet2,
     ListPack(u64),
+    StreamListPacks,
+    Module,
+}
+
+impl EncodingType {
+    /// Name of the encoding as written by the formatters
+    pub fn name(&self) -> &'static str {
+        match self {
+            EncodingType::String => "string",
+            EncodingType::LinkedList => "linkedlist",
+            EncodingType::Hashtable => "hashtable",
+            EncodingType::Skiplist => "skiplist",
+            EncodingType::Intset(_) => "intset",
+            EncodingType::Ziplist(_) => "ziplist",
+            EncodingType::Zipmap(_) => "zipmap",
+            EncodingType::Quicklist => "quicklist",
+            EncodingType::Quicklist2 => "quicklist2",
+            EncodingType::ZSet2 => "zset2",
+            EncodingType::ListPack(_) => "listpack",
+            EncodingType::StreamListPacks => "stream",
+            EncodingType::Module => "module",
+        }
+    }
+
+    /// Size in bytes of compact encodings
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            EncodingType::Intset(size)
+            | EncodingType::Ziplist(size)
+            | EncodingType::Zipmap(size)
+            | EncodingType::ListPack(size) => Some(*size),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+#[derive(Debug)]
+pub struct StreamEntry {
+    pub id: StreamId,
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+pub struct StreamPendingEntry {
+    pub id: StreamId,
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug)]
+pub struct StreamConsumer {
+    pub name: Vec<u8>,
+    pub seen_time: u64,
+    // Only stored by RDB_TYPE_STREAM_LISTPACKS_3 and newer
+    pub active_time: Option<u64>,
+    pub pending: Vec<StreamId>,
+}
+
+#[derive(Debug)]
+pub struct StreamConsumerGroup {
+    pub name: Vec<u8>,
+    pub last_delivered_id: StreamId,
+    // Only stored by RDB_TYPE_STREAM_LISTPACKS_2 and newer
+    pub entries_read: Option<u64>,
+    pub pending: Vec<StreamPendingEntry>,
+    pub consumers: Vec<StreamConsumer>,
+}
+
+#[derive(Debug)]
+pub struct Stream {
+    pub entries: Vec<StreamEntry>,
+    pub length: u64,
+    pub last_id: StreamId,
+    // Only stored by RDB_TYPE_STREAM_LISTPACKS_2 and newer
+    pub first_id: Option<StreamId>,
+    pub max_deleted_id: Option<StreamId>,
+    pub entries_added: Option<u64>,
+    pub groups: Vec<StreamConsumerGroup>,
+}
+
+/// Charset used to encode module type names into 64-bit module IDs
+const MODULE_TYPE_NAME_CHARSET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Module type of a module value, decoded from its 64-bit module ID.
+///
+/// The upper 54 bits hold the 9 character type name, 6 bits per character,
+/// the lower 10 bits hold the encoding version of the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleId {
+    pub name: String,
+    pub version: u64,
+}
+
+impl From<u64> for ModuleId {
+    fn from(module_id: u64) -> ModuleId {
+        let version = module_id & 1023;
+        let mut id = module_id >> 10;
+        let mut name = [0u8; 9];
+        for c in name.iter_mut().rev() {
+            *c = MODULE_TYPE_NAME_CHARSET[(id & 63) as usize];
+            id >>= 6;
+        }
+        ModuleId {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            version,
+        }
+    }
+}
+
+impl fmt::Display for ModuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.version)
+    }
+}
+
+/// A single value of a module payload, as stored by the module's
+/// `RedisModule_Save*` calls
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleField {
+    SInt(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    String(Vec<u8>),
+}
+
+/// Summary of a scalable Bloom filter (`MBbloom--`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    pub capacity: u64,
+    pub error_rate: f64,
+    pub items: u64,
+    pub filters: u64,
+    pub expansion: u64,
+    pub bytes: u64,
+}
+
+/// Summary of a Cuckoo filter (`MBbloomCF`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CuckooFilter {
+    pub capacity: u64,
+    pub bucket_size: u64,
+    pub items: u64,
+    pub deletes: u64,
+    pub filters: u64,
+    pub expansion: u64,
+    pub max_iterations: u64,
+}
+
+/// Summary of a Count-Min sketch (`CMSk-TYPE`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountMinSketch {
+    pub width: u64,
+    pub depth: u64,
+    pub count: u64,
+}
+
+/// Summary of a Top-K sketch (`TopK-TYPE`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopK {
+    pub k: u64,
+    pub width: u64,
+    pub depth: u64,
+    pub decay: f64,
+    pub items: Vec<Vec<u8>>,
+}
+
+/// Summary of a RedisTimeSeries series (`TSDB-TYPE`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    pub retention: u64,
+    pub chunk_size: u64,
+    pub labels: Vec<(Vec<u8>, Vec<u8>)>,
+    pub rules: u64,
+    pub chunks: u64,
+    pub samples: u64,
+    pub first_timestamp: Option<u64>,
+    pub last_timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleValue {
+    /// The payload was skipped without being decoded
+    Skipped,
+    /// The payload as a flat list of its opcode-typed values
+    Raw(Vec<ModuleField>),
+    /// Named properties produced by a registered `ModuleDecoder`
+    Custom(IndexMap<String, ModuleField>),
+    /// A RedisJSON document, serialized as JSON text
+    Json(String),
+    Bloom(BloomFilter),
+    Cuckoo(CuckooFilter),
+    CountMinSketch(CountMinSketch),
+    TopK(TopK),
+    TimeSeries(TimeSeries),
+}
+
+impl ModuleValue {
+    /// Scalar properties of the built-in module summaries, in display order.
+    /// The items of a Top-K sketch and the labels of a time series are not included.
+    pub fn summary(&self) -> Vec<(&'static str, ModuleField)> {
+        use self::ModuleField::{Double, UInt};
+
+        match self {
+            ModuleValue::Bloom(bloom) => vec![
+                ("capacity", UInt(bloom.capacity)),
+                ("error_rate", Double(bloom.error_rate)),
+                ("items", UInt(bloom.items)),
+                ("filters", UInt(bloom.filters)),
+                ("expansion", UInt(bloom.expansion)),
+                ("bytes", UInt(bloom.bytes)),
+            ],
+            ModuleValue::Cuckoo(cuckoo) => vec![
+                ("capacity", UInt(cuckoo.capacity)),
+                ("bucket_size", UInt(cuckoo.bucket_size)),
+                ("items", UInt(cuckoo.items)),
+                ("deletes", UInt(cuckoo.deletes)),
+                ("filters", UInt(cuckoo.filters)),
+                ("expansion", UInt(cuckoo.expansion)),
+                ("max_iterations", UInt(cuckoo.max_iterations)),
+            ],
+            ModuleValue::CountMinSketch(sketch) => vec![
+                ("width", UInt(sketch.width)),
+                ("depth", UInt(sketch.depth)),
+                ("count", UInt(sketch.count)),
+            ],
+            ModuleValue::TopK(topk) => vec![
+                ("k", UInt(topk.k)),
+                ("width", UInt(topk.width)),
+                ("depth", UInt(topk.depth)),
+                ("decay", Double(topk.decay)),
+            ],
+            ModuleValue::TimeSeries(series) => {
+                let mut summary = vec![
+                    ("retention", UInt(series.retention)),
+                    ("chunk_size", UInt(series.chunk_size)),
+                    ("rules", UInt(series.rules)),
+                    ("chunks", UInt(series.chunks)),
+                    ("samples", UInt(series.samples)),
+                ];
+                if let Some(first_timestamp) = series.first_timestamp {
+                    summary.push(("first_timestamp", UInt(first_timestamp)));
+                }
+                summary.push(("last_timestamp", UInt(series.last_timestamp)));
+                summary
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Per-key metadata stored in front of a key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyMeta {
+    /// Database the key is stored in
+    pub database: u32,
+    /// Expiry of the key, in milliseconds
+    pub expiry: Option<u64>,
+    /// LRU idle time in seconds, stored when an LRU eviction policy is used
+    pub idle: Option<u64>,
+    /// LFU access frequency counter, stored when an LFU eviction policy is used
+    pub freq: Option<u8>,
+    /// Encoding the value is stored with
+    pub encoding: EncodingType,
+    /// Offset of the first byte of the key's entry, including its expiry
+    /// and access metadata, from the start of the dump
+    pub offset: u64,
+    /// Length of the key's entry in bytes
+    pub length: u64,
 }
 
 #[derive(Debug)]
@@ -92,32 +648,449 @@ pub enum RdbValue {
         key: Vec<u8>,
         value: Vec<u8>,
     },
+    ModuleAux {
+        module: ModuleId,
+        // REDISMODULE_AUX_BEFORE_RDB (1) or REDISMODULE_AUX_AFTER_RDB (2)
+        when: u64,
+        payload: Vec<ModuleField>,
+    },
+    Function {
+        library_code: Vec<u8>,
+    },
+    SlotInfo {
+        slot_id: u64,
+        slot_size: u64,
+        expires_slot_size: u64,
+    },
     Checksum(Vec<u8>),
     String {
         key: Vec<u8>,
         value: Vec<u8>,
         expiry: Option<u64>,
+        meta: KeyMeta,
     },
     Hash {
         key: Vec<u8>,
         values: IndexMap<Vec<u8>, Vec<u8>>,
+        // Expiry of the fields with a TTL, in milliseconds
+        field_expiry: IndexMap<Vec<u8>, u64>,
         expiry: Option<u64>,
+        meta: KeyMeta,
     },
     Set {
         key: Vec<u8>,
         members: Vec<Vec<u8>>,
         expiry: Option<u64>,
+        meta: KeyMeta,
     },
     List {
         key: Vec<u8>,
         values: Vec<Vec<u8>>,
         expiry: Option<u64>,
+        meta: KeyMeta,
     },
     SortedSet {
         key: Vec<u8>,
         values: Vec<(f64, Vec<u8>)>, // (score, member)
         expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    Stream {
+        key: Vec<u8>,
+        stream: Stream,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    Module {
+        key: Vec<u8>,
+        module: ModuleId,
+        value: ModuleValue,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    /// Start of a collection whose elements follow one by one, when
+    /// elements are streamed (see `RdbDecoder::stream_elements`)
+    StartCollection {
+        key: Vec<u8>,
+        kind: CollectionKind,
+        // Number of elements, if stored in front of them
+        length: Option<u64>,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    Element(CollectionElement),
+    EndCollection,
+}
+
+/// Type of a collection streamed element by element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionKind {
+    List,
+    Set,
+    SortedSet,
+    /// `field_expiry` is set if fields of the hash may have a TTL
+    Hash {
+        field_expiry: bool,
+    },
+}
+
+/// Single element of a collection streamed element by element
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollectionElement {
+    /// Element of a list or member of a set
+    Member(Vec<u8>),
+    /// Field of a hash, with its expiry in milliseconds if it has a TTL
+    Field {
+        field: Vec<u8>,
+        value: Vec<u8>,
+        expires_at: Option<u64>,
+    },
+    /// Member of a sorted set
+    Scored { score: f64, member: Vec<u8> },
+}
+
+impl KeyMeta {
+    pub(crate) fn with_encoding(encoding: EncodingType) -> KeyMeta {
+        KeyMeta {
+            encoding,
+            ..Default::default()
+        }
+    }
+}
+
+impl RdbValue {
+    /// Key of key values, `None` for all other values
+    pub fn key(&self) -> Option<&[u8]> {
+        match self {
+            RdbValue::String { key, .. }
+            | RdbValue::Hash { key, .. }
+            | RdbValue::Set { key, .. }
+            | RdbValue::List { key, .. }
+            | RdbValue::SortedSet { key, .. }
+            | RdbValue::Stream { key, .. }
+            | RdbValue::Module { key, .. }
+            | RdbValue::StartCollection { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Metadata of key values, `None` for all other values
+    pub fn meta(&self) -> Option<&KeyMeta> {
+        match self {
+            RdbValue::String { meta, .. }
+            | RdbValue::Hash { meta, .. }
+            | RdbValue::Set { meta, .. }
+            | RdbValue::List { meta, .. }
+            | RdbValue::SortedSet { meta, .. }
+            | RdbValue::Stream { meta, .. }
+            | RdbValue::Module { meta, .. }
+            | RdbValue::StartCollection { meta, .. } => Some(meta),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn meta_mut(&mut self) -> Option<&mut KeyMeta> {
+        match self {
+            RdbValue::String { meta, .. }
+            | RdbValue::Hash { meta, .. }
+            | RdbValue::Set { meta, .. }
+            | RdbValue::List { meta, .. }
+            | RdbValue::SortedSet { meta, .. }
+            | RdbValue::Stream { meta, .. }
+            | RdbValue::Module { meta, .. }
+            | RdbValue::StartCollection { meta, .. } => Some(meta),
+            _ => None,
+        }
+    }
+}
+
+/// String read by `SliceDecoder`, borrowed from the dump where possible
+#[derive(Debug, Clone, PartialEq)]
+pub enum Blob<'a> {
+    /// String stored as is, or an integer stored in binary form
+    Bytes(Cow<'a, [u8]>),
+    /// LZF compressed string, only decompressed when its bytes are requested
+    Lzf {
+        compressed: &'a [u8],
+        // Length once decompressed
+        length: usize,
+    },
+}
+
+impl<'a> Blob<'a> {
+    /// Bytes of the string, decompressing it if needed
+    pub fn bytes(&self) -> RdbResult<Cow<'a, [u8]>> {
+        match self {
+            Blob::Bytes(Cow::Borrowed(bytes)) => Ok(Cow::Borrowed(bytes)),
+            blob => blob.clone().into_bytes(),
+        }
+    }
+
+    /// Bytes of the string, decompressing it if needed
+    pub fn into_bytes(self) -> RdbResult<Cow<'a, [u8]>> {
+        match self {
+            Blob::Bytes(bytes) => Ok(bytes),
+            Blob::Lzf { compressed, length } => {
+                crate::decoder::decompress_lzf(compressed, length).map(Cow::Owned)
+            }
+        }
+    }
+
+    /// Length of the string, after decompression
+    pub fn len(&self) -> usize {
+        match self {
+            Blob::Bytes(bytes) => bytes.len(),
+            Blob::Lzf { length, .. } => *length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Blob::Lzf { .. })
+    }
+}
+
+/// Value read by `SliceDecoder`, borrowing its strings from the dump.
+///
+/// Keys, hash fields and members of sets and sorted sets are decompressed
+/// as they are read, string values, list elements and hash values only when
+/// their bytes are requested. Entries of ziplists, listpacks and quicklists
+/// are borrowed too unless the blob holding them is compressed, integers
+/// and intset members are decoded into owned strings. Zipmaps, streams,
+/// module values and all entries other than keys are decoded into an owned
+/// `RdbValue`.
+#[derive(Debug)]
+pub enum BorrowedValue<'a> {
+    String {
+        key: Cow<'a, [u8]>,
+        value: Blob<'a>,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    Hash {
+        key: Cow<'a, [u8]>,
+        values: Vec<(Cow<'a, [u8]>, Blob<'a>)>,
+        // Expiry of the fields with a TTL, in milliseconds
+        field_expiry: IndexMap<Cow<'a, [u8]>, u64>,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    Set {
+        key: Cow<'a, [u8]>,
+        members: Vec<Cow<'a, [u8]>>,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    List {
+        key: Cow<'a, [u8]>,
+        values: Vec<Blob<'a>>,
+        expiry: Option<u64>,
+        meta: KeyMeta,
+    },
+    SortedSet {
+        key: Cow<'a, [u8]>,
+        values: Vec<(f64, Cow<'a, [u8]>)>, // (score, member)
+        expiry: Option<u64>,
+        meta: KeyMeta,
     },
+    Owned(RdbValue),
+}
+
+impl BorrowedValue<'_> {
+    /// Key of key values, `None` for all other values
+    pub fn key(&self) -> Option<&[u8]> {
+        match self {
+            BorrowedValue::String { key, .. }
+            | BorrowedValue::Hash { key, .. }
+            | BorrowedValue::Set { key, .. }
+            | BorrowedValue::List { key, .. }
+            | BorrowedValue::SortedSet { key, .. } => Some(key),
+            BorrowedValue::Owned(value) => value.key(),
+        }
+    }
+
+    /// Metadata of key values, `None` for all other values
+    pub fn meta(&self) -> Option<&KeyMeta> {
+        match self {
+            BorrowedValue::String { meta, .. }
+            | BorrowedValue::Hash { meta, .. }
+            | BorrowedValue::Set { meta, .. }
+            | BorrowedValue::List { meta, .. }
+            | BorrowedValue::SortedSet { meta, .. } => Some(meta),
+            BorrowedValue::Owned(value) => value.meta(),
+        }
+    }
+
+    pub(crate) fn meta_mut(&mut self) -> Option<&mut KeyMeta> {
+        match self {
+            BorrowedValue::String { meta, .. }
+            | BorrowedValue::Hash { meta, .. }
+            | BorrowedValue::Set { meta, .. }
+            | BorrowedValue::List { meta, .. }
+            | BorrowedValue::SortedSet { meta, .. } => Some(meta),
+            BorrowedValue::Owned(value) => value.meta_mut(),
+        }
+    }
+
+    /// Copies the value out of the dump, decompressing all its strings
+    pub fn into_owned(self) -> RdbResult<RdbValue> {
+        let value = match self {
+            BorrowedValue::String {
+                key,
+                value,
+                expiry,
+                meta,
+            } => RdbValue::String {
+                key: key.into_owned(),
+                value: value.into_bytes()?.into_owned(),
+                expiry,
+                meta,
+            },
+            BorrowedValue::Hash {
+                key,
+                values,
+                field_expiry,
+                expiry,
+                meta,
+            } => RdbValue::Hash {
+                key: key.into_owned(),
+                values: values
+                    .into_iter()
+                    .map(|(field, value)| {
+                        Ok((field.into_owned(), value.into_bytes()?.into_owned()))
+                    })
+                    .collect::<RdbResult<_>>()?,
+                field_expiry: field_expiry
+                    .into_iter()
+                    .map(|(field, expires_at)| (field.into_owned(), expires_at))
+                    .collect(),
+                expiry,
+                meta,
+            },
+            BorrowedValue::Set {
+                key,
+                members,
+                expiry,
+                meta,
+            } => RdbValue::Set {
+                key: key.into_owned(),
+                members: members.into_iter().map(Cow::into_owned).collect(),
+                expiry,
+                meta,
+            },
+            BorrowedValue::List {
+                key,
+                values,
+                expiry,
+                meta,
+            } => RdbValue::List {
+                key: key.into_owned(),
+                values: values
+                    .into_iter()
+                    .map(|value| Ok(value.into_bytes()?.into_owned()))
+                    .collect::<RdbResult<_>>()?,
+                expiry,
+                meta,
+            },
+            BorrowedValue::SortedSet {
+                key,
+                values,
+                expiry,
+                meta,
+            } => RdbValue::SortedSet {
+                key: key.into_owned(),
+                values: values
+                    .into_iter()
+                    .map(|(score, member)| (score, member.into_owned()))
+                    .collect(),
+                expiry,
+                meta,
+            },
+            BorrowedValue::Owned(value) => value,
+        };
+        Ok(value)
+    }
+}
+
+// Collections decoded into owned data keep their own variant
+impl From<RdbValue> for BorrowedValue<'_> {
+    fn from(value: RdbValue) -> Self {
+        match value {
+            RdbValue::String {
+                key,
+                value,
+                expiry,
+                meta,
+            } => BorrowedValue::String {
+                key: Cow::Owned(key),
+                value: Blob::Bytes(Cow::Owned(value)),
+                expiry,
+                meta,
+            },
+            RdbValue::Hash {
+                key,
+                values,
+                field_expiry,
+                expiry,
+                meta,
+            } => BorrowedValue::Hash {
+                key: Cow::Owned(key),
+                values: values
+                    .into_iter()
+                    .map(|(field, value)| (Cow::Owned(field), Blob::Bytes(Cow::Owned(value))))
+                    .collect(),
+                field_expiry: field_expiry
+                    .into_iter()
+                    .map(|(field, expires_at)| (Cow::Owned(field), expires_at))
+                    .collect(),
+                expiry,
+                meta,
+            },
+            RdbValue::Set {
+                key,
+                members,
+                expiry,
+                meta,
+            } => BorrowedValue::Set {
+                key: Cow::Owned(key),
+                members: members.into_iter().map(Cow::Owned).collect(),
+                expiry,
+                meta,
+            },
+            RdbValue::List {
+                key,
+                values,
+                expiry,
+                meta,
+            } => BorrowedValue::List {
+                key: Cow::Owned(key),
+                values: values
+                    .into_iter()
+                    .map(|value| Blob::Bytes(Cow::Owned(value)))
+                    .collect(),
+                expiry,
+                meta,
+            },
+            RdbValue::SortedSet {
+                key,
+                values,
+                expiry,
+                meta,
+            } => BorrowedValue::SortedSet {
+                key: Cow::Owned(key),
+                values: values
+                    .into_iter()
+                    .map(|(score, member)| (score, Cow::Owned(member)))
+                    .collect(),
+                expiry,
+                meta,
+            },
+            value => BorrowedValue::Owned(value),
+        }
+    }
 }
 
 #[cfg(feature = "python")]
@@ -131,61 +1104,156 @@ impl<'py> IntoPyObject<'py> for RdbValue {
             RdbValue::Hash {
                 key,
                 values,
+                field_expiry,
                 expiry,
+                meta,
             } => {
                 let dict = PyDict::new(py);
                 let values_dict = PyDict::new(py);
                 for (k, v) in values {
                     values_dict.set_item(k, v)?;
                 }
+                let field_expiry_dict = PyDict::new(py);
+                for (k, v) in field_expiry {
+                    field_expiry_dict.set_item(k, v)?;
+                }
                 dict.set_item("type", "hash")?;
                 dict.set_item("key", key)?;
                 dict.set_item("values", values_dict)?;
+                dict.set_item("field_expiry", field_expiry_dict)?;
                 dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
                 Ok(dict)
             }
             RdbValue::List {
                 key,
                 values,
                 expiry,
+                meta,
             } => {
                 let dict = PyDict::new(py);
                 dict.set_item("type", "list")?;
                 dict.set_item("key", key)?;
                 dict.set_item("values", values)?;
                 dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
                 Ok(dict)
             }
             RdbValue::Set {
                 key,
                 members,
                 expiry,
+                meta,
             } => {
                 let dict = PyDict::new(py);
                 dict.set_item("type", "set")?;
                 dict.set_item("key", key)?;
                 dict.set_item("members", members)?;
                 dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
                 Ok(dict)
             }
             RdbValue::SortedSet {
                 key,
                 values,
                 expiry,
+                meta,
             } => {
                 let dict = PyDict::new(py);
                 dict.set_item("type", "sorted_set")?;
                 dict.set_item("key", key)?;
                 dict.set_item("values", values)?;
                 dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
+                Ok(dict)
+            }
+            RdbValue::Stream {
+                key,
+                stream,
+                expiry,
+                meta,
+            } => {
+                let dict = PyDict::new(py);
+                let entries = stream
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry.id.to_string(), entry.fields))
+                    .collect::<Vec<_>>();
+                dict.set_item("type", "stream")?;
+                dict.set_item("key", key)?;
+                dict.set_item("entries", entries)?;
+                dict.set_item("length", stream.length)?;
+                dict.set_item("last_id", stream.last_id.to_string())?;
+                dict.set_item("first_id", stream.first_id.map(|id| id.to_string()))?;
+                dict.set_item(
+                    "max_deleted_id",
+                    stream.max_deleted_id.map(|id| id.to_string()),
+                )?;
+                dict.set_item("entries_added", stream.entries_added)?;
+                let groups = PyList::empty(py);
+                for group in stream.groups {
+                    let group_dict = PyDict::new(py);
+                    group_dict.set_item("name", group.name)?;
+                    group_dict
+                        .set_item("last_delivered_id", group.last_delivered_id.to_string())?;
+                    group_dict.set_item("entries_read", group.entries_read)?;
+                    let pending = group
+                        .pending
+                        .iter()
+                        .map(|nack| (nack.id.to_string(), nack.delivery_time, nack.delivery_count))
+                        .collect::<Vec<_>>();
+                    group_dict.set_item("pending", pending)?;
+                    let consumers = PyList::empty(py);
+                    for consumer in group.consumers {
+                        let consumer_dict = PyDict::new(py);
+                        consumer_dict.set_item("name", consumer.name)?;
+                        consumer_dict.set_item("seen_time", consumer.seen_time)?;
+                        consumer_dict.set_item("active_time", consumer.active_time)?;
+                        let pending = consumer
+                            .pending
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<_>>();
+                        consumer_dict.set_item("pending", pending)?;
+                        consumers.append(consumer_dict)?;
+                    }
+                    group_dict.set_item("consumers", consumers)?;
+                    groups.append(group_dict)?;
+                }
+                dict.set_item("groups", groups)?;
+                dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
+                Ok(dict)
+            }
+            RdbValue::Module {
+                key,
+                module,
+                value,
+                expiry,
+                meta,
+            } => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "module")?;
+                dict.set_item("key", key)?;
+                dict.set_item("module", module.name)?;
+                dict.set_item("version", module.version)?;
+                dict.set_item("value", module_value_into_py(py, value)?)?;
+                dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
                 Ok(dict)
             }
-            RdbValue::String { key, value, expiry } => {
+            RdbValue::String {
+                key,
+                value,
+                expiry,
+                meta,
+            } => {
                 let dict = PyDict::new(py);
                 dict.set_item("type", "string")?;
                 dict.set_item("key", key)?;
                 dict.set_item("value", value)?;
                 dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
                 Ok(dict)
             }
             RdbValue::SelectDb(db) => {
@@ -211,6 +1279,88 @@ impl<'py> IntoPyObject<'py> for RdbValue {
                 dict.set_item("value", value)?;
                 Ok(dict)
             }
+            RdbValue::ModuleAux {
+                module,
+                when,
+                payload,
+            } => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "module_aux")?;
+                dict.set_item("module", module.name)?;
+                dict.set_item("version", module.version)?;
+                dict.set_item("when", when)?;
+                dict.set_item(
+                    "payload",
+                    module_value_into_py(py, ModuleValue::Raw(payload))?,
+                )?;
+                Ok(dict)
+            }
+            RdbValue::Function { library_code } => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "function")?;
+                dict.set_item("library_code", library_code)?;
+                Ok(dict)
+            }
+            RdbValue::SlotInfo {
+                slot_id,
+                slot_size,
+                expires_slot_size,
+            } => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "slot_info")?;
+                dict.set_item("slot_id", slot_id)?;
+                dict.set_item("slot_size", slot_size)?;
+                dict.set_item("expires_slot_size", expires_slot_size)?;
+                Ok(dict)
+            }
+            RdbValue::StartCollection {
+                key,
+                kind,
+                length,
+                expiry,
+                meta,
+            } => {
+                let dict = PyDict::new(py);
+                let kind = match kind {
+                    CollectionKind::List => "list",
+                    CollectionKind::Set => "set",
+                    CollectionKind::SortedSet => "sorted_set",
+                    CollectionKind::Hash { .. } => "hash",
+                };
+                dict.set_item("type", "start_collection")?;
+                dict.set_item("key", key)?;
+                dict.set_item("kind", kind)?;
+                dict.set_item("length", length)?;
+                dict.set_item("expiry", expiry)?;
+                set_meta_items(&dict, &meta)?;
+                Ok(dict)
+            }
+            RdbValue::Element(element) => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "element")?;
+                match element {
+                    CollectionElement::Member(member) => dict.set_item("member", member)?,
+                    CollectionElement::Field {
+                        field,
+                        value,
+                        expires_at,
+                    } => {
+                        dict.set_item("field", field)?;
+                        dict.set_item("value", value)?;
+                        dict.set_item("expires_at", expires_at)?;
+                    }
+                    CollectionElement::Scored { score, member } => {
+                        dict.set_item("score", score)?;
+                        dict.set_item("member", member)?;
+                    }
+                }
+                Ok(dict)
+            }
+            RdbValue::EndCollection => {
+                let dict = PyDict::new(py);
+                dict.set_item("type", "end_collection")?;
+                Ok(dict)
+            }
             RdbValue::Checksum(checksum) => {
                 let dict = PyDict::new(py);
                 dict.set_item("type", "checksum")?;
@@ -220,3 +1370,98 @@ impl<'py> IntoPyObject<'py> for RdbValue {
         }
     }
 }
+
+#[cfg(feature = "python")]
+fn set_meta_items(dict: &Bound<'_, PyDict>, meta: &KeyMeta) -> PyResult<()> {
+    dict.set_item("database", meta.database)?;
+    dict.set_item("idle", meta.idle)?;
+    dict.set_item("freq", meta.freq)?;
+    dict.set_item("encoding", meta.encoding.name())?;
+    dict.set_item("encoding_size", meta.encoding.size())?;
+    dict.set_item("offset", meta.offset)?;
+    dict.set_item("length", meta.length)?;
+    Ok(())
+}
+
+#[cfg(feature = "python")]
+fn module_field_into_py(py: Python<'_>, field: ModuleField) -> PyResult<PyObject> {
+    let obj = match field {
+        ModuleField::SInt(val) => val.into_pyobject(py)?.into_any(),
+        ModuleField::UInt(val) => val.into_pyobject(py)?.into_any(),
+        ModuleField::Float(val) => val.into_pyobject(py)?.into_any(),
+        ModuleField::Double(val) => val.into_pyobject(py)?.into_any(),
+        ModuleField::String(val) => val.into_pyobject(py)?.into_any(),
+    };
+    Ok(obj.unbind())
+}
+
+#[cfg(feature = "python")]
+fn module_value_into_py(py: Python<'_>, value: ModuleValue) -> PyResult<PyObject> {
+    match value {
+        ModuleValue::Skipped => Ok(py.None()),
+        ModuleValue::Raw(fields) => {
+            let list = PyList::empty(py);
+            for field in fields {
+                list.append(module_field_into_py(py, field)?)?;
+            }
+            Ok(list.into_any().unbind())
+        }
+        ModuleValue::Custom(properties) => {
+            let dict = PyDict::new(py);
+            for (name, field) in properties {
+                dict.set_item(name, module_field_into_py(py, field)?)?;
+            }
+            Ok(dict.into_any().unbind())
+        }
+        ModuleValue::Json(document) => Ok(document.into_pyobject(py)?.into_any().unbind()),
+        ModuleValue::Bloom(bloom) => {
+            let dict = PyDict::new(py);
+            dict.set_item("capacity", bloom.capacity)?;
+            dict.set_item("error_rate", bloom.error_rate)?;
+            dict.set_item("items", bloom.items)?;
+            dict.set_item("filters", bloom.filters)?;
+            dict.set_item("expansion", bloom.expansion)?;
+            dict.set_item("bytes", bloom.bytes)?;
+            Ok(dict.into_any().unbind())
+        }
+        ModuleValue::Cuckoo(cuckoo) => {
+            let dict = PyDict::new(py);
+            dict.set_item("capacity", cuckoo.capacity)?;
+            dict.set_item("bucket_size", cuckoo.bucket_size)?;
+            dict.set_item("items", cuckoo.items)?;
+            dict.set_item("deletes", cuckoo.deletes)?;
+            dict.set_item("filters", cuckoo.filters)?;
+            dict.set_item("expansion", cuckoo.expansion)?;
+            dict.set_item("max_iterations", cuckoo.max_iterations)?;
+            Ok(dict.into_any().unbind())
+        }
+        ModuleValue::CountMinSketch(cms) => {
+            let dict = PyDict::new(py);
+            dict.set_item("width", cms.width)?;
+            dict.set_item("depth", cms.depth)?;
+            dict.set_item("count", cms.count)?;
+            Ok(dict.into_any().unbind())
+        }
+        ModuleValue::TopK(topk) => {
+            let dict = PyDict::new(py);
+            dict.set_item("k", topk.k)?;
+            dict.set_item("width", topk.width)?;
+            dict.set_item("depth", topk.depth)?;
+            dict.set_item("decay", topk.decay)?;
+            dict.set_item("items", topk.items)?;
+            Ok(dict.into_any().unbind())
+        }
+        ModuleValue::TimeSeries(series) => {
+            let dict = PyDict::new(py);
+            dict.set_item("retention", series.retention)?;
+            dict.set_item("chunk_size", series.chunk_size)?;
+            dict.set_item("labels", series.labels)?;
+            dict.set_item("rules", series.rules)?;
+            dict.set_item("chunks", series.chunks)?;
+            dict.set_item("samples", series.samples)?;
+            dict.set_item("first_timestamp", series.first_timestamp)?;
+            dict.set_item("last_timestamp", series.last_timestamp)?;
+            Ok(dict.into_any().unbind())
+        }
+    }
+}
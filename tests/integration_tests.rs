@@ -1,13 +1,16 @@
 use assert_cmd::Command;
 use pretty_assertions::assert_eq;
+use rdb::types::EncodingType;
 use rdb::{self, filter, formatter};
 use redis::Client;
 use rstest::rstest;
+use std::cell::Cell;
 use std::fs;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
 use testcontainers::ContainerAsync;
 use testcontainers_modules::{
     redis::Redis, testcontainers::runners::AsyncRunner, testcontainers::ImageExt,
@@ -81,6 +84,1135 @@ fn test_dump_matches_expected(#[files("tests/dumps/*.rdb")] path: PathBuf, #[cas
     );
 }
 
+#[rstest]
+#[case::redis("tests/dumps/hash_field_expiry.rdb", 12, rdb::ServerFlavor::Redis)]
+#[case::valkey("tests/dumps/valkey_rdb_80.rdb", 80, rdb::ServerFlavor::Valkey)]
+fn test_header_reports_version_and_flavor(
+    #[case] path: &str,
+    #[case] version: u32,
+    #[case] flavor: rdb::ServerFlavor,
+) {
+    let file = File::open(path).expect("Failed to open dump file");
+    let mut decoder = rdb::RdbDecoder::new(BufReader::new(file), filter::Simple::new())
+        .expect("Failed to read header");
+    assert_eq!(decoder.header().version, version);
+
+    for value in decoder.by_ref() {
+        value.expect("Failed to parse RDB file");
+    }
+    assert_eq!(decoder.header().flavor, flavor);
+}
+
+#[test]
+fn test_key_meta_is_reported_per_key() {
+    let file = File::open("tests/dumps/expiry_per_key.rdb").expect("Failed to open dump file");
+    let decoder = rdb::RdbDecoder::new(BufReader::new(file), filter::Simple::new())
+        .expect("Failed to read header");
+
+    let mut metas = Vec::new();
+    for value in decoder {
+        let value = value.expect("Failed to parse RDB file");
+        if let Some(meta) = value.meta() {
+            metas.push(meta.clone());
+        }
+    }
+
+    let expected = [
+        (0, Some(1893456000000), EncodingType::String, 11, 24),
+        (0, None, EncodingType::String, 35, 20),
+        (1, None, EncodingType::Hashtable, 57, 12),
+    ];
+    assert_eq!(metas.len(), expected.len());
+    for (meta, (database, expiry, encoding, offset, length)) in metas.iter().zip(expected) {
+        assert_eq!(meta.database, database);
+        assert_eq!(meta.expiry, expiry);
+        assert_eq!(meta.encoding, encoding);
+        assert_eq!(meta.offset, offset);
+        assert_eq!(meta.length, length);
+    }
+}
+
+#[rstest]
+fn test_checksum_verification_accepts_dump(#[files("tests/dumps/*.rdb")] path: PathBuf) {
+    let file = File::open(&path).expect("Failed to open dump file");
+    let mut decoder = rdb::RdbDecoder::new(BufReader::new(file), filter::Simple::new())
+        .expect("Failed to read header");
+    decoder.verify_checksum(true);
+    for value in decoder {
+        value.unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    }
+}
+
+// Decodes a possibly malformed dump to the end, errors are expected but panics are not
+fn decode_all(dump: &[u8]) {
+    if let Ok(decoder) = rdb::RdbDecoder::new(Cursor::new(dump), filter::Simple::new()) {
+        for value in decoder {
+            let _ = value;
+        }
+    }
+}
+
+#[rstest]
+fn test_malformed_dump_does_not_panic(#[files("tests/dumps/*.rdb")] path: PathBuf) {
+    let dump = fs::read(&path).expect("Failed to read dump file");
+    let step = (dump.len() / 256).max(1);
+
+    for length in (0..dump.len()).step_by(step) {
+        decode_all(&dump[..length]);
+    }
+    for position in (0..dump.len()).step_by(step) {
+        for flip in [0x01, 0x80, 0xff] {
+            let mut corrupted = dump.clone();
+            corrupted[position] ^= flip;
+            decode_all(&corrupted);
+        }
+    }
+}
+
+#[test]
+fn test_checksum_verification_rejects_corrupted_dump() {
+    let mut dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump file");
+    let position = dump
+        .windows(7)
+        .position(|window| window == b"forever")
+        .unwrap();
+    dump[position] = b'F';
+
+    let mut decoder = rdb::RdbDecoder::new(Cursor::new(dump), filter::Simple::new())
+        .expect("Failed to read header");
+    decoder.verify_checksum(true);
+    let error = decoder
+        .find_map(|value| value.err())
+        .expect("Corruption was not detected");
+    assert!(matches!(
+        error,
+        rdb::RdbError::ChecksumMismatch {
+            expected: 0xfc3a5f50477e0c5e,
+            ..
+        }
+    ));
+}
+
+fn first_error(dump: Vec<u8>) -> rdb::RdbError {
+    match rdb::RdbDecoder::new(Cursor::new(dump), filter::Simple::new()) {
+        Ok(mut decoder) => decoder
+            .find_map(|value| value.err())
+            .expect("Corruption was not detected"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn test_errors_report_category_and_location() {
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump file");
+
+    let error = first_error(dump[..50].to_vec());
+    assert!(matches!(error, rdb::RdbError::Truncated { .. }));
+    assert_eq!(error.name(), "truncated");
+    assert_eq!(
+        error.location(),
+        &rdb::ErrorLocation {
+            offset: Some(50),
+            database: Some(0),
+            key: Some(b"persistent".to_vec()),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Dump is truncated at offset 50 in db 0, key \"persistent\""
+    );
+
+    // Length byte of "forever" turned into an unknown string encoding
+    let mut corrupted = dump.clone();
+    corrupted[0x2f] = 0xc5;
+    let error = first_error(corrupted);
+    assert!(matches!(
+        error,
+        rdb::RdbError::CorruptEncoding {
+            encoding: "string",
+            ..
+        }
+    ));
+    assert_eq!(error.location().offset, Some(48));
+    assert_eq!(error.location().key.as_deref(), Some(&b"persistent"[..]));
+
+    let mut corrupted = dump.clone();
+    corrupted[..5].copy_from_slice(b"RADIS");
+    let error = first_error(corrupted);
+    assert!(matches!(error, rdb::RdbError::BadMagic { ref found, .. } if found == b"RADIS"));
+
+    let mut corrupted = dump.clone();
+    corrupted[5..9].copy_from_slice(b"0099");
+    let error = first_error(corrupted);
+    assert!(
+        matches!(error, rdb::RdbError::UnsupportedVersion { ref found, .. } if found == "0099")
+    );
+    assert_eq!(error.to_string(), "Unsupported RDB version 0099");
+}
+
+#[test]
+fn test_hash_listpack_with_odd_entries_is_corrupt() {
+    // Hash listpack "odd" holding a field without a value
+    let listpack = [0x09, 0, 0, 0, 0x01, 0, 0x81, b'f', 0xff];
+    let mut dump = b"REDIS0011\xfe\x00\x10\x03odd".to_vec();
+    dump.push(listpack.len() as u8);
+    dump.extend_from_slice(&listpack);
+    dump.extend_from_slice(&[0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    let error = first_error(dump);
+    assert_eq!(
+        error.to_string(),
+        "Corrupt listpack at offset 26 in db 0, key \"odd\": Odd number of listpack entries: 1"
+    );
+}
+
+fn decode_skipping_corrupt_keys(dump: Vec<u8>) -> (Vec<String>, Vec<rdb::RdbError>, bool) {
+    let mut decoder = rdb::RdbDecoder::new(Cursor::new(dump), filter::Simple::new())
+        .expect("Failed to read header");
+    decoder.skip_corrupt_keys(true);
+
+    let mut keys = Vec::new();
+    let mut errors = Vec::new();
+    for value in decoder.by_ref() {
+        match value {
+            Ok(value) => {
+                if let Some(key) = value.key() {
+                    keys.push(String::from_utf8_lossy(key).into_owned());
+                }
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    (keys, errors, decoder.is_finished())
+}
+
+#[test]
+fn test_skip_corrupt_keys() {
+    let dump = fs::read("tests/dumps/corrupt/corrupt_listpack.rdb").expect("Failed to read dump");
+
+    let (keys, errors, finished) = decode_skipping_corrupt_keys(dump.clone());
+    assert_eq!(keys, vec!["before", "after", "members"]);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0],
+        rdb::RdbError::CorruptEncoding {
+            encoding: "listpack",
+            ..
+        }
+    ));
+    assert_eq!(errors[0].location().key.as_deref(), Some(&b"broken"[..]));
+    assert!(finished);
+
+    // The value of "after" is cut off, nothing can be read past it
+    let (keys, errors, finished) = decode_skipping_corrupt_keys(dump[..0x42].to_vec());
+    assert_eq!(keys, vec!["before"]);
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[1], rdb::RdbError::Truncated { .. }));
+    assert!(finished);
+}
+
+#[test]
+fn test_corrupt_key_is_not_reported_as_truncation() {
+    let dump = fs::read("tests/dumps/corrupt/overrunning_listpack_entry.rdb")
+        .expect("Failed to read dump");
+    let keys_and_errors = |values: Vec<rdb::RdbResult<Option<Vec<u8>>>>| {
+        let (keys, errors): (Vec<_>, Vec<_>) = values.into_iter().partition(Result::is_ok);
+        let keys: Vec<_> = keys.into_iter().filter_map(Result::unwrap).collect();
+        let errors: Vec<_> = errors.into_iter().map(Result::unwrap_err).collect();
+        assert_eq!(keys, vec![b"good".to_vec()]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].name(), "corrupt_encoding");
+        assert_eq!(errors[0].location().key.as_deref(), Some(&b"bad"[..]));
+    };
+
+    let mut decoder = rdb::RdbDecoder::new(Cursor::new(&dump), filter::Simple::new()).unwrap();
+    decoder.skip_corrupt_keys(true);
+    keys_and_errors(
+        decoder
+            .by_ref()
+            .map(|value| value.map(|value| value.key().map(<[u8]>::to_vec)))
+            .collect(),
+    );
+    assert!(decoder.is_finished());
+    assert!(decoder.truncation().is_none());
+
+    let mut decoder = rdb::SliceDecoder::new(&dump, filter::Simple::new()).unwrap();
+    decoder.skip_corrupt_keys(true);
+    keys_and_errors(
+        decoder
+            .by_ref()
+            .map(|value| value.map(|value| value.key().map(|key| key.to_vec())))
+            .collect(),
+    );
+    assert!(decoder.is_finished());
+    assert!(decoder.truncation().is_none());
+
+    let mut decoder = rdb::ParallelDecoder::new(&dump, filter::Simple::new()).unwrap();
+    decoder.threads(2);
+    decoder.skip_corrupt_keys(true);
+    let summary = decoder
+        .parse(formatter::Nil::new(None))
+        .expect("Failed to parse RDB file");
+    assert_eq!(summary.skipped_keys.len(), 1);
+    assert_eq!(summary.skipped_keys[0].name(), "corrupt_encoding");
+    assert!(summary.fatal_error.is_none());
+    assert!(decoder.truncation().is_none());
+}
+
+#[test]
+fn test_parser_reports_skipped_keys() {
+    let output = PathBuf::from("/tmp/rdb_test_corrupt_listpack.stats");
+    let file = File::open("tests/dumps/corrupt/corrupt_listpack.rdb").expect("Failed to open dump");
+    let summary = rdb::RdbParser::builder()
+        .with_reader(BufReader::new(file))
+        .with_filter(filter::Simple::new())
+        .with_formatter(formatter::Stats::new(Some(output.clone())))
+        .with_skip_corrupt_keys(true)
+        .build()
+        .parse()
+        .expect("Failed to parse RDB file");
+
+    assert_eq!(summary.skipped_keys.len(), 1);
+    assert!(summary.fatal_error.is_none());
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        "db=0 keys=2 expires=0\n\
+         db=0 type=string keys=2\n\
+         db=1 keys=1 expires=0\n\
+         db=1 type=set keys=1\n\
+         skipped_keys=1\n"
+    );
+
+    let file = File::open("tests/dumps/corrupt/corrupt_listpack.rdb").expect("Failed to open dump");
+    let error = rdb::parse(
+        BufReader::new(file),
+        formatter::Nil::new(None),
+        filter::Simple::new(),
+    )
+    .unwrap_err();
+    assert_eq!(error.name(), "corrupt_encoding");
+}
+
+#[test]
+fn test_cli_exit_status_tells_skipped_keys_from_truncation() {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    let output = cmd
+        .args(["--format", "nil", "--skip-corrupt-keys"])
+        .arg("tests/dumps/corrupt/corrupt_listpack.rdb")
+        .assert()
+        .code(2)
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .ends_with("Corrupt keys skipped: 1\n"));
+
+    // The corrupt key is skipped and the key after it is written
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "json", "--skip-corrupt-keys"])
+        .arg("tests/dumps/corrupt/overrunning_listpack_entry.rdb")
+        .assert()
+        .code(2)
+        .stdout("[{\"good\":\"value\"}]\n")
+        .stderr(
+            "Skipped key: Corrupt listpack at offset 27 in db 0, key \"bad\": \
+             Entry overruns its blob\n\
+             Corrupt keys skipped: 1\n",
+        );
+
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").unwrap();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(file.path(), &dump[..69]).unwrap();
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "nil", "--skip-corrupt-keys"])
+        .arg(file.path())
+        .assert()
+        .code(3)
+        .stderr(
+            "Stopped parsing: Dump is truncated at offset 69 in db 1\n\
+             Data ends at offset 69 after 3 complete keys, the EOF marker is missing\n",
+        );
+}
+
+#[rstest]
+#[case::within_key(50, Some("persistent"), 1, false)]
+#[case::before_eof_marker(69, None, 3, false)]
+#[case::within_checksum(74, None, 3, true)]
+fn test_truncated_dump_is_reported(
+    #[case] length: usize,
+    #[case] key: Option<&str>,
+    #[case] keys_read: u64,
+    #[case] eof_marker: bool,
+) {
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump file");
+    let mut decoder =
+        rdb::RdbDecoder::new(Cursor::new(dump[..length].to_vec()), filter::Simple::new())
+            .expect("Failed to read header");
+
+    let keys = decoder
+        .by_ref()
+        .map_while(|value| value.ok())
+        .filter(|value| value.key().is_some())
+        .count();
+    assert_eq!(keys as u64, keys_read);
+    assert!(decoder.next().is_none());
+    assert_eq!(
+        decoder.truncation(),
+        Some(&rdb::TruncationReport {
+            offset: length as u64,
+            key: key.map(|key| key.as_bytes().to_vec()),
+            keys_read,
+            eof_marker,
+        })
+    );
+}
+
+#[test]
+fn test_truncated_dump_output_is_complete() {
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump file");
+    let output = PathBuf::from("/tmp/rdb_test_truncated.json");
+    let error = rdb::parse(
+        Cursor::new(dump[..50].to_vec()),
+        formatter::JSON::new(Some(output.clone())),
+        filter::Simple::new(),
+    )
+    .unwrap_err();
+
+    assert!(matches!(error, rdb::RdbError::Truncated { .. }));
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        "[{\"expiring\":\"soon\"}]\n"
+    );
+}
+
+#[test]
+fn test_filtered_keys_are_not_yielded() {
+    let mut filter = filter::Simple::new();
+    filter.add_keys(regex::Regex::new("^persistent$").unwrap());
+    let output = PathBuf::from("/tmp/rdb_test_filtered_keys.json");
+    let file = File::open("tests/dumps/expiry_per_key.rdb").expect("Failed to open dump file");
+    rdb::parse(
+        BufReader::new(file),
+        formatter::JSON::new(Some(output.clone())),
+        filter,
+    )
+    .expect("Failed to parse RDB file");
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        "[{\"persistent\":\"forever\"},{}]\n"
+    );
+}
+
+#[rstest]
+#[case::database(filter::Simple::add_database, 1, rdb::FilteredKeys { database: 2, ..Default::default() })]
+#[case::key_type(filter::Simple::add_type, rdb::Type::Set, rdb::FilteredKeys { value_type: 2, ..Default::default() })]
+#[case::key(filter::Simple::add_keys, regex::Regex::new("^p").unwrap(), rdb::FilteredKeys { key: 2, ..Default::default() })]
+fn test_filtered_keys_are_counted<T>(
+    #[case] add: fn(&mut filter::Simple, T),
+    #[case] criterion: T,
+    #[case] expected: rdb::FilteredKeys,
+) {
+    let mut filter = filter::Simple::new();
+    add(&mut filter, criterion);
+    let file = File::open("tests/dumps/expiry_per_key.rdb").expect("Failed to open dump file");
+    let mut decoder =
+        rdb::RdbDecoder::new(BufReader::new(file), filter).expect("Failed to read header");
+
+    let values: Vec<_> = decoder
+        .by_ref()
+        .collect::<Result<_, _>>()
+        .expect("Failed to decode");
+    // One SelectDb per database in the dump, none for the filtered keys
+    let selects = values
+        .iter()
+        .filter(|value| matches!(value, rdb::types::RdbValue::SelectDb(_)))
+        .count();
+    assert_eq!(selects, 2);
+    assert_eq!(
+        values.iter().filter(|value| value.key().is_some()).count(),
+        1
+    );
+    assert_eq!(decoder.filtered_keys(), expected);
+}
+
+#[rstest]
+#[case::database(filter::Simple::add_database, u32::MAX)]
+#[case::key(filter::Simple::add_keys, regex::Regex::new("^$").unwrap())]
+fn test_filtered_encodings_are_skipped<T: Clone>(
+    #[files("tests/dumps/*.rdb")] path: PathBuf,
+    #[case] add: fn(&mut filter::Simple, T),
+    #[case] criterion: T,
+) {
+    let dump = fs::read(&path).expect("Failed to read dump file");
+    let values: Vec<_> = rdb::RdbDecoder::new(Cursor::new(&dump), filter::Simple::new())
+        .expect("Failed to read header")
+        .collect::<Result<_, _>>()
+        .expect("Failed to decode");
+    let keys = values.iter().filter(|value| value.key().is_some()).count() as u64;
+    let expected: Vec<_> = values
+        .iter()
+        .filter(|value| value.key().is_none())
+        .map(|value| format!("{:?}", value))
+        .collect();
+
+    // Every key is left out, so that every encoding in the dump is skipped
+    let mut filter = filter::Simple::new();
+    add(&mut filter, criterion.clone());
+    let mut decoder = rdb::RdbDecoder::new(Cursor::new(&dump), filter).unwrap();
+    let values: Vec<_> = decoder
+        .by_ref()
+        .map(|value| format!("{:?}", value.expect("Failed to skip value")))
+        .collect();
+    assert_eq!(values, expected);
+    let filtered = decoder.filtered_keys();
+    assert_eq!(filtered.database + filtered.value_type + filtered.key, keys);
+
+    let mut filter = filter::Simple::new();
+    add(&mut filter, criterion);
+    let mut decoder = rdb::SliceDecoder::new(&dump, filter).unwrap();
+    let values: Vec<_> = decoder
+        .by_ref()
+        .map(|value| {
+            let value = value.and_then(|value| value.into_owned());
+            format!("{:?}", value.expect("Failed to skip value"))
+        })
+        .collect();
+    assert_eq!(values, expected);
+    let filtered = decoder.filtered_keys();
+    assert_eq!(filtered.database + filtered.value_type + filtered.key, keys);
+}
+
+// Reader counting the bytes read from it, but not those seeked over
+struct ReadCounter<R> {
+    inner: R,
+    read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for ReadCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.set(self.read.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for ReadCounter<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+// Dump with a large string in database 0 and a small one in database 1
+fn dump_with_large_value(length: u32) -> Vec<u8> {
+    let mut dump = b"REDIS0003\xfe\x00\x00\x05large\x80".to_vec();
+    dump.extend_from_slice(&length.to_be_bytes());
+    dump.resize(dump.len() + length as usize, b'x');
+    dump.extend_from_slice(b"\xfe\x01\x00\x05small\x05value\xff");
+    dump
+}
+
+fn decode_reader_filtered(dump: &[u8]) -> Vec<String> {
+    let mut filter = filter::Simple::new();
+    filter.add_database(1);
+    rdb::RdbDecoder::new(Cursor::new(dump), filter)
+        .expect("Failed to read header")
+        .map(|value| format!("{:?}", value))
+        .collect()
+}
+
+fn decode_seeking(dump: Vec<u8>, verify_checksum: bool) -> (Vec<String>, u64) {
+    let read = Rc::new(Cell::new(0));
+    let reader = ReadCounter {
+        inner: Cursor::new(dump),
+        read: read.clone(),
+    };
+    let mut filter = filter::Simple::new();
+    filter.add_database(1);
+    let mut decoder = rdb::RdbDecoder::new(reader, filter).expect("Failed to read header");
+    decoder.skip_by_seeking().expect("Failed to enable seeking");
+    decoder.verify_checksum(verify_checksum);
+    let values = decoder.map(|value| format!("{:?}", value)).collect();
+    (values, read.get())
+}
+
+#[test]
+fn test_filtered_values_are_seeked_over() {
+    let dump = dump_with_large_value(1 << 20);
+    let expected = decode_reader_filtered(&dump);
+
+    let (values, read) = decode_seeking(dump.clone(), false);
+    assert_eq!(values, expected);
+    assert!(read < 1024, "Read {} bytes", read);
+
+    // Skipped bytes are read when they are needed for the checksum
+    let (values, read) = decode_seeking(dump.clone(), true);
+    assert_eq!(values, expected);
+    assert_eq!(read, dump.len() as u64);
+
+    // Seeking does not go past the end of a truncated dump
+    let truncated = dump[..dump.len() / 2].to_vec();
+    let (values, _) = decode_seeking(truncated.clone(), false);
+    assert_eq!(values, decode_reader_filtered(&truncated));
+}
+
+// Reader that cannot seek, like a pipe opened as a file
+struct Unseekable<R>(R);
+
+impl<R: Read> Read for Unseekable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R> Seek for Unseekable<R> {
+    fn seek(&mut self, _pos: SeekFrom) -> std::io::Result<u64> {
+        Err(std::io::ErrorKind::NotSeekable.into())
+    }
+}
+
+#[test]
+fn test_unseekable_reader_is_read_sequentially() {
+    let output = PathBuf::from("/tmp/rdb_test_unseekable.plain");
+    let dump = dump_with_large_value(1 << 16);
+    let mut filter = filter::Simple::new();
+    filter.add_database(1);
+    rdb::RdbParser::builder()
+        .with_seekable_reader(Unseekable(Cursor::new(dump)))
+        .with_filter(filter)
+        .with_formatter(formatter::Plain::new(Some(output.clone())))
+        .build()
+        .parse()
+        .expect("Failed to parse RDB file");
+
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        "db=1 small -> value\n"
+    );
+}
+
+#[test]
+fn test_cli_reads_from_pipe() {
+    let dump = fs::read("tests/dumps/dictionary.rdb").expect("Failed to read dump");
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "plain", "/dev/stdin"])
+        .write_stdin(dump)
+        .assert()
+        .success()
+        .stdout(
+            fs::read_to_string("tests/dumps/plain/dictionary.plain")
+                .expect("Failed to read expected output"),
+        );
+}
+
+#[rstest]
+#[case::json(
+    "json",
+    "[{\"intset_16\":{\"value\":[\"32764\",\"32765\",\"32766\"],\
+     \"encoding\":\"intset\",\"encoding_size\":14}}]\n"
+)]
+#[case::plain(
+    "plain",
+    "db=0 intset_16 (encoding=intset, encoding_size=14)\n\
+     db=0 intset_16 { 32764 } \n\
+     db=0 intset_16 { 32765 } \n\
+     db=0 intset_16 { 32766 } \n"
+)]
+fn test_show_encoding(#[case] format: &str, #[case] expected: &str) {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args([
+        "--format",
+        format,
+        "--show-encoding",
+        "tests/dumps/intset_16.rdb",
+    ])
+    .assert()
+    .success()
+    .stdout(expected.to_string());
+}
+
+#[rstest]
+#[case::json(
+    "json",
+    "[{\"idle_string\":{\"value\":\"cold\",\"idle\":120},\
+     \"freq_list\":{\"value\":[\"a\",\"b\"],\"freq\":5}}]\n"
+)]
+#[case::plain(
+    "plain",
+    "aux redis-ver -> 5.0.0\n\
+     db=0 idle_string (idle=120)\n\
+     db=0 idle_string -> cold\n\
+     db=0 freq_list (freq=5)\n\
+     db=0 freq_list[0] -> a\n\
+     db=0 freq_list[1] -> b\n\
+     checksum bd1a3d1d901f6112\n"
+)]
+fn test_show_access_metadata(#[case] format: &str, #[case] expected: &str) {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args([
+        "--format",
+        format,
+        "--show-access-metadata",
+        "--keys",
+        "^(idle_string|freq_list)$",
+        "tests/dumps/key_access_metadata.rdb",
+    ])
+    .assert()
+    .success()
+    .stdout(expected.to_string());
+}
+
+#[test]
+fn test_stats_report_slot_info() {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "stats", "tests/dumps/cluster_slot_info.rdb"])
+        .assert()
+        .success()
+        .stdout(
+            "db=0 keys=3 expires=1\n\
+             db=0 type=string keys=3\n\
+             slot=6680 keys=1 expires=1\n\
+             slot=6777 keys=1 expires=0\n\
+             slot=10778 keys=1 expires=0\n",
+        );
+}
+
+#[test]
+fn test_stats_report_modules() {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "stats", "tests/dumps/redis_stack_modules.rdb"])
+        .assert()
+        .success()
+        .stdout(
+            "db=0 keys=4 expires=0\n\
+             db=0 type=module keys=4\n\
+             db=0 module=MBbloom-- keys=1 capacity=100 items=2 filters=1 bytes=128\n\
+             db=0 module=ReJSON-RL keys=1\n\
+             db=0 module=TSDB-TYPE keys=1 chunks=2 samples=300\n\
+             db=0 module=hellotype keys=1\n",
+        );
+}
+
+#[test]
+fn test_module_payloads_are_captured() {
+    let output = PathBuf::from("/tmp/rdb_test_module_payloads.plain");
+    let file = File::open("tests/dumps/redis_stack_modules.rdb").expect("Failed to open dump file");
+    rdb::RdbParser::builder()
+        .with_reader(BufReader::new(file))
+        .with_filter(filter::Simple::new())
+        .with_formatter(formatter::Plain::new(Some(output.clone())))
+        .with_module_payloads(true)
+        .build()
+        .parse()
+        .expect("Failed to parse RDB file");
+
+    // Modules with a decoder are decoded as before, the others are written field by field
+    let expected = load_expected(PathBuf::from("redis_stack_modules.rdb"), "plain").replace(
+        "db=0 greeting <hellotype/0>\n",
+        "db=0 greeting <hellotype/0> [0] -> 2\n\
+         db=0 greeting <hellotype/0> [1] -> -7\n\
+         db=0 greeting <hellotype/0> [2] -> hi\n\
+         db=0 greeting <hellotype/0> [3] -> 1.5\n",
+    );
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        expected
+    );
+}
+
+#[test]
+fn test_protocol_restores_access_metadata() {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    let output = cmd
+        .args([
+            "--format",
+            "protocol",
+            "--restore-access-metadata",
+            "tests/dumps/key_access_metadata.rdb",
+        ])
+        .output()
+        .expect("Failed to run rdb");
+    assert!(output.status.success());
+
+    // DUMP payload of the string "cold": type, value, RDB version and CRC64
+    let payload = b"\x00\x04cold\x09\x00\x27\xa1\xac\x0d\x13\xb7\xcc\x1c";
+    let mut restore = b"*7\r\n$7\r\nRESTORE\r\n$11\r\nidle_string\r\n$1\r\n0\r\n$16\r\n".to_vec();
+    restore.extend_from_slice(payload);
+    restore.extend_from_slice(b"\r\n$7\r\nREPLACE\r\n$8\r\nIDLETIME\r\n$3\r\n120\r\n");
+    assert!(output
+        .stdout
+        .windows(restore.len())
+        .any(|window| window == restore.as_slice()));
+
+    // Keys without access metadata are still written with regular commands
+    let set = b"*3\r\n$3\r\nSET\r\n$9\r\nplain_key\r\n$5\r\nplain\r\n";
+    assert!(output
+        .stdout
+        .windows(set.len())
+        .any(|window| window == set.as_slice()));
+}
+
+fn run_streamed(path: &Path, format: &str) -> Vec<u8> {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    let output = cmd
+        .args(["--format", format, "--stream-elements"])
+        .arg(path)
+        .output()
+        .expect("Failed to run rdb");
+    assert!(output.status.success());
+    output.stdout
+}
+
+#[rstest]
+#[case::json("json")]
+#[case::plain("plain")]
+fn test_streamed_elements_match_expected(
+    #[files("tests/dumps/*.rdb")] path: PathBuf,
+    #[case] format: &str,
+) {
+    let actual = String::from_utf8_lossy(&run_streamed(&path, format)).into_owned();
+    assert_eq!(
+        actual,
+        load_expected(path.clone(), format),
+        "Output doesn't match for {}",
+        path.display()
+    );
+}
+
+// Streamed hashes set the TTL of each field on its own, see below
+#[rstest]
+fn test_streamed_protocol_matches_expected(
+    #[files("tests/dumps/*.rdb")]
+    #[exclude("hash_field_expiry")]
+    path: PathBuf,
+) {
+    let actual = String::from_utf8_lossy(&run_streamed(&path, "protocol")).into_owned();
+    assert_eq!(
+        actual,
+        load_expected(path.clone(), "protocol"),
+        "Output doesn't match for {}",
+        path.display()
+    );
+}
+
+#[test]
+fn test_streamed_protocol_sets_field_ttls() {
+    let actual = run_streamed(Path::new("tests/dumps/hash_field_expiry.rdb"), "protocol");
+    let expected = "*4\r\n$4\r\nHSET\r\n$5\r\ncache\r\n$6\r\npage:3\r\n$5\r\n<div>\r\n\
+                    *6\r\n$10\r\nHPEXPIREAT\r\n$5\r\ncache\r\n$13\r\n1893456000000\r\n\
+                    $6\r\nFIELDS\r\n$1\r\n1\r\n$6\r\npage:3\r\n";
+    assert!(String::from_utf8_lossy(&actual).ends_with(expected));
+}
+
+#[test]
+fn test_decoder_streams_elements() {
+    let file = File::open("tests/dumps/quicklist_with_multiple_nodes.rdb").unwrap();
+    let mut decoder = rdb::RdbDecoder::new(BufReader::new(file), filter::Simple::new()).unwrap();
+    decoder.stream_elements(true);
+
+    let values: Vec<_> = decoder.map(|value| value.unwrap()).collect();
+    let start = values
+        .iter()
+        .position(|value| matches!(value, rdb::types::RdbValue::StartCollection { .. }))
+        .expect("Collection should be started");
+    let rdb::types::RdbValue::StartCollection {
+        kind, length, meta, ..
+    } = &values[start]
+    else {
+        unreachable!()
+    };
+    assert_eq!(*kind, rdb::CollectionKind::List);
+    assert_eq!(*length, None);
+    assert_eq!(meta.encoding, EncodingType::Quicklist);
+
+    let elements = values[start + 1..]
+        .iter()
+        .take_while(|value| matches!(value, rdb::types::RdbValue::Element(_)))
+        .count();
+    assert!(elements > 1);
+    assert!(matches!(
+        values[start + 1 + elements],
+        rdb::types::RdbValue::EndCollection
+    ));
+}
+
+#[test]
+fn test_truncated_streamed_collection_is_closed() {
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").unwrap();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    // Cut off after the length of the set "members", before its element
+    fs::write(file.path(), &dump[..68]).unwrap();
+
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    cmd.args(["--format", "json", "--stream-elements"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stdout("[{\"expiring\":\"soon\",\"persistent\":\"forever\"},{\"members\":[]}]\n")
+        .stderr(
+            "Failed to parse RDB file: Dump is truncated at offset 68 in db 1, key \"members\"\n\n",
+        );
+}
+
+fn decode_slice(data: &[u8]) -> Vec<String> {
+    let mut decoder = match rdb::SliceDecoder::new(data, filter::Simple::new()) {
+        Ok(decoder) => decoder,
+        Err(err) => return vec![format!("{:?}", err)],
+    };
+    decoder.verify_checksum(true);
+    decoder
+        .map(|value| format!("{:?}", value.and_then(|value| value.into_owned())))
+        .collect()
+}
+
+fn decode_reader(data: &[u8]) -> Vec<String> {
+    let mut decoder = match rdb::RdbDecoder::new(Cursor::new(data), filter::Simple::new()) {
+        Ok(decoder) => decoder,
+        Err(err) => return vec![format!("{:?}", err)],
+    };
+    decoder.verify_checksum(true);
+    decoder.map(|value| format!("{:?}", value)).collect()
+}
+
+#[rstest]
+fn test_slice_decoder_matches_rdb_decoder(#[files("tests/dumps/*.rdb")] path: PathBuf) {
+    let dump = fs::read(&path).unwrap();
+    assert_eq!(decode_slice(&dump), decode_reader(&dump));
+
+    // Errors are reported at the same position
+    let truncated = &dump[..dump.len() / 2];
+    assert_eq!(decode_slice(truncated), decode_reader(truncated));
+}
+
+#[test]
+fn test_slice_decoder_borrows_from_dump() {
+    let dump = fs::read("tests/dumps/rdb_version_5_with_checksum.rdb").unwrap();
+    let within_dump = |bytes: &[u8]| dump.as_ptr_range().contains(&bytes.as_ptr());
+
+    let mut keys = 0;
+    for value in rdb::SliceDecoder::new(&dump, filter::Simple::new()).unwrap() {
+        if let rdb::BorrowedValue::String { key, value, .. } = value.unwrap() {
+            assert!(matches!(key, std::borrow::Cow::Borrowed(key) if within_dump(key)));
+            assert!(!value.is_compressed());
+            keys += 1;
+        }
+    }
+    assert!(keys > 0);
+}
+
+#[rstest]
+#[case::list_ziplist("ziplist_that_doesnt_compress.rdb")]
+#[case::sorted_set_ziplist("parser_filters.rdb")]
+#[case::quicklist("quicklist_with_multiple_nodes.rdb")]
+#[case::listpacks("modern_encodings.rdb")]
+#[case::hash_listpack("valkey_rdb_80.rdb")]
+#[case::hash_listpack_ex("hash_field_expiry.rdb")]
+fn test_slice_decoder_borrows_compact_entries(#[case] dump: &str) {
+    use std::borrow::Cow;
+
+    let dump = fs::read(Path::new("tests/dumps").join(dump)).unwrap();
+    let within_dump = |bytes: &Cow<[u8]>| matches!(bytes, Cow::Borrowed(bytes) if dump.as_ptr_range().contains(&bytes.as_ptr()));
+    let is_compact = |meta: &rdb::types::KeyMeta| {
+        matches!(
+            meta.encoding,
+            EncodingType::Ziplist(_)
+                | EncodingType::ListPack(_)
+                | EncodingType::Quicklist
+                | EncodingType::Quicklist2
+        )
+    };
+
+    let mut entries = 0;
+    for value in rdb::SliceDecoder::new(&dump, filter::Simple::new()).unwrap() {
+        entries += match value.unwrap() {
+            rdb::BorrowedValue::List { values, meta, .. } if is_compact(&meta) => values
+                .iter()
+                .filter(|value| matches!(value, rdb::Blob::Bytes(bytes) if within_dump(bytes)))
+                .count(),
+            rdb::BorrowedValue::Set { members, meta, .. } if is_compact(&meta) => {
+                members.iter().filter(|member| within_dump(member)).count()
+            }
+            rdb::BorrowedValue::SortedSet { values, meta, .. } if is_compact(&meta) => values
+                .iter()
+                .filter(|(_, member)| within_dump(member))
+                .count(),
+            rdb::BorrowedValue::Hash { values, meta, .. } if is_compact(&meta) => values
+                .iter()
+                .filter(|(field, _)| within_dump(field))
+                .count(),
+            _ => 0,
+        };
+    }
+    assert!(entries > 0);
+}
+
+#[test]
+fn test_slice_decoder_decompresses_lazily() {
+    let dump = fs::read("tests/dumps/parser_filters.rdb").unwrap();
+    let value = rdb::SliceDecoder::new(&dump, filter::Simple::new())
+        .unwrap()
+        .map(|value| value.unwrap())
+        .find_map(|value| match value {
+            rdb::BorrowedValue::String { value, .. } if value.is_compressed() => Some(value),
+            _ => None,
+        })
+        .expect("Dump should contain a compressed string");
+
+    assert_eq!(value.bytes().unwrap().len(), value.len());
+    assert_ne!(value.len(), 0);
+}
+
+fn run_parallel(path: &Path, format: &str, args: &[&str]) -> String {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    let output = cmd
+        .args(["--format", format, "--threads", "4"])
+        .args(args)
+        .arg(path)
+        .output()
+        .expect("Failed to run rdb");
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[rstest]
+#[case::json("json")]
+#[case::plain("plain")]
+#[case::protocol("protocol")]
+fn test_parallel_output_matches_expected(
+    #[files("tests/dumps/*.rdb")] path: PathBuf,
+    #[case] format: &str,
+) {
+    assert_eq!(
+        run_parallel(&path, format, &[]),
+        load_expected(path.clone(), format),
+        "Output doesn't match for {}",
+        path.display()
+    );
+}
+
+#[rstest]
+fn test_unordered_parallel_output_has_all_keys(#[files("tests/dumps/*.rdb")] path: PathBuf) {
+    let sorted = |output: String| {
+        let mut lines: Vec<_> = output.lines().map(String::from).collect();
+        lines.sort();
+        lines
+    };
+    assert_eq!(
+        sorted(run_parallel(&path, "plain", &["--unordered"])),
+        sorted(load_expected(path, "plain"))
+    );
+}
+
+#[test]
+fn test_parallel_decoder_reports_losses() {
+    let dump = fs::read("tests/dumps/corrupt/corrupt_listpack.rdb").expect("Failed to read dump");
+    let mut decoder = rdb::ParallelDecoder::new(&dump, filter::Simple::new()).unwrap();
+    decoder.threads(2);
+    decoder.skip_corrupt_keys(true);
+    let summary = decoder
+        .parse(formatter::Nil::new(None))
+        .expect("Failed to parse RDB file");
+    assert_eq!(summary.skipped_keys.len(), 1);
+    assert!(summary.fatal_error.is_none());
+
+    let mut decoder = rdb::ParallelDecoder::new(&dump, filter::Simple::new()).unwrap();
+    let error = decoder.parse(formatter::Nil::new(None)).unwrap_err();
+    assert_eq!(error.name(), "corrupt_encoding");
+
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump");
+    let mut decoder = rdb::ParallelDecoder::new(&dump[..50], filter::Simple::new()).unwrap();
+    let error = decoder.parse(formatter::Nil::new(None)).unwrap_err();
+    assert!(matches!(error, rdb::RdbError::Truncated { .. }));
+    assert_eq!(
+        decoder.truncation(),
+        Some(&rdb::TruncationReport {
+            offset: 50,
+            key: Some(b"persistent".to_vec()),
+            keys_read: 1,
+            eof_marker: false,
+        })
+    );
+}
+
+#[rstest]
+#[case::json("json")]
+#[case::plain("plain")]
+#[case::protocol("protocol")]
+fn test_pipelined_output_matches_expected(
+    #[files("tests/dumps/*.rdb")] path: PathBuf,
+    #[case] format: &str,
+) {
+    let mut cmd = Command::cargo_bin("rdb").unwrap();
+    let output = cmd
+        .args(["--format", format, "--pipelined"])
+        .arg(&path)
+        .output()
+        .expect("Failed to run rdb");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        load_expected(path.clone(), format),
+        "Output doesn't match for {}",
+        path.display()
+    );
+}
+
+fn parse_pipelined<F: rdb::Formatter>(
+    dump: &[u8],
+    formatter: F,
+    skip_corrupt_keys: bool,
+) -> rdb::RdbResult<rdb::LossSummary> {
+    rdb::RdbParser::builder()
+        .with_reader(Cursor::new(dump))
+        .with_filter(filter::Simple::new())
+        .with_formatter(formatter)
+        .with_skip_corrupt_keys(skip_corrupt_keys)
+        .with_pipelining(1)
+        .build()
+        .parse()
+}
+
+// Formatter failing to write any key
+struct FailingFormatter;
+
+impl rdb::Formatter for FailingFormatter {
+    fn format(&mut self, value: &rdb::types::RdbValue) -> std::io::Result<()> {
+        if value.key().is_some() {
+            return Err(std::io::Error::other("disk full"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pipelined_parse_propagates_errors() {
+    let dump = fs::read("tests/dumps/corrupt/corrupt_listpack.rdb").expect("Failed to read dump");
+    let summary =
+        parse_pipelined(&dump, formatter::Nil::new(None), true).expect("Failed to parse RDB file");
+    assert_eq!(summary.skipped_keys.len(), 1);
+    assert!(summary.fatal_error.is_none());
+    let error = parse_pipelined(&dump, formatter::Nil::new(None), false).unwrap_err();
+    assert_eq!(error.name(), "corrupt_encoding");
+
+    let dump = fs::read("tests/dumps/expiry_per_key.rdb").expect("Failed to read dump");
+    let output = PathBuf::from("/tmp/rdb_test_truncated_pipelined.json");
+    let error = parse_pipelined(
+        &dump[..50],
+        formatter::JSON::new(Some(output.clone())),
+        false,
+    )
+    .unwrap_err();
+    assert!(matches!(error, rdb::RdbError::Truncated { .. }));
+    assert_eq!(
+        fs::read_to_string(&output).expect("Failed to read output"),
+        "[{\"expiring\":\"soon\"}]\n"
+    );
+
+    // The decoding thread stops once the formatter fails
+    let error = parse_pipelined(&dump, FailingFormatter, false).unwrap_err();
+    assert!(error.to_string().contains("disk full"), "{}", error);
+}
+
 async fn redis_client(major_version: u8, minor_version: u8) -> (Client, ContainerAsync<Redis>) {
     let container = Redis::default()
         .with_tag(format!("{}.{}-alpine", major_version, minor_version))
@@ -219,7 +1351,7 @@ async fn test_redis_protocol_reproducibility(#[case] major_version: u8, #[case]
 #[rstest]
 fn test_cli_commands_succeed(
     #[files("tests/dumps/*.rdb")] path: PathBuf,
-    #[values("json", "plain", "protocol")] format: &str,
+    #[values("json", "plain", "protocol", "stats")] format: &str,
     #[values("", "1")] databases: &str,
     #[values("", "hash", "set", "list", "sortedset", "string")] types: &str,
 ) {